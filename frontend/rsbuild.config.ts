@@ -39,6 +39,9 @@ export default defineConfig({
     server: {
         port: 8080,
         strictPort: true,
+        proxy: {
+            '/api': 'http://localhost:8888',
+        },
     },
     tools: {
         rspack: {
@@ -0,0 +1,203 @@
+mod structs;
+
+use std::sync::Arc;
+use axum::{extract::State, response::IntoResponse, routing::{get, post}, Router};
+use hyper::StatusCode;
+use super::extract::Json;
+use structs::{BackupResponse, JobsResponse, RateLimitExemptPayload, RateLimitsResponse, ReloadConfigurationResponse, RunJobPayload};
+use crate::{configuration::Configuration, error::{FieldError, Problem}, i18n::{tr, tr_args}, middleware::{AuthCheck, AuthLayer, RateLimitLayer}, services::JobInfo, state::AppState, Error, Role};
+
+pub fn admin_router(app_state: Arc<AppState>) -> Router
+{   
+    Router::new()      
+        .route("/admin/configuration", get(get_configuration)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/admin/configuration/reload", post(reload_configuration)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/admin/rate_limits", get(get_rate_limits)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/admin/rate_limits/exempt", post(add_rate_limit_exempt).delete(remove_rate_limit_exempt)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/admin/backup", post(backup)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/admin/jobs", get(get_jobs)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/admin/jobs/run", post(run_job)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .with_state(app_state.clone())
+}
+
+///Текущие действующие настройки
+#[utoipa::path(get, path = "/admin/configuration", tag = "admin",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Действующие настройки", body = Configuration)))]
+pub async fn get_configuration(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, Error>
+{
+    Ok((
+        StatusCode::OK,
+        Json(app_state.configuration.get().as_ref().clone())
+    ))
+}
+
+///Перечитать файл настроек, параметры требующие перезапуска не применяются
+#[utoipa::path(post, path = "/admin/configuration/reload", tag = "admin",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses(
+        (status = 200, description = "Настройки перечитаны", body = ReloadConfigurationResponse),
+        (status = 422, description = "Ошибка в файле настроек", body = Problem, content_type = "application/problem+json")))]
+pub async fn reload_configuration(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, Error>
+{
+    let changed = app_state.configuration.reload()?;
+    Ok((
+        StatusCode::OK,
+        Json(ReloadConfigurationResponse { changed })
+    ))
+}
+
+fn rate_limits_response(app_state: &AppState) -> RateLimitsResponse
+{
+    let cfg = app_state.configuration.get();
+    RateLimitsResponse
+    {
+        limits: cfg.rate_limits.clone(),
+        configured_exempt: cfg.rate_limit_exempt.clone(),
+        exempt: app_state.services.rate_limit_service.exempt()
+    }
+}
+
+///Действующие ограничения частоты запросов и клиенты, на которых они не распространяются
+#[utoipa::path(get, path = "/admin/rate_limits", tag = "admin",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Ограничения и доверенные клиенты", body = RateLimitsResponse)))]
+pub async fn get_rate_limits(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, Error>
+{
+    Ok((
+        StatusCode::OK,
+        Json(rate_limits_response(&app_state))
+    ))
+}
+
+///Снять ограничения с доверенного клиента до перезапуска сервера,
+/// постоянный список задается в настройке `rate_limit_exempt`
+#[utoipa::path(post, path = "/admin/rate_limits/exempt", tag = "admin",
+    request_body = RateLimitExemptPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Клиент добавлен", body = RateLimitsResponse)))]
+pub async fn add_rate_limit_exempt(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<RateLimitExemptPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    let client = payload.client.trim();
+    if client.is_empty()
+    {
+        return Err(Error::InvalidFields(vec![FieldError::new("client", tr("rate_limit_client_empty"))]));
+    }
+    app_state.services.rate_limit_service.add_exempt(client);
+    logger::info!("Клиент `{}` добавлен в список без ограничения частоты запросов", client);
+    Ok((
+        StatusCode::OK,
+        Json(rate_limits_response(&app_state))
+    ))
+}
+
+#[utoipa::path(delete, path = "/admin/rate_limits/exempt", tag = "admin",
+    request_body = RateLimitExemptPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Клиент удален", body = RateLimitsResponse)))]
+pub async fn remove_rate_limit_exempt(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<RateLimitExemptPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    if !app_state.services.rate_limit_service.remove_exempt(payload.client.trim())
+    {
+        return Err(Error::InvalidFields(vec![FieldError::new("client", tr_args("rate_limit_exempt_not_found", &[&payload.client]))]));
+    }
+    logger::info!("Клиент `{}` удален из списка без ограничения частоты запросов", &payload.client);
+    Ok((
+        StatusCode::OK,
+        Json(rate_limits_response(&app_state))
+    ))
+}
+
+///Резервная копия баз данных без остановки сервера, старые копии сверх `backup_retention` удаляются
+#[utoipa::path(post, path = "/admin/backup", tag = "admin",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Созданные резервные копии", body = BackupResponse)))]
+pub async fn backup(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, Error>
+{
+    let files = app_state.services.backup_service.backup().await?;
+    Ok((
+        StatusCode::OK,
+        Json(BackupResponse { files })
+    ))
+}
+
+///Периодические и отложенные задачи с результатом последнего запуска
+#[utoipa::path(get, path = "/admin/jobs", tag = "admin",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Задачи", body = JobsResponse)))]
+pub async fn get_jobs(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, Error>
+{
+    let jobs = app_state.services.job_service.list().await?;
+    Ok((
+        StatusCode::OK,
+        Json(JobsResponse { jobs })
+    ))
+}
+
+///Запуск задачи вне расписания, задача выполняется в фоне, результат виден в `/admin/jobs`
+#[utoipa::path(post, path = "/admin/jobs/run", tag = "admin",
+    request_body = RunJobPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses(
+        (status = 202, description = "Задача поставлена в очередь", body = JobInfo),
+        (status = 404, description = "Задача не найдена", body = Problem, content_type = "application/problem+json")))]
+pub async fn run_job(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<RunJobPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    let job = app_state.services.job_service.trigger(payload.id.trim()).await?;
+    logger::info!("Задача `{}` запущена администратором", &job.id);
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(job)
+    ))
+}
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::{configuration::RateLimit, services::{BackupFile, JobInfo}};
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct ReloadConfigurationResponse
+{
+    ///имена измененных параметров
+    pub changed: Vec<String>
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct RateLimitsResponse
+{
+    ///ограничения по группам маршрутов
+    pub limits: BTreeMap<String, RateLimit>,
+    ///клиенты без ограничений из настроек
+    pub configured_exempt: Vec<String>,
+    ///клиенты без ограничений, добавленные администратором
+    pub exempt: Vec<String>
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct BackupResponse
+{
+    pub files: Vec<BackupFile>
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct JobsResponse
+{
+    pub jobs: Vec<JobInfo>
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct RunJobPayload
+{
+    ///имя периодической задачи или id отложенной
+    pub id: String
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct RateLimitExemptPayload
+{
+    ///ip адрес, id пользователя или id сессии
+    pub client: String
+}
@@ -1,64 +1,77 @@
 mod structs;
 
 use std::{net::SocketAddr, sync::Arc};
-use axum::{body::Body, extract::{ConnectInfo, State}, response::{IntoResponse, Response}, routing::{get, post}, Extension, Json, Router};
+use axum::{body::Body, extract::{ConnectInfo, State}, response::{IntoResponse, Response}, routing::{get, post}, Extension, Router};
 use hyper::StatusCode;
-use structs::{LoginPayload, PasswordPayload, SessionPayload, UserUpdatePayload};
-use tower_http::trace::{DefaultMakeSpan, TraceLayer};
-use crate::{db::{UserDbo}, middleware::{AuthCheck, FingerprintExtractor, ResponseSessionWrapper, SessionExtension}, services::{AuthorizationInformation, Contact, UserInformation}, state::AppState, Error};
+use super::extract::Json;
+use structs::{AdminUserUpdatePayload, LoginPayload, PasswordPayload, PasswordlessLoginPayload, PasswordlessRequestPayload, PasswordlessRequestResponse, SessionPayload, UserUpdatePayload};
+use crate::{i18n::{tr, tr_args}, middleware::{AuthCheck, FingerprintExtractor, ResponseSessionWrapper, SessionExtension}, services::{AuthorizationInformation, Contact, Profile, UserInformation}, state::AppState, error::{FieldError, Problem}, Error};
 use crate::Role;
-use crate::middleware::AuthLayer;
+use crate::middleware::{AuthLayer, RateLimitLayer};
 
 
 
 pub fn authorization_router(app_state: Arc<AppState>) -> Router
 {   
     Router::new()      
-        .route("/auth/login", post(login))
+        .route("/auth/login", post(login)
+            .route_layer(RateLimitLayer::new("auth", Arc::clone(&app_state))))
+        .route("/auth/passwordless/request", post(passwordless_request)
+            .route_layer(RateLimitLayer::new("auth", Arc::clone(&app_state))))
+        .route("/auth/passwordless/login", post(passwordless_login)
+            .route_layer(RateLimitLayer::new("auth", Arc::clone(&app_state))))
 
         .route("/auth/update_key", get(update_access)
+            .route_layer(RateLimitLayer::new("session", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::Session,
                 Arc::clone(&app_state),
                 &[Role::User, Role::Administrator])))
 
         .route("/auth/change_password", post(change_password)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::All,
                 Arc::clone(&app_state),
                 &[Role::User, Role::Administrator])))
             
         .route("/auth/admin", get(admin_section)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::All,
                 Arc::clone(&app_state),
                 &[Role::Administrator])))
 
         .route("/auth/exit", get(exit)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::All,
                 Arc::clone(&app_state),
                 &[Role::User, Role::Administrator])))
 
         .route("/auth/exit_from", post(exit_from)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::All,
                 Arc::clone(&app_state),
                 &[Role::User, Role::Administrator])))
 
         .route("/auth/exit_all", get(exit_all)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::All,
                 Arc::clone(&app_state),
                 &[Role::User, Role::Administrator])))
             
         .route("/auth/update_user_info", post(update_user_info)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::All,
                 Arc::clone(&app_state),
                 &[Role::User, Role::Administrator])))
                 
         .route("/auth/update_user", post(update_user_info_by_admin)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
             .route_layer(AuthLayer::with_roles(
                 AuthCheck::All,
                 Arc::clone(&app_state),
@@ -66,9 +79,16 @@ pub fn authorization_router(app_state: Arc<AppState>) -> Router
 
         .with_state(app_state.clone())
         //.layer(crate::api::cors_layer(app_state.clone()))
-        .layer(TraceLayer::new_for_http().make_span_with(DefaultMakeSpan::default().include_headers(true)))
 }
 
+#[utoipa::path(post, path = "/auth/login", tag = "auth",
+    request_body = LoginPayload,
+    security(("fingerprint" = [])),
+    responses(
+        (status = 200, description = "Вход выполнен, cookie сессии установлена", body = UserInformation),
+        (status = 401, description = "Неверное имя пользователя или пароль", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Идентификатор входа соответствует нескольким пользователям", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Слишком много попыток входа, время ожидания в заголовке `Retry-After`", body = Problem, content_type = "application/problem+json")))]
 pub async fn login(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(app_state): State<Arc<AppState>>,
@@ -82,7 +102,7 @@ pub async fn login(
     if let Ok((user_info, session)) = user
     {
         logger::debug!("Юзер {} прошел авторизацию", &payload.login);
-        let session_wrapper = ResponseSessionWrapper::new(Arc::new(session), app_state.configuration.clone());
+        let session_wrapper = ResponseSessionWrapper::new(Arc::new(session), app_state.configuration.get());
         Ok((
             StatusCode::OK,
             session_wrapper,
@@ -94,6 +114,53 @@ pub async fn login(
         Err(user.err().unwrap())
     }
 }
+#[utoipa::path(post, path = "/auth/passwordless/request", tag = "auth",
+    request_body = PasswordlessRequestPayload,
+    security(("fingerprint" = [])),
+    responses(
+        (status = 200, description = "Код отправлен на подтвержденный контакт", body = PasswordlessRequestResponse),
+        (status = 403, description = "Вход без пароля отключен", body = Problem, content_type = "application/problem+json")))]
+pub async fn passwordless_request(
+    State(app_state): State<Arc<AppState>>,
+    FingerprintExtractor(fp): FingerprintExtractor,
+    Json(payload): Json<PasswordlessRequestPayload>) 
+-> Result<impl IntoResponse, Error>
+{
+    let request_id = app_state.services.passwordless_service.request(&payload.login, &fp).await?;
+    Ok((
+        StatusCode::OK,
+        Json(PasswordlessRequestResponse { request_id: request_id.to_string() }),
+    ))
+}
+#[utoipa::path(post, path = "/auth/passwordless/login", tag = "auth",
+    request_body = PasswordlessLoginPayload,
+    security(("fingerprint" = [])),
+    responses(
+        (status = 200, description = "Вход выполнен, cookie сессии установлена", body = UserInformation),
+        (status = 422, description = "Неверный или просроченный код", body = Problem, content_type = "application/problem+json")))]
+pub async fn passwordless_login(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(app_state): State<Arc<AppState>>,
+    FingerprintExtractor(fp): FingerprintExtractor,
+    Json(payload): Json<PasswordlessLoginPayload>) 
+-> Result<impl IntoResponse, Error>
+{
+    let ip = addr.ip().to_string();
+    let user = app_state.services.passwordless_service.redeem(&payload.request_id, &payload.code, &fp).await?;
+    let username = user.username.clone();
+    let (user_info, session) = app_state.services.user_service.start_session(user, &ip, &fp, &payload.device).await?;
+    logger::debug!("Юзер {} прошел авторизацию по одноразовому коду", &username);
+    let session_wrapper = ResponseSessionWrapper::new(Arc::new(session), app_state.configuration.get());
+    Ok((
+        StatusCode::OK,
+        session_wrapper,
+        Json(user_info),
+    ))
+}
+#[utoipa::path(post, path = "/auth/change_password", tag = "auth",
+    request_body = PasswordPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, description = "Пароль изменен", body = String, content_type = "text/plain")))]
 pub async fn change_password(
     State(app_state): State<Arc<AppState>>,
     Extension(session_wrapper): Extension<SessionExtension>,
@@ -107,6 +174,9 @@ pub async fn change_password(
     Ok(result.into_response())
 }
 
+#[utoipa::path(get, path = "/auth/admin", tag = "auth",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Проверка прав администратора", body = String, content_type = "text/plain")))]
 pub async fn admin_section(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(app_state): State<Arc<AppState>>,
@@ -116,9 +186,12 @@ pub async fn admin_section(
     let user = app_state.services.database_service.user_repository.get_user(&session_wrapper.session.user_id).await?;
     Ok((
         StatusCode::OK,
-        "вы зашли в админский роут",
+        tr("admin_section"),
     ).into_response())
 }
+#[utoipa::path(get, path = "/auth/exit", tag = "auth",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, description = "Текущая сессия завершена", body = String, content_type = "text/plain")))]
 pub async fn exit(
     State(app_state): State<Arc<AppState>>,
     Extension(session_wrapper): Extension<SessionExtension>) 
@@ -127,6 +200,10 @@ pub async fn exit(
     let result = app_state.services.user_service.exit_from_session(&session_wrapper.session.session_id).await?;
     Ok(result.into_response())
 }
+#[utoipa::path(post, path = "/auth/exit_from", tag = "auth",
+    request_body = SessionPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, description = "Сессия завершена", body = String, content_type = "text/plain")))]
 pub async fn exit_from(
     State(app_state): State<Arc<AppState>>,
     Json(payload): Json<SessionPayload>)
@@ -141,13 +218,13 @@ pub async fn exit_from(
     else 
     {
         logger::error!("Ошибка парсинга uid {}", &payload.session_id);
-        Ok((
-            StatusCode::BAD_REQUEST,
-            format!("id сессии {} не валиден", payload.session_id),
-        ).into_response())
+        Err(Error::InvalidFields(vec![FieldError::new("session_id", tr_args("invalid_session_id", &[&payload.session_id]))]))
     }
 }
 
+#[utoipa::path(get, path = "/auth/exit_all", tag = "auth",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, description = "Все сессии пользователя завершены", body = String, content_type = "text/plain")))]
 pub async fn exit_all(
     State(app_state): State<Arc<AppState>>,
     Extension(session_wrapper): Extension<SessionExtension>)
@@ -157,6 +234,9 @@ pub async fn exit_all(
     Ok(result.into_response())
 }
 
+#[utoipa::path(get, path = "/auth/update_key", tag = "auth",
+    security(("session_cookie" = [], "fingerprint" = [])),
+    responses((status = 200, description = "Новый ключ доступа", body = String, content_type = "text/plain")))]
 pub async fn update_access(
     ConnectInfo(_addr): ConnectInfo<SocketAddr>,
     Extension(session_wrapper): Extension<SessionExtension>,
@@ -165,7 +245,7 @@ pub async fn update_access(
 {
     //TODO надо ли проверить ip адрес? не всегда он будет совпадать так как везде почти динамический
     let key = app_state.services.user_service.update_access_key(&session_wrapper.session, &session_wrapper.fingerprint).await?;
-    let response_wrapper = ResponseSessionWrapper::new(session_wrapper.session, app_state.configuration.clone());
+    let response_wrapper = ResponseSessionWrapper::new(session_wrapper.session, app_state.configuration.get());
     Ok((
         StatusCode::OK,
         response_wrapper,
@@ -173,111 +253,90 @@ pub async fn update_access(
     ))
 }
 
-pub async fn update_user_info(
-    State(app_state): State<Arc<AppState>>,
-    Extension(session_wrapper): Extension<SessionExtension>,
-    Json(payload): Json<UserUpdatePayload>)
--> Result<impl IntoResponse, Error>
+///Преобразование полезной нагрузки в информацию о пользователе,
+/// данные авторизации (роль, активность, аудитории) переносятся только если `with_authorization`
+fn to_user_information(payload: UserUpdatePayload, user_id: &uuid::Uuid, with_authorization: bool) -> UserInformation
 {
-    let update_user = |payload: UserUpdatePayload, user_id: uuid::Uuid|
+    UserInformation
     {
-        let user = UserInformation
+        id: user_id.to_string(),
+        username: payload.username,
+        avatar: None,
+        profile: payload.profile.map(|p| Profile
         {
-            id: user_id.to_string(),
-            username: payload.username,
-            contacts: payload.contacts.into_iter().map(|c|
-            {
-                let id =c.id.unwrap_or(uuid::Uuid::now_v7().to_string());
-                Contact
-                {
-                    id,
-                    contact: c.contact,
-                    contact_type: c.contact_type
-                }
-            }).collect(),
-            
-            authorization_information: None,
-        };
-        user
-    };
-    if let Some(role) = session_wrapper.role.as_ref()
-    {
-        match role.parse().unwrap()
+            last_name: p.last_name,
+            first_name: p.first_name,
+            middle_name: p.middle_name,
+            position: p.position,
+            department: p.department,
+            timezone: p.timezone,
+            locale: p.locale
+        }),
+        contacts: payload.contacts.into_iter().map(|c|
         {
-            Role::Administrator =>
+            let id =c.id.unwrap_or(uuid::Uuid::now_v7().to_string());
+            Contact
             {
-                let user_info = UserInformation
-                {
-                    id: session_wrapper.session.user_id.to_string(),
-                    username: payload.username,
-                    contacts: payload.contacts.into_iter().map(|c|
-                    {
-                        let id =c.id.unwrap_or(uuid::Uuid::now_v7().to_string());
-                        Contact
-                        {
-                            id,
-                            contact: c.contact,
-                            contact_type: c.contact_type
-                        }
-                    }).collect(),
-                    authorization_information: Some(AuthorizationInformation
-                    {
-                        is_active: payload.is_active,
-                        role: payload.role,
-                        audiences: payload.audiences,
-                        access_key: None
-                    }),
-                };
-                let result = app_state.services.user_service.update_user_by_admin(user_info).await?;
-                Ok(result.into_response())
+                id,
+                contact: c.contact,
+                contact_type: c.contact_type,
+                verified: false,
+                is_primary: c.is_primary
             }
-            _ => 
+        }).collect(),
+        authorization_information: if with_authorization
+        {
+            Some(AuthorizationInformation
             {
-                let user_info = update_user(payload, session_wrapper.session.user_id.clone());
-                let result = app_state.services.user_service.update_user_info(user_info).await?;
-                Ok(result.into_response())
-            }
+                is_active: payload.is_active,
+                role: payload.role,
+                audiences: payload.audiences,
+                access_key: None
+            })
+        }
+        else
+        {
+            None
         }
     }
+}
+
+#[utoipa::path(post, path = "/auth/update_user_info", tag = "auth",
+    request_body = UserUpdatePayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, description = "Данные обновлены", body = String, content_type = "text/plain")))]
+pub async fn update_user_info(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    Json(payload): Json<UserUpdatePayload>)
+-> Result<impl IntoResponse, Error>
+{
+    let is_admin = session_wrapper.role.as_ref().as_ref().is_some_and(|r| matches!(Role::try_parse(r), Some(Role::Administrator)));
+    let user_info = to_user_information(payload, &session_wrapper.session.user_id, is_admin);
+    if is_admin
+    {
+        let result = app_state.services.user_service.update_user_by_admin(user_info).await?;
+        Ok(result.into_response())
+    }
     else 
     {
-        let user_info = update_user(payload, session_wrapper.session.user_id.clone());
         let result = app_state.services.user_service.update_user_info(user_info).await?;
         Ok(result.into_response())
     }
-    // else 
-    // {
-    //     let error = user.err().unwrap();
-    //     logger::error!("{}", error.to_string());
-    //     Err(error)    
-    // }
 }
 
+#[utoipa::path(post, path = "/auth/update_user", tag = "auth",
+    request_body = AdminUserUpdatePayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Данные обновлены", body = String, content_type = "text/plain")))]
 pub async fn update_user_info_by_admin(
     State(app_state): State<Arc<AppState>>,
-    Extension(session_wrapper): Extension<SessionExtension>,
-    Json(payload): Json<UserUpdatePayload>)
+    Json(payload): Json<AdminUserUpdatePayload>)
 -> Result<impl IntoResponse, Error>
 {
-    let user = UserDbo
-    {
-        id: session_wrapper.session.user_id,
-        username: String::new(),
-        password: String::new(),
-        name: payload.name,
-        surname_1: payload.surname_1,
-        surname_2: payload.surname_2,
-        is_active: payload.is_active,
-        avatar: payload.avatar,
-        role: payload.role,
-        audiences: payload.audiences,
-        information: InformationDbo
-        {
-            phones: payload.information.phones,
-            email: payload.information.email
-        }
-    };
-    let result = app_state.services.user_service.update_user_by_admin(user).await?;
+    let user_id = payload.id.parse::<uuid::Uuid>().map_err(|_| Error::ValidationError(tr_args("invalid_user_id", &[&payload.id])))?;
+    let user_info = to_user_information(payload.user, &user_id, true);
+    let result = app_state.services.user_service.update_user_by_admin(user_info).await?;
     Ok(result.into_response())
 }
 
@@ -1,44 +1,95 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-use crate::Role;
+use crate::{ContactType, Role};
 
 
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, ToSchema)]
 pub struct LoginPayload
 {
     pub login: String,
     pub password: String,
     pub device: String
 }
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct PasswordlessRequestPayload
+{
+    ///имя пользователя или подтвержденный контакт
+    pub login: String
+}
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct PasswordlessRequestResponse
+{
+    pub request_id: String
+}
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct PasswordlessLoginPayload
+{
+    pub request_id: uuid::Uuid,
+    ///код из сообщения или токен из ссылки
+    pub code: String,
+    pub device: String
+}
+#[derive(Debug, Deserialize, Clone, ToSchema)]
 pub struct PasswordPayload
 {
     pub old_password: String,
     pub new_password: String
 }
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, ToSchema)]
 pub struct SessionPayload
 {
     pub session_id: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, ToSchema)]
 pub struct UserContactsPayload
 {
     pub id: Option<String>,
-    pub contact_type: String,
-    pub contact: String
+    pub contact_type: ContactType,
+    pub contact: String,
+    ///основной контакт, если ни один контакт не отмечен основным, основной контакт не меняется
+    #[serde(default)]
+    pub is_primary: bool
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, ToSchema)]
+pub struct UserProfilePayload
+{
+    #[serde(default)]
+    pub last_name: String,
+    #[serde(default)]
+    pub first_name: String,
+    #[serde(default)]
+    pub middle_name: String,
+    #[serde(default)]
+    pub position: String,
+    #[serde(default)]
+    pub department: String,
+    pub timezone: String,
+    pub locale: String
+}
+
+#[derive(Deserialize, Debug, Clone, ToSchema)]
 pub struct UserUpdatePayload
 {
     pub username: String,
     pub is_active: bool,
     pub role: Role,
     pub audiences: Vec<String>,
-    pub contacts: Vec<UserContactsPayload>
+    pub contacts: Vec<UserContactsPayload>,
+    ///если не передан профиль не изменяется
+    pub profile: Option<UserProfilePayload>
+}
+
+///обновление данных любого пользователя администратором
+#[derive(Deserialize, Debug, Clone, ToSchema)]
+pub struct AdminUserUpdatePayload
+{
+    pub id: String,
+    #[serde(flatten)]
+    pub user: UserUpdatePayload
 }
 
 
@@ -0,0 +1,134 @@
+mod structs;
+
+use std::sync::Arc;
+use axum::{extract::State, response::IntoResponse, routing::post, Extension, Router};
+use hyper::StatusCode;
+use super::extract::Json;
+use structs::{AddContactPayload, ConfirmContactPayload, ContactPayload};
+use crate::{i18n::tr, middleware::{AuthCheck, AuthLayer, RateLimitLayer, SessionExtension}, services::Contact, state::AppState, Error, Role};
+
+pub fn contacts_router(app_state: Arc<AppState>) -> Router
+{   
+    Router::new()      
+        .route("/contacts/add", post(add_contact)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::User, Role::Administrator])))
+
+        .route("/contacts/delete", post(delete_contact)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::User, Role::Administrator])))
+
+        .route("/contacts/primary", post(set_primary_contact)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::User, Role::Administrator])))
+
+        .route("/contacts/verification/request", post(request_verification)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::User, Role::Administrator])))
+
+        .route("/contacts/verification/confirm", post(confirm_verification)
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::User, Role::Administrator])))
+
+        .with_state(app_state.clone())
+}
+
+#[utoipa::path(post, path = "/contacts/add", tag = "contacts",
+    request_body = AddContactPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, body = Contact, description = "Добавленный контакт")))]
+pub async fn add_contact(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    Json(payload): Json<AddContactPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    let contact = app_state.services.contact_service.add(&session_wrapper.session.user_id, payload.contact_type, &payload.contact).await?;
+    Ok((
+        StatusCode::OK,
+        Json(contact)
+    ))
+}
+
+#[utoipa::path(post, path = "/contacts/delete", tag = "contacts",
+    request_body = ContactPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, body = String, content_type = "text/plain", description = "Контакт удален")))]
+pub async fn delete_contact(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    Json(payload): Json<ContactPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    app_state.services.contact_service.delete(&session_wrapper.session.user_id, &payload.contact_id).await?;
+    Ok((
+        StatusCode::OK,
+        tr("contact_deleted")
+    ))
+}
+
+#[utoipa::path(post, path = "/contacts/primary", tag = "contacts",
+    request_body = ContactPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, body = String, content_type = "text/plain", description = "Основной контакт изменен")))]
+pub async fn set_primary_contact(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    Json(payload): Json<ContactPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    app_state.services.contact_service.set_primary(&session_wrapper.session.user_id, &payload.contact_id).await?;
+    Ok((
+        StatusCode::OK,
+        tr("primary_contact_changed")
+    ))
+}
+
+#[utoipa::path(post, path = "/contacts/verification/request", tag = "contacts",
+    request_body = ContactPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, body = String, content_type = "text/plain", description = "Код подтверждения отправлен")))]
+pub async fn request_verification(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    Json(payload): Json<ContactPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    app_state.services.contact_service.request_verification(&session_wrapper.session.user_id, &payload.contact_id).await?;
+    Ok((
+        StatusCode::OK,
+        tr("verification_code_sent")
+    ))
+}
+
+#[utoipa::path(post, path = "/contacts/verification/confirm", tag = "contacts",
+    request_body = ConfirmContactPayload,
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, body = String, content_type = "text/plain", description = "Контакт подтвержден")))]
+pub async fn confirm_verification(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    Json(payload): Json<ConfirmContactPayload>)
+-> Result<impl IntoResponse, Error>
+{
+    app_state.services.contact_service.confirm_verification(&session_wrapper.session.user_id, &payload.contact_id, payload.code).await?;
+    Ok((
+        StatusCode::OK,
+        tr("contact_verified")
+    ))
+}
@@ -0,0 +1,23 @@
+use serde::Deserialize;
+use utoipa::ToSchema;
+use crate::ContactType;
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct AddContactPayload
+{
+    pub contact_type: ContactType,
+    pub contact: String
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct ContactPayload
+{
+    pub contact_id: uuid::Uuid
+}
+
+#[derive(Debug, Deserialize, Clone, ToSchema)]
+pub struct ConfirmContactPayload
+{
+    pub contact_id: uuid::Uuid,
+    pub code: u32
+}
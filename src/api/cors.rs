@@ -14,8 +14,13 @@ pub fn cors_layer(state: Arc<AppState>) -> CorsLayer
     //     "http://localhost:9090".parse().unwrap(),
     //     "http://xarman.space".parse().unwrap()
     // ];
-    let origins: Vec<HeaderValue> = state.configuration.origins.iter().map(|v| v.parse().unwrap()).collect();
-    let fingerprint_header_name: HeaderName = state.configuration.fingerprint_header_name.parse().unwrap();
+    //источники проверяются по текущим настройкам, поэтому их можно менять без перезапуска
+    let configuration = state.configuration.clone();
+    let origins = AllowOrigin::predicate(move |origin: &HeaderValue, _| 
+    {
+        configuration.get().origins.iter().any(|o| o.as_bytes() == origin.as_bytes())
+    });
+    let fingerprint_header_name: HeaderName = state.configuration.get().fingerprint_header_name.parse().unwrap();
     let cors_layer = CorsLayer::new()
             .allow_origin(origins)
             .allow_methods([Method::GET, Method::POST, Method::OPTIONS, Method::PUT, Method::HEAD, Method::PATCH])
@@ -0,0 +1,86 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+use axum::{extract::State, http::{HeaderMap, HeaderName}, response::{sse::{Event, KeepAlive}, IntoResponse, Sse}, routing::get, Extension, Router};
+use futures::Stream;
+use crate::{i18n::tr_args, middleware::{AuthCheck, AuthLayer, RateLimitLayer, SessionExtension}, services::EventSubscription, state::AppState, Error, Role};
+
+const LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+///интервал проверки сессии открытого потока: сессия могла истечь или быть удалена из другого процесса (cli)
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn events_router(app_state: Arc<AppState>) -> Router
+{
+    Router::new()
+        .route("/events", get(events)
+            .route_layer(RateLimitLayer::new("session", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::Session,
+                Arc::clone(&app_state),
+                &[Role::User, Role::Administrator])))
+        .with_state(app_state.clone())
+}
+
+///Поток событий сессии в формате `text/event-stream`.
+/// Ключ доступа не нужен: поток живет дольше ключа, поэтому проверяются только сессия и отпечаток клиента.
+/// При переподключении клиент передает `Last-Event-ID` и получает пропущенные события, если они еще есть в буфере,
+/// иначе приходит событие `resync`. Поток закрывается при выходе из сессии, ее истечении и остановке сервера
+#[utoipa::path(get, path = "/events", tag = "events",
+    params(("Last-Event-ID" = Option<u64>, Header, description = "Идентификатор последнего полученного события")),
+    security(("session_cookie" = [], "fingerprint" = [])),
+    responses((status = 200, description = "Поток событий, между событиями отправляются комментарии для поддержания соединения", body = String, content_type = "text/event-stream")))]
+pub async fn events(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    headers: HeaderMap)
+-> Result<impl IntoResponse, Error>
+{
+    let session = &session_wrapper.session;
+    let user = app_state.services.database_service.user_repository.get_user(&session.user_id).await?;
+    if !user.is_active
+    {
+        return Err(Error::AuthError(tr_args("user_inactive", &[&user.username])));
+    }
+    let last_event_id = headers.get(&LAST_EVENT_ID)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    let subscription = app_state.services.sse_service.subscribe(session.session_id, session.user_id, user.role, last_event_id);
+    logger::debug!("Открыт поток событий сессии {}", session.session_id);
+    let heartbeat = Duration::from_secs(app_state.configuration.get().sse_heartbeat_interval as u64);
+    let stream = event_stream(Arc::clone(&app_state), session.session_id, subscription);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(heartbeat)))
+}
+
+fn event_stream(app_state: Arc<AppState>, session_id: uuid::Uuid, subscription: EventSubscription) -> impl Stream<Item = Result<Event, Infallible>>
+{
+    let check = tokio::time::interval_at(tokio::time::Instant::now() + SESSION_CHECK_INTERVAL, SESSION_CHECK_INTERVAL);
+    futures::stream::unfold((app_state, subscription, check), move |(app_state, mut subscription, mut check)| async move
+    {
+        loop
+        {
+            tokio::select!
+            {
+                _ = app_state.shutdown.cancelled() => return None,
+                _ = check.tick() =>
+                {
+                    if !super::session_active(&app_state, &session_id).await
+                    {
+                        logger::debug!("Поток событий сессии {} закрыт: сессия истекла или удалена", session_id);
+                        return None;
+                    }
+                }
+                event = subscription.next() =>
+                {
+                    let Some(event) = event else
+                    {
+                        logger::debug!("Поток событий сессии {} закрыт", session_id);
+                        return None;
+                    };
+                    let event = Event::default()
+                        .id(event.id.to_string())
+                        .event(&event.event)
+                        .data(&event.data);
+                    return Some((Ok(event), (app_state, subscription, check)));
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,86 @@
+use std::ops::{Deref, DerefMut};
+use axum::{extract::{FromRequest, FromRequestParts, Request}, http::request::Parts, response::{IntoResponse, Response}};
+use serde::{de::DeserializeOwned, Serialize};
+use crate::Error;
+
+//Обертки над экстракторами axum: ошибки разбора запроса возвращаются в формате `application/problem+json`, а не текстом
+
+///Тело запроса (и ответа) в формате json
+pub struct Json<T>(pub T);
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync
+{
+    type Rejection = Error;
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection>
+    {
+        let axum::Json(value) = axum::Json::<T>::from_request(req, state).await?;
+        Ok(Json(value))
+    }
+}
+impl<T: Serialize> IntoResponse for Json<T>
+{
+    fn into_response(self) -> Response
+    {
+        axum::Json(self.0).into_response()
+    }
+}
+
+///Параметры строки запроса
+pub struct Query<T>(pub T);
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync
+{
+    type Rejection = Error;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection>
+    {
+        let axum::extract::Query(value) = axum::extract::Query::<T>::from_request_parts(parts, state).await?;
+        Ok(Query(value))
+    }
+}
+
+///Параметры пути
+pub struct Path<T>(pub T);
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync
+{
+    type Rejection = Error;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection>
+    {
+        let axum::extract::Path(value) = axum::extract::Path::<T>::from_request_parts(parts, state).await?;
+        Ok(Path(value))
+    }
+}
+
+///Тело запроса `multipart/form-data`
+pub struct Multipart(pub axum::extract::Multipart);
+impl<S> FromRequest<S> for Multipart
+where
+    S: Send + Sync
+{
+    type Rejection = Error;
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection>
+    {
+        Ok(Multipart(axum::extract::Multipart::from_request(req, state).await?))
+    }
+}
+impl Deref for Multipart
+{
+    type Target = axum::extract::Multipart;
+    fn deref(&self) -> &Self::Target
+    {
+        &self.0
+    }
+}
+impl DerefMut for Multipart
+{
+    fn deref_mut(&mut self) -> &mut Self::Target
+    {
+        &mut self.0
+    }
+}
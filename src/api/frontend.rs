@@ -0,0 +1,105 @@
+use std::{path::Path, sync::Arc};
+use axum::{body::Body, extract::{Request, State}, http::{header::CACHE_CONTROL, HeaderValue, Method}, response::{IntoResponse, Response}, Router};
+use hyper::StatusCode;
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+use crate::{error::Problem, i18n::tr, state::AppState};
+
+///файлы с хэшем содержимого в имени никогда не меняются
+const IMMUTABLE: HeaderValue = HeaderValue::from_static("public, max-age=31536000, immutable");
+///`index.html` и файлы без хэша браузер должен проверять при каждой загрузке
+const REVALIDATE: HeaderValue = HeaderValue::from_static("no-cache");
+
+#[derive(Clone)]
+struct Frontend
+{
+    assets: ServeDir,
+    index: ServeFile
+}
+
+///Собранный фронтенд из `frontend_directory`: файлы отдаются как есть (или их `.br`/`.gz` версии, если клиент их принимает),
+/// остальные пути без расширения считаются маршрутами клиента и получают `index.html`.
+/// `None` если фронтенд отключен в настройках или не собран
+pub fn frontend_router(app_state: Arc<AppState>) -> Option<Router>
+{
+    let cfg = app_state.configuration.get();
+    if cfg.frontend_directory.trim().is_empty()
+    {
+        return None;
+    }
+    let dir = Path::new(&cfg.frontend_directory);
+    let index = dir.join("index.html");
+    if !index.exists()
+    {
+        logger::warn!("Фронтенд не найден в `{}`, будет доступно только api", &cfg.frontend_directory);
+        return None;
+    }
+    let frontend = Frontend
+    {
+        assets: ServeDir::new(dir).precompressed_br().precompressed_gzip(),
+        index: ServeFile::new(index).precompressed_br().precompressed_gzip()
+    };
+    Some(Router::new()
+        .fallback(serve_frontend)
+        .with_state(Arc::new(frontend)))
+}
+
+async fn serve_frontend(State(frontend): State<Arc<Frontend>>, request: Request) -> Response
+{
+    let path = request.uri().path().to_owned();
+    //маршруты api никогда не отдаются фронтендом, даже если такой файл есть
+    if path == "/api" || path.starts_with("/api/") || !matches!(*request.method(), Method::GET | Method::HEAD)
+    {
+        return not_found();
+    }
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    let client_route = !file_name.contains('.');
+    let response = if client_route
+    {
+        frontend.index.clone().oneshot(request).await
+    }
+    else
+    {
+        frontend.assets.clone().oneshot(request).await
+    };
+    let Ok(response) = response;
+    if response.status() == StatusCode::NOT_FOUND
+    {
+        return not_found();
+    }
+    let mut response = response.map(Body::new);
+    if response.status().is_success()
+    {
+        let cache = if !client_route && is_hashed(file_name) { IMMUTABLE } else { REVALIDATE };
+        response.headers_mut().insert(CACHE_CONTROL, cache);
+    }
+    response
+}
+
+fn not_found() -> Response
+{
+    Problem::new(StatusCode::NOT_FOUND, "route_not_found", tr("route_not_found")).into_response()
+}
+
+///Имя файла содержит хэш содержимого, например `index.3f9a1c2b.js`
+fn is_hashed(file_name: &str) -> bool
+{
+    let parts: Vec<&str> = file_name.split('.').collect();
+    parts.len() > 2 && parts[1..parts.len() - 1].iter().any(|p| p.len() >= 8 && p.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::is_hashed;
+
+    #[test]
+    fn test_is_hashed()
+    {
+        assert!(is_hashed("index.3f9a1c2b.js"));
+        assert!(is_hashed("lib-vue.0a1b2c3d.css"));
+        assert!(!is_hashed("favicon.ico"));
+        assert!(!is_hashed("app.min.js"));
+        assert!(!is_hashed("3f9a1c2b.js"));
+    }
+}
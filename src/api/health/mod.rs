@@ -0,0 +1,70 @@
+mod structs;
+
+use std::sync::Arc;
+use axum::{extract::State, http::header::CONTENT_TYPE, response::IntoResponse, routing::get, Json, Router};
+use hyper::StatusCode;
+use structs::ReadinessResponse;
+use crate::{state::AppState, Error};
+
+pub fn health_router(app_state: Arc<AppState>) -> Router
+{   
+    Router::new()      
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready))
+        .route("/metrics", get(metrics))
+        .with_state(app_state.clone())
+}
+
+///Процесс запущен и обрабатывает запросы
+#[utoipa::path(get, path = "/health/live", tag = "health",
+    responses((status = 200, description = "Сервер запущен", body = String, content_type = "text/plain")))]
+pub async fn live() -> impl IntoResponse
+{
+    (StatusCode::OK, "ok")
+}
+
+///Сервер готов принимать запросы: доступны обе базы данных и загружен ключ подписи.
+/// Во время остановки сервера всегда возвращает 503
+#[utoipa::path(get, path = "/health/ready", tag = "health",
+    responses(
+        (status = 200, description = "Сервер готов", body = ReadinessResponse),
+        (status = 503, description = "Сервер не готов или останавливается", body = ReadinessResponse)))]
+pub async fn ready(State(app_state): State<Arc<AppState>>) -> impl IntoResponse
+{
+    let services = &app_state.services;
+    let response = ReadinessResponse
+    {
+        database: services.database_service.ping().await.into(),
+        sessions_database: services.database_service.ping_sessions().await.into(),
+        signing_key: services.jwt_service.self_check().await.into(),
+        shutting_down: app_state.shutdown.is_cancelled()
+    };
+    let status = if response.is_ready()
+    {
+        StatusCode::OK
+    }
+    else
+    {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(response))
+}
+
+///Метрики в текстовом формате Prometheus, если метрики отключены в настройках - 404
+#[utoipa::path(get, path = "/metrics", tag = "health",
+    responses(
+        (status = 200, description = "Метрики Prometheus", body = String, content_type = "text/plain"),
+        (status = 404, description = "Метрики отключены")))]
+pub async fn metrics(State(app_state): State<Arc<AppState>>) -> Result<impl IntoResponse, Error>
+{
+    if !app_state.configuration.get().metrics_enabled
+    {
+        return Ok((StatusCode::NOT_FOUND, [(CONTENT_TYPE, "text/plain; charset=utf-8")], String::new()));
+    }
+    let body = app_state.services.metrics_service.render(&app_state.services.database_service).await?;
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        body
+    ))
+}
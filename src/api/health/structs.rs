@@ -0,0 +1,42 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::Error;
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+#[serde(tag = "status", content = "error", rename_all = "lowercase")]
+pub enum CheckStatus
+{
+    Ok,
+    Failed(String)
+}
+impl From<Result<(), Error>> for CheckStatus
+{
+    fn from(value: Result<(), Error>) -> Self
+    {
+        match value
+        {
+            Ok(_) => CheckStatus::Ok,
+            Err(e) =>
+            {
+                logger::error!("Проверка готовности не пройдена: {}", e.to_string());
+                CheckStatus::Failed(e.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct ReadinessResponse
+{
+    pub database: CheckStatus,
+    pub sessions_database: CheckStatus,
+    pub signing_key: CheckStatus,
+    pub shutting_down: bool
+}
+impl ReadinessResponse
+{
+    pub fn is_ready(&self) -> bool
+    {
+        !self.shutting_down && [&self.database, &self.sessions_database, &self.signing_key].iter().all(|c| matches!(c, CheckStatus::Ok))
+    }
+}
@@ -3,9 +3,26 @@ mod cors;
 mod test_api;
 mod router;
 mod authorization;
+mod users;
+mod contacts;
+mod admin;
+mod health;
+mod events;
+mod ws;
+mod openapi;
 mod server;
+mod tls;
+mod frontend;
+mod extract;
+pub use server::start;
 use std::sync::Arc;
 use axum::{extract::FromRequestParts, http::{request::Parts, HeaderValue}, response::{IntoResponseParts, Response, ResponseParts}};
 use cors::cors_layer;
-use crate::{configuration::Configuration, db::Session};
+use crate::{configuration::Configuration, db::Session, state::AppState};
 
+///Сессия долгого соединения (`/events`, `/ws`) еще существует и не истекла
+async fn session_active(app_state: &AppState, session_id: &uuid::Uuid) -> bool
+{
+    app_state.services.database_service.session_repository.get_session(session_id).await
+        .is_ok_and(|s| !s.is_expired())
+}
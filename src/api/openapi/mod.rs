@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use axum::{extract::State, response::IntoResponse, routing::get, Json, Router};
+use hyper::StatusCode;
+use utoipa::{openapi::{security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme}, ContentBuilder, Ref, ResponseBuilder}, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+use crate::{configuration::Configuration, error::{FieldError, Problem}, state::AppState};
+
+///маршруты внутри `/api`
+const OPENAPI_PATH: &str = "/openapi.json";
+const DOCS_PATH: &str = "/docs";
+///адрес спецификации для интерфейса документации
+const OPENAPI_URL: &str = "/api/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Planner API", description = "Ошибки возвращаются в формате `application/problem+json` (схема `Problem`), поле `code` стабильно и не зависит от языка сообщения"),
+    paths(
+        super::authorization::login,
+        super::authorization::passwordless_request,
+        super::authorization::passwordless_login,
+        super::authorization::update_access,
+        super::authorization::change_password,
+        super::authorization::admin_section,
+        super::authorization::exit,
+        super::authorization::exit_from,
+        super::authorization::exit_all,
+        super::authorization::update_user_info,
+        super::authorization::update_user_info_by_admin,
+        super::users::import_users,
+        super::users::export_users,
+        super::users::upload_avatar,
+        super::users::delete_avatar,
+        super::users::get_avatar,
+        super::contacts::add_contact,
+        super::contacts::delete_contact,
+        super::contacts::set_primary_contact,
+        super::contacts::request_verification,
+        super::contacts::confirm_verification,
+        super::admin::get_configuration,
+        super::admin::reload_configuration,
+        super::admin::get_rate_limits,
+        super::admin::add_rate_limit_exempt,
+        super::admin::remove_rate_limit_exempt,
+        super::admin::backup,
+        super::admin::get_jobs,
+        super::admin::run_job,
+        super::events::events,
+        super::ws::ws,
+        super::health::live,
+        super::health::ready,
+        super::health::metrics
+    ),
+    components(schemas(Problem, FieldError)),
+    tags(
+        (name = "auth", description = "Вход, сессии и данные текущего пользователя"),
+        (name = "users", description = "Импорт, экспорт и аватары пользователей"),
+        (name = "contacts", description = "Контакты текущего пользователя"),
+        (name = "admin", description = "Управление сервером"),
+        (name = "events", description = "Поток событий сервера (Server-Sent Events) и канал WebSocket"),
+        (name = "health", description = "Состояние сервера и метрики")
+    )
+)]
+struct ApiDoc;
+
+///Спецификация OpenAPI с учетом настроек сервера:
+/// имена cookie сессии и заголовка отпечатка берутся из настроек, у всех маршрутов есть ответ с ошибкой `Problem`,
+/// у защищенных маршрутов - ответы 401 и 403, у маршрутов с ограничением частоты запросов - 429.
+/// Роли, необходимые для доступа к маршруту, перечислены в требовании `bearer`
+pub fn openapi(cfg: &Configuration) -> utoipa::openapi::OpenApi
+{
+    let mut openapi = ApiDoc::openapi();
+    let components = openapi.components.get_or_insert_with(Default::default);
+    components.add_security_scheme("session_cookie", SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+        &cfg.session_cookie_name, "Ключ сессии, устанавливается при входе"))));
+    components.add_security_scheme("fingerprint", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+        &cfg.fingerprint_header_name, "Уникальный идентификатор клиента, должен совпадать с переданным при входе"))));
+    components.add_security_scheme("bearer", SecurityScheme::Http(HttpBuilder::new()
+        .scheme(HttpAuthScheme::Bearer)
+        .bearer_format("JWT")
+        .description(Some("Ключ доступа, выдается при входе и обновляется через `/api/auth/update_key`"))
+        .build()));
+    //маршруты в обработчиках указаны относительно `/api`, проверки состояния и метрики остаются в корне
+    openapi.paths.paths = std::mem::take(&mut openapi.paths.paths).into_iter()
+        .map(|(path, item)| if is_api_path(&path) { (["/api", &path].concat(), item) } else { (path, item) })
+        .collect();
+    for (path, item) in openapi.paths.paths.iter_mut()
+    {
+        let limited = path.starts_with("/api/");
+        let operations = [&mut item.get, &mut item.post, &mut item.put, &mut item.delete, &mut item.patch];
+        for operation in operations.into_iter().flatten()
+        {
+            let secured = operation.security.as_ref().is_some_and(|s| !s.is_empty());
+            let responses = &mut operation.responses.responses;
+            responses.entry("default".to_owned()).or_insert_with(|| problem_response("Ошибка").into());
+            if secured
+            {
+                responses.entry("401".to_owned()).or_insert_with(|| problem_response("Сессия или ключ доступа недействительны").into());
+                responses.entry("403".to_owned()).or_insert_with(|| problem_response("Недостаточно прав").into());
+            }
+            if limited
+            {
+                responses.entry("429".to_owned()).or_insert_with(|| problem_response(
+                    "Превышено ограничение частоты запросов, время ожидания в заголовке `Retry-After`. \
+                    Остаток запросов передается в заголовках `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset`").into());
+            }
+        }
+    }
+    openapi
+}
+
+fn is_api_path(path: &str) -> bool
+{
+    !path.starts_with("/health/") && path != "/metrics"
+}
+
+fn problem_response(description: &str) -> utoipa::openapi::Response
+{
+    ResponseBuilder::new()
+        .description(description)
+        .content("application/problem+json", ContentBuilder::new().schema(Some(Ref::from_schema_name("Problem"))).build())
+        .build()
+}
+
+///Спецификация доступна всегда на `/api/openapi.json`, интерфейс документации на `/api/docs` - если включен `api_docs_ui`
+pub fn openapi_router(app_state: Arc<AppState>) -> Router
+{
+    let cfg = app_state.configuration.get();
+    //имена cookie и заголовка меняются только с перезапуском, поэтому спецификация строится один раз
+    let spec = Arc::new(openapi(&cfg));
+    let router = Router::new()
+        .route(OPENAPI_PATH, get(openapi_json))
+        .with_state(Arc::clone(&spec));
+    if cfg.api_docs_ui
+    {
+        let spec = serde_json::to_value(spec.as_ref()).unwrap_or_default();
+        router.merge(SwaggerUi::new(DOCS_PATH).external_url_unchecked(OPENAPI_URL, spec))
+    }
+    else
+    {
+        router
+    }
+}
+
+async fn openapi_json(State(spec): State<Arc<utoipa::openapi::OpenApi>>) -> impl IntoResponse
+{
+    (StatusCode::OK, Json(spec.as_ref().clone()))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::configuration::Configuration;
+
+    #[test]
+    fn test_openapi()
+    {
+        let cfg = Configuration::default();
+        let json = serde_json::to_value(super::openapi(&cfg)).unwrap();
+        assert_eq!(json["components"]["securitySchemes"]["session_cookie"]["name"], cfg.session_cookie_name);
+        assert_eq!(json["components"]["securitySchemes"]["fingerprint"]["in"], "header");
+        assert!(json["components"]["schemas"]["LoginPayload"].is_object());
+        let login = &json["paths"]["/api/auth/login"]["post"];
+        assert_eq!(login["responses"]["default"]["content"]["application/problem+json"]["schema"]["$ref"], "#/components/schemas/Problem");
+        assert!(login["responses"]["401"].is_object());
+        assert!(login["responses"]["429"].is_object());
+        assert!(login["responses"]["404"].is_null());
+        let update_user = &json["paths"]["/api/auth/update_user"]["post"];
+        assert_eq!(update_user["security"][0]["bearer"][0], "Administrator");
+        assert!(update_user["responses"]["401"].is_object());
+        assert!(json["paths"]["/health/live"]["get"]["responses"]["401"].is_null());
+    }
+}
@@ -2,25 +2,265 @@ use std::sync::Arc;
 
 use axum::{response::IntoResponse, Router};
 use hyper::StatusCode;
-use tower_http::trace::{DefaultMakeSpan, TraceLayer};
+use tower_http::trace::TraceLayer;
 
-use crate::state::AppState;
+use crate::{error::Problem, i18n::tr, middleware::{hsts_middleware, locale_middleware, metrics_middleware, request_id_middleware, RequestSpan, ResponseLog}, state::AppState};
 
 
 pub fn router(app_state: Arc<AppState>) -> Router
 {   
     let auth_router = super::authorization::authorization_router(Arc::clone(&app_state));
-    Router::new()
-        .fallback(handler_404)      
-        .with_state(app_state.clone())
-        .layer(super::cors::cors_layer(app_state.clone()))
+    let users_router = super::users::users_router(Arc::clone(&app_state));
+    let contacts_router = super::contacts::contacts_router(Arc::clone(&app_state));
+    let admin_router = super::admin::admin_router(Arc::clone(&app_state));
+    let events_router = super::events::events_router(Arc::clone(&app_state));
+    let ws_router = super::ws::ws_router(Arc::clone(&app_state));
+    let health_router = super::health::health_router(Arc::clone(&app_state));
+    let openapi_router = super::openapi::openapi_router(Arc::clone(&app_state));
+    //api доступно под `/api`, чтобы маршруты фронтенда и api никогда не пересекались
+    let api_router = Router::new()
+        .merge(auth_router)
+        .merge(users_router)
+        .merge(contacts_router)
+        .merge(admin_router)
+        .merge(events_router)
+        .merge(ws_router)
+        .merge(openapi_router)
+        .fallback(handler_404)
+        .layer(super::cors::cors_layer(app_state.clone()));
+    let router = Router::new()
+        .nest("/api", api_router)
+        .merge(health_router);
+    let router = match super::frontend::frontend_router(Arc::clone(&app_state))
+    {
+        Some(frontend_router) => router.merge(frontend_router),
+        None => router.fallback(handler_404)
+    };
+    //единственный TraceLayer, внутри request_id - идентификатор запроса уже известен при создании span
+    router
         .layer(
             TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().include_headers(true)),
-        ).merge(auth_router)
+                .make_span_with(RequestSpan::new(&app_state.configuration.get()))
+                .on_response(ResponseLog),
+        )
+        .layer(axum::middleware::from_fn_with_state(app_state.clone(), hsts_middleware))
+        .layer(axum::middleware::from_fn_with_state(app_state.clone(), metrics_middleware))
+        .layer(axum::middleware::from_fn(locale_middleware))
+        .layer(axum::middleware::from_fn(request_id_middleware))
 }
 
 async fn handler_404() -> impl IntoResponse 
 {
-    (StatusCode::NOT_FOUND, "Такого пути нет")
-}
\ No newline at end of file
+    Problem::new(StatusCode::NOT_FOUND, "route_not_found", tr("route_not_found"))
+}
+#[cfg(test)]
+mod tests
+{
+    use std::{net::SocketAddr, pin::Pin, sync::Arc};
+    use axum::{body::Body, extract::connect_info::MockConnectInfo, http::{header::{AUTHORIZATION, CONTENT_TYPE, COOKIE, SET_COOKIE}, Request}, Router};
+    use futures::StreamExt;
+    use hyper::StatusCode;
+    use tower::ServiceExt;
+    use crate::{configuration::{Configuration, RateLimit, RateLimitKey}, db::{ISessionRepository, Session, SessionDbo, UserDbo}, state::{AppState, AppStateBuilder}, Error, Role};
+
+    ///настройки без файлов: базы в памяти, временный ключ, без фронтенда
+    fn test_builder() -> AppStateBuilder
+    {
+        let mut cfg = Configuration::default();
+        cfg.frontend_directory = String::new();
+        AppStateBuilder::new(cfg).in_memory().ephemeral_key()
+    }
+    fn test_router(state: Arc<AppState>) -> Router
+    {
+        super::router(state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))))
+    }
+    async fn create_admin(state: &AppState)
+    {
+        let repository = &state.services.database_service.user_repository;
+        let user = UserDbo
+        {
+            id: uuid::Uuid::now_v7(),
+            username: "admin".to_owned(),
+            password: "admin_password".to_owned(),
+            is_active: true,
+            role: Role::Administrator,
+            audiences: Vec::new(),
+            avatar: None,
+            profile: None,
+            contacts: Vec::new()
+        };
+        repository.create(user).await.unwrap();
+    }
+    fn login_request(cfg: &Configuration, password: &str) -> Request<Body>
+    {
+        let body = serde_json::json!({ "login": "admin", "password": password, "device": "test" });
+        Request::post("/api/auth/login")
+            .header(CONTENT_TYPE, "application/json")
+            .header(&cfg.fingerprint_header_name, "test-fingerprint")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_routes()
+    {
+        let state = Arc::new(test_builder().build().await.unwrap());
+        let app = test_router(state);
+        let response = app.clone().oneshot(Request::get("/health/live").header("x-request-id", "test-request").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-request-id"], "test-request");
+        let response = app.clone().oneshot(Request::get("/api/unknown").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        let avatar = ["/api/users/avatar/", &uuid::Uuid::now_v7().to_string()].concat();
+        let response = app.clone().oneshot(Request::get(avatar).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        let response = app.clone().oneshot(Request::get("/api/admin/configuration").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(Request::get("/api/admin/jobs").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.oneshot(Request::get("/api/ws").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_login()
+    {
+        let state = Arc::new(test_builder().build().await.unwrap());
+        create_admin(&state).await;
+        let cfg = state.configuration.get();
+        let app = test_router(Arc::clone(&state));
+        let response = app.clone().oneshot(login_request(&cfg, "admin_password")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with(&[cfg.session_cookie_name.as_str(), "="].concat()));
+        let response = app.oneshot(login_request(&cfg, "wrong_password")).await.unwrap();
+        assert_ne!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unverified_token_limit()
+    {
+        let mut cfg = Configuration::default();
+        cfg.frontend_directory = String::new();
+        cfg.rate_limits.insert("auth".to_owned(), RateLimit { key: RateLimitKey::Token, capacity: 1, refill_per_minute: 1 });
+        let state = Arc::new(AppStateBuilder::new(cfg).in_memory().ephemeral_key().build().await.unwrap());
+        let cfg = state.configuration.get();
+        let app = test_router(state);
+        //непроверенный ключ не дает отдельного лимита, запросы считаются по ip
+        for (token, status) in [("first-random-token", StatusCode::UNAUTHORIZED), ("second-random-token", StatusCode::TOO_MANY_REQUESTS)]
+        {
+            let mut request = login_request(&cfg, "wrong_password");
+            request.headers_mut().insert(AUTHORIZATION, ["Bearer ", token].concat().parse().unwrap());
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_json()
+    {
+        let state = Arc::new(test_builder().build().await.unwrap());
+        let cfg = state.configuration.get();
+        let app = test_router(state);
+        let request = |body: &str| Request::post("/api/auth/login")
+            .header(CONTENT_TYPE, "application/json")
+            .header(&cfg.fingerprint_header_name, "test-fingerprint")
+            .body(Body::from(body.to_owned()))
+            .unwrap();
+        let response = app.clone().oneshot(request("{\"login\": ")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], "invalid_json");
+        let response = app.oneshot(request("{\"login\": \"admin\"}")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["errors"][0]["field"], "body");
+    }
+
+    ///хранилище сессий, недоступное для записи
+    struct FailingSessionRepository;
+    impl ISessionRepository for FailingSessionRepository
+    {
+        fn create_session<'a>(&'a self, _: &'a uuid::Uuid, _: u8, _: u8, _: &'a str, _: &'a str, _: &'a str) -> Pin<Box<dyn Future<Output = Result<Session, Error>> + Send + 'a>>
+        {
+            Box::pin(async { Err(Error::Internal("sessions unavailable".to_owned())) })
+        }
+        fn get_session<'a>(&'a self, _: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<Session, Error>> + Send + 'a>>
+        {
+            Box::pin(async { Err(Error::SessionNotFound) })
+        }
+        fn insert_or_replace_session<'a>(&'a self, _: &'a SessionDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+        {
+            Box::pin(async { Err(Error::Internal("sessions unavailable".to_owned())) })
+        }
+        fn sessions_count<'a>(&'a self, _: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + 'a>>
+        {
+            Box::pin(async { Ok(0) })
+        }
+        fn active_sessions_count<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
+        {
+            Box::pin(async { Ok(0) })
+        }
+        fn delete_all_sessions<'a>(&'a self, _: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
+        {
+            Box::pin(async { Ok(0) })
+        }
+        fn delete_session<'a>(&'a self, _: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+        {
+            Box::pin(async { Ok(()) })
+        }
+        fn update_session_key<'a>(&'a self, _: &'a uuid::Uuid, _: u8) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+        {
+            Box::pin(async { Err(Error::SessionNotFound) })
+        }
+        fn delete_expired<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
+        {
+            Box::pin(async { Ok(0) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_swapped_session_repository()
+    {
+        let state = Arc::new(test_builder().session_repository(Box::new(FailingSessionRepository)).build().await.unwrap());
+        create_admin(&state).await;
+        let cfg = state.configuration.get();
+        let response = test_router(Arc::clone(&state)).oneshot(login_request(&cfg, "admin_password")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().get(SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_events()
+    {
+        let state = Arc::new(test_builder().build().await.unwrap());
+        create_admin(&state).await;
+        let cfg = state.configuration.get();
+        let app = test_router(Arc::clone(&state));
+        let response = app.clone().oneshot(login_request(&cfg, "admin_password")).await.unwrap();
+        let cookie = response.headers()[SET_COOKIE].to_str().unwrap().split(';').next().unwrap().to_owned();
+        let session_id: uuid::Uuid = cookie.split_once('=').unwrap().1.parse().unwrap();
+        let request = Request::get("/api/events")
+            .header(COOKIE, &cookie)
+            .header(&cfg.fingerprint_header_name, "test-fingerprint")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+        let mut body = response.into_body().into_data_stream();
+        state.services.sse_service.publish_to_role(Role::User, "hidden", &0).unwrap();
+        state.services.sse_service.publish_to_all("test", &1).unwrap();
+        let frame = body.next().await.unwrap().unwrap();
+        let frame = String::from_utf8_lossy(&frame);
+        assert!(frame.contains("event: test\ndata: 1"), "{}", frame);
+        state.services.user_service.exit_from_session(&session_id).await.unwrap();
+        assert!(body.next().await.is_none());
+    }
+}
@@ -1,16 +1,104 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use logger::debug;
+use tokio_util::sync::CancellationToken;
 use crate::state::AppState;
-use super::router::router;
+use super::{router::router, tls};
 
-async fn start() -> Result<(), crate::Error>
+pub async fn start(state: Arc<AppState>) -> Result<(), crate::Error>
 {
-    let state = Arc::new(AppState::initialize().await?);
-    let addr = SocketAddr::from(([0, 0, 0, 0], state.configuration.server_port));
-    debug!("Апи сервера доступно на {}", &addr);
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, router(state).into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
-    Ok(())
+    let cfg = state.configuration.get();
+    let ip: std::net::IpAddr = cfg.bind_address.parse()
+    .map_err(|_| crate::Error::ValidationError(["неверный адрес `", &cfg.bind_address, "`"].concat()))?;
+    let addr = SocketAddr::new(ip, cfg.server_port);
+    let shutdown = state.shutdown.clone();
+    let metrics = state.services.metrics_service.clone();
+    state.configuration.spawn_watcher(shutdown.clone(), move |ok| metrics.job_result("configuration_reload", ok));
+    state.services.job_service.spawn_scheduler(shutdown.clone());
+    let app = router(state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+    let mut server = if cfg.tls_enabled()
+    {
+        let tls = tls::load(&cfg).await?;
+        let metrics = state.services.metrics_service.clone();
+        tls::spawn_reloader(tls.clone(), &cfg, shutdown.clone(), move |ok| metrics.job_result("tls_reload", ok));
+        if cfg.http_redirect_port != 0
+        {
+            tls::spawn_redirect(ip, &cfg, shutdown.clone()).await?;
+        }
+        let handle = axum_server::Handle::new();
+        let signal_handle = handle.clone();
+        let signal = shutdown.clone();
+        tokio::spawn(async move
+        {
+            shutdown_signal(signal).await;
+            signal_handle.graceful_shutdown(None);
+        });
+        debug!("Апи сервера доступно на https://{}", &addr);
+        let server = axum_server::bind_rustls(addr, tls).handle(handle).serve(app);
+        tokio::spawn(async move { server.await })
+    }
+    else
+    {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        debug!("Апи сервера доступно на http://{}", &addr);
+        let server = axum::serve(listener, app).with_graceful_shutdown(shutdown_signal(shutdown.clone()));
+        tokio::spawn(async move { server.await })
+    };
+    let result = tokio::select!
+    {
+        result = &mut server => result.map_err(std::io::Error::other).and_then(|r| r),
+        _ = drain_timeout(&state, shutdown) =>
+        {
+            logger::warn!("Незавершенные запросы не обработаны за отведенное время, сервер будет остановлен принудительно");
+            server.abort();
+            Ok(())
+        }
+    };
+    state.services.database_service.close().await;
+    logger::info!("Сервер остановлен");
+    Ok(result?)
+}
+
+///Ожидание SIGINT, SIGTERM или отмены `shutdown`, после сигнала отменяет `shutdown` чтобы остановить фоновые задачи
+async fn shutdown_signal(shutdown: CancellationToken)
+{
+    let ctrl_c = async
+    {
+        if let Err(e) = tokio::signal::ctrl_c().await
+        {
+            logger::error!("Не удалось подписаться на SIGINT: {}", e.to_string());
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async
+    {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        {
+            Ok(mut s) => { s.recv().await; },
+            Err(e) =>
+            {
+                logger::error!("Не удалось подписаться на SIGTERM: {}", e.to_string());
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select!
+    {
+        _ = ctrl_c => logger::info!("Получен SIGINT, остановка сервера"),
+        _ = terminate => logger::info!("Получен SIGTERM, остановка сервера"),
+        _ = shutdown.cancelled() => logger::info!("Остановка сервера")
+    }
+    shutdown.cancel();
+}
+
+///Завершается через `shutdown_timeout` секунд после начала остановки
+async fn drain_timeout(state: &AppState, shutdown: CancellationToken)
+{
+    shutdown.cancelled().await;
+    let timeout = state.configuration.get().shutdown_timeout;
+    tokio::time::sleep(Duration::from_secs(timeout as u64)).await;
 }
 
 
@@ -21,7 +109,8 @@ mod tests
     async fn test_running()
     {
         logger::StructLogger::new_default();
-        super::start().await;
+        let state = std::sync::Arc::new(crate::state::AppState::initialize().await.unwrap());
+        let _ = super::start(state).await;
         loop 
         {
             tokio::time::sleep(tokio::time::Duration::from_millis(60000)).await;
@@ -0,0 +1,126 @@
+use std::{net::{IpAddr, SocketAddr}, path::Path, time::{Duration, SystemTime}};
+use axum::{http::{header::HOST, uri::Authority, HeaderMap, Uri}, response::{IntoResponse, Redirect, Response}, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use hyper::StatusCode;
+use tokio_util::sync::CancellationToken;
+use crate::{configuration::Configuration, error::Problem, i18n::tr, Error};
+
+///интервал проверки изменения файлов сертификата
+const CERT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+pub async fn load(cfg: &Configuration) -> Result<RustlsConfig, Error>
+{
+    RustlsConfig::from_pem_file(&cfg.tls_cert_path, &cfg.tls_key_path).await
+        .map_err(|e| Error::ValidationError(["Не удалось загрузить сертификат `", &cfg.tls_cert_path, "`: ", &e.to_string()].concat()))
+}
+
+fn modified(paths: &(String, String)) -> Option<(SystemTime, SystemTime)>
+{
+    let cert = std::fs::metadata(Path::new(&paths.0)).and_then(|m| m.modified()).ok()?;
+    let key = std::fs::metadata(Path::new(&paths.1)).and_then(|m| m.modified()).ok()?;
+    Some((cert, key))
+}
+
+///Перезагрузка сертификата и ключа при изменении файлов, новые соединения используют новый сертификат.
+/// Если новые файлы не удалось загрузить продолжает работать старый сертификат
+pub fn spawn_reloader<F: Fn(bool) + Send + 'static>(tls: RustlsConfig, cfg: &Configuration, shutdown: CancellationToken, on_reload: F)
+{
+    let paths = (cfg.tls_cert_path.clone(), cfg.tls_key_path.clone());
+    tokio::spawn(async move
+    {
+        let mut last = modified(&paths);
+        loop
+        {
+            tokio::select!
+            {
+                _ = shutdown.cancelled() => break,
+                _ = tokio::time::sleep(CERT_CHECK_INTERVAL) => {}
+            }
+            let current = modified(&paths);
+            if current.is_none() || current == last
+            {
+                continue;
+            }
+            last = current;
+            match tls.reload_from_pem_file(&paths.0, &paths.1).await
+            {
+                Ok(_) =>
+                {
+                    logger::info!("Сертификат `{}` перезагружен", &paths.0);
+                    on_reload(true);
+                },
+                Err(e) =>
+                {
+                    logger::error!("Ошибка перезагрузки сертификата `{}`, используется предыдущий: {}", &paths.0, e.to_string());
+                    on_reload(false);
+                }
+            }
+        }
+    });
+}
+
+///Адрес https для запроса, пришедшего по http, порт 443 в адресе не указывается
+fn https_location(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Option<String>
+{
+    let authority = headers.get(HOST)?.to_str().ok()?.parse::<Authority>().ok()?;
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    if https_port == 443
+    {
+        Some(["https://", authority.host(), path].concat())
+    }
+    else
+    {
+        Some(["https://", authority.host(), ":", &https_port.to_string(), path].concat())
+    }
+}
+
+///Слушатель http на `http_redirect_port`, перенаправляющий все запросы на https
+pub async fn spawn_redirect(ip: IpAddr, cfg: &Configuration, shutdown: CancellationToken) -> Result<(), Error>
+{
+    let https_port = cfg.server_port;
+    let addr = SocketAddr::new(ip, cfg.http_redirect_port);
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move
+    {
+        redirect(&headers, &uri, https_port)
+    });
+    logger::debug!("Перенаправление http на https доступно на {}", &addr);
+    tokio::spawn(async move
+    {
+        let server = axum::serve(listener, app).with_graceful_shutdown(async move { shutdown.cancelled().await });
+        if let Err(e) = server.await
+        {
+            logger::error!("Ошибка слушателя перенаправления на https: {}", e.to_string());
+        }
+    });
+    Ok(())
+}
+
+fn redirect(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Response
+{
+    match https_location(headers, uri, https_port)
+    {
+        Some(location) => Redirect::permanent(&location).into_response(),
+        None => Problem::new(StatusCode::BAD_REQUEST, "missing_host", tr("missing_host")).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use axum::http::{header::HOST, HeaderMap, HeaderValue, Uri};
+    use super::https_location;
+
+    #[test]
+    fn test_https_location()
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("planner.example:80"));
+        let uri: Uri = "/auth/login?next=%2F".parse().unwrap();
+        assert_eq!(https_location(&headers, &uri, 443).unwrap(), "https://planner.example/auth/login?next=%2F");
+        assert_eq!(https_location(&headers, &uri, 8443).unwrap(), "https://planner.example:8443/auth/login?next=%2F");
+        headers.insert(HOST, HeaderValue::from_static("[::1]:8080"));
+        assert_eq!(https_location(&headers, &"/".parse().unwrap(), 443).unwrap(), "https://[::1]/");
+        assert!(https_location(&HeaderMap::new(), &uri, 443).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+mod structs;
+
+use std::sync::Arc;
+use axum::{extract::{DefaultBodyLimit, State}, http::header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE}, response::IntoResponse, routing::{get, post}, Extension, Router};
+use hyper::StatusCode;
+use super::extract::{Json, Multipart, Path, Query};
+use structs::{AvatarQuery, AvatarUpload, ExportQuery, ImportQuery};
+use crate::{error::Problem, i18n::tr, middleware::{AuthCheck, AuthLayer, RateLimitLayer, SessionExtension}, services::{ImportReport, TransferFormat}, state::AppState, Error, Role};
+
+pub fn users_router(app_state: Arc<AppState>) -> Router
+{   
+    Router::new()      
+        .route("/users/import", post(import_users)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/users/export", get(export_users)
+            .route_layer(RateLimitLayer::new("admin", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::Administrator])))
+
+        .route("/users/avatar", post(upload_avatar).delete(delete_avatar)
+            .layer(DefaultBodyLimit::max(app_state.configuration.get().avatar_max_size_kb as usize * 1024 + 64 * 1024))
+            .route_layer(RateLimitLayer::new("api", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &[Role::User, Role::Administrator])))
+
+        .route("/users/avatar/{user_id}", get(get_avatar)
+            .route_layer(RateLimitLayer::new("public", Arc::clone(&app_state))))
+
+        .with_state(app_state.clone())
+}
+
+///Тело запроса - содержимое файла импорта в формате `format`
+#[utoipa::path(post, path = "/users/import", tag = "users",
+    params(ImportQuery),
+    request_body(content = String, content_type = "text/plain", description = "Содержимое файла импорта в формате csv или json"),
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Отчет импорта", body = ImportReport)))]
+pub async fn import_users(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ImportQuery>,
+    body: String)
+-> Result<impl IntoResponse, Error>
+{
+    let report = app_state.services.user_transfer_service.import(&body, query.format, query.dry_run).await?;
+    Ok((
+        StatusCode::OK,
+        Json(report)
+    ))
+}
+
+#[utoipa::path(get, path = "/users/export", tag = "users",
+    params(ExportQuery),
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["Administrator"])),
+    responses((status = 200, description = "Файл экспорта в формате csv или json", body = String, content_type = ["text/csv", "application/json"])))]
+pub async fn export_users(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<ExportQuery>)
+-> Result<impl IntoResponse, Error>
+{
+    let data = app_state.services.user_transfer_service.export(query.format).await?;
+    let filename = match query.format
+    {
+        TransferFormat::Csv => "attachment; filename=\"users.csv\"",
+        TransferFormat::Json => "attachment; filename=\"users.json\""
+    };
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, query.format.content_type()), (CONTENT_DISPOSITION, filename)],
+        data
+    ))
+}
+
+///Ожидается multipart с полем `avatar`
+#[utoipa::path(post, path = "/users/avatar", tag = "users",
+    request_body(content = AvatarUpload, content_type = "multipart/form-data"),
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses(
+        (status = 200, description = "Url загруженного аватара", body = String, content_type = "text/plain"),
+        (status = 422, description = "Файл не является допустимым изображением", body = Problem, content_type = "application/problem+json")))]
+pub async fn upload_avatar(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    mut multipart: Multipart)
+-> Result<impl IntoResponse, Error>
+{
+    while let Some(field) = multipart.next_field().await.map_err(|e| Error::AvatarError(e.body_text()))?
+    {
+        if field.name() == Some("avatar")
+        {
+            let content_type = field.content_type().map(|c| c.to_owned());
+            let data = field.bytes().await.map_err(|e| Error::AvatarError(e.body_text()))?;
+            let url = app_state.services.avatar_service.upload(&session_wrapper.session.user_id, content_type.as_deref(), data.to_vec()).await?;
+            return Ok((
+                StatusCode::OK,
+                url
+            ));
+        }
+    }
+    Err(Error::AvatarError("avatar_field_missing".to_owned()))
+}
+
+#[utoipa::path(delete, path = "/users/avatar", tag = "users",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 200, description = "Аватар удален", body = String, content_type = "text/plain")))]
+pub async fn delete_avatar(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>)
+-> Result<impl IntoResponse, Error>
+{
+    app_state.services.avatar_service.delete(&session_wrapper.session.user_id).await?;
+    Ok((
+        StatusCode::OK,
+        tr("avatar_deleted")
+    ))
+}
+
+#[utoipa::path(get, path = "/users/avatar/{user_id}", tag = "users",
+    params(("user_id" = uuid::Uuid, Path, description = "id пользователя"), AvatarQuery),
+    responses(
+        (status = 200, description = "Изображение аватара", body = Vec<u8>, content_type = "image/png"),
+        (status = 404, description = "Аватар не загружен", body = Problem, content_type = "application/problem+json")))]
+pub async fn get_avatar(
+    State(app_state): State<Arc<AppState>>,
+    Path(user_id): Path<uuid::Uuid>,
+    Query(query): Query<AvatarQuery>)
+-> Result<impl IntoResponse, Error>
+{
+    let path = app_state.services.avatar_service.avatar_path(&user_id, query.size);
+    if !tokio::fs::try_exists(&path).await?
+    {
+        return Err(Error::AvatarNotFound);
+    }
+    let data = tokio::fs::read(&path).await?;
+    //по адресу с версией всегда лежит один и тот же файл, без версии даем кэшировать ненадолго
+    let cache = if query.v.is_some()
+    {
+        "public, max-age=31536000, immutable"
+    }
+    else
+    {
+        "public, max-age=300"
+    };
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, "image/png"), (CACHE_CONTROL, cache)],
+        data
+    ).into_response())
+}
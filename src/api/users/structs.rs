@@ -0,0 +1,38 @@
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+use crate::services::TransferFormat;
+
+#[derive(Debug, Deserialize, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery
+{
+    pub format: TransferFormat,
+    #[serde(default)]
+    pub dry_run: bool
+}
+
+#[derive(Debug, Deserialize, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery
+{
+    pub format: TransferFormat
+}
+
+#[derive(Debug, Deserialize, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AvatarQuery
+{
+    pub size: Option<u32>,
+    ///версия аватара из url, если указана ответ кэшируется без ограничений
+    pub v: Option<String>
+}
+
+///Тело запроса загрузки аватара, используется только в описании api
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct AvatarUpload
+{
+    ///изображение png, jpeg, webp или gif
+    #[schema(value_type = String, format = Binary)]
+    pub avatar: Vec<u8>
+}
@@ -0,0 +1,138 @@
+use std::{sync::Arc, time::Duration};
+use axum::{extract::{ws::{Message, WebSocket}, State, WebSocketUpgrade}, response::IntoResponse, routing::get, Extension, Router};
+use tokio::time::Instant;
+use crate::{db::Session, i18n::{current_locale, with_locale}, middleware::{AuthCheck, AuthLayer, RateLimitLayer, SessionExtension}, services::{ClientMessage, ServerMessage, Viewer, WsConnection}, state::AppState, Error, Role};
+
+const WS_ROLES: [Role; 2] = [Role::User, Role::Administrator];
+///интервал проверки сессии открытого соединения: сессия могла истечь или быть удалена из другого процесса (cli)
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn ws_router(app_state: Arc<AppState>) -> Router
+{
+    Router::new()
+        .route("/ws", get(ws)
+            .route_layer(RateLimitLayer::new("session", Arc::clone(&app_state)))
+            .route_layer(AuthLayer::with_roles(
+                AuthCheck::All,
+                Arc::clone(&app_state),
+                &WS_ROLES)))
+        .with_state(app_state.clone())
+}
+
+///Двусторонний канал для совместной работы, проверки при подключении те же что и у остальных маршрутов: сессия, отпечаток и ключ доступа.
+/// Сообщения в json с полем `type`: клиент отправляет `subscribe`/`unsubscribe` с `topic` вида `project:<id>` или `board:<id>`
+/// и `refresh` с `access_key` после обновления ключа доступа. Сервер отвечает `subscribed`, `unsubscribed`, `refreshed`,
+/// присылает изменения (`event`), идентификаторы зрителей (`presence`), `resync` при потере сообщений и `error` с `Problem`.
+/// Если новый ключ не прислан за время жизни ключа доступа, соединение закрывается
+#[utoipa::path(get, path = "/ws", tag = "events",
+    security(("session_cookie" = [], "fingerprint" = [], "bearer" = ["User", "Administrator"])),
+    responses((status = 101, description = "Соединение переведено на протокол WebSocket")))]
+pub async fn ws(
+    State(app_state): State<Arc<AppState>>,
+    Extension(session_wrapper): Extension<SessionExtension>,
+    upgrade: WebSocketUpgrade)
+-> Result<impl IntoResponse, Error>
+{
+    let session = Arc::clone(&session_wrapper.session);
+    let user = app_state.services.database_service.user_repository.get_user(&session.user_id).await?;
+    let role = session_wrapper.role.as_deref().and_then(Role::try_parse).unwrap_or(user.role);
+    let viewer = Viewer
+    {
+        user_id: user.id
+    };
+    //язык запроса сохраняется для сообщений об ошибках внутри соединения
+    let locale = current_locale();
+    Ok(upgrade.on_upgrade(move |socket| with_locale(locale, connection(socket, app_state, session, viewer, role))))
+}
+
+async fn connection(mut socket: WebSocket, app_state: Arc<AppState>, session: Arc<Session>, viewer: Viewer, role: Role)
+{
+    let mut connection = app_state.services.ws_service.connect(session.session_id, viewer, role);
+    logger::debug!("Открыто соединение WebSocket сессии {}", session.session_id);
+    let mut key_deadline = Instant::now() + key_lifetime(&app_state);
+    let mut check = tokio::time::interval_at(Instant::now() + SESSION_CHECK_INTERVAL, SESSION_CHECK_INTERVAL);
+    loop
+    {
+        let replies: Vec<String> = tokio::select!
+        {
+            _ = app_state.shutdown.cancelled() => break,
+            _ = tokio::time::sleep_until(key_deadline) =>
+            {
+                let expired: ServerMessage = Error::AuthError("ws_access_key_expired".to_owned()).into();
+                let _ = socket.send(Message::Text(expired.to_json().into())).await;
+                break;
+            }
+            _ = check.tick() =>
+            {
+                if !super::session_active(&app_state, &session.session_id).await
+                {
+                    break;
+                }
+                continue;
+            }
+            message = connection.recv() => match message
+            {
+                Some(message) => vec![message.to_string()],
+                None => break
+            },
+            incoming = socket.recv() => match incoming
+            {
+                Some(Ok(Message::Text(text))) => match handle_message(&app_state, &mut connection, &session, text.as_str()).await
+                {
+                    Ok((replies, refreshed)) =>
+                    {
+                        if refreshed
+                        {
+                            key_deadline = Instant::now() + key_lifetime(&app_state);
+                        }
+                        replies.iter().map(|r| r.to_json()).collect()
+                    },
+                    //недействительный ключ доступа закрывает соединение, остальные ошибки только сообщаются клиенту
+                    Err(e @ Error::JwtError(_)) =>
+                    {
+                        let _ = socket.send(Message::Text(ServerMessage::from(e).to_json().into())).await;
+                        break;
+                    },
+                    Err(e) => vec![ServerMessage::from(e).to_json()]
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue
+            }
+        };
+        for reply in replies
+        {
+            if socket.send(Message::Text(reply.into())).await.is_err()
+            {
+                return;
+            }
+        }
+    }
+    logger::debug!("Закрыто соединение WebSocket сессии {}", session.session_id);
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+///Ответы на сообщение клиента и признак обновленного ключа доступа
+async fn handle_message(app_state: &AppState, connection: &mut WsConnection, session: &Session, text: &str) -> Result<(Vec<ServerMessage>, bool), Error>
+{
+    let message: ClientMessage = serde_json::from_str(text)?;
+    match message
+    {
+        ClientMessage::Subscribe { topic } => Ok((vec![connection.subscribe(&topic)?], false)),
+        ClientMessage::Unsubscribe { topic } => Ok((vec![connection.unsubscribe(&topic)?], false)),
+        ClientMessage::Refresh { access_key } =>
+        {
+            let roles = WS_ROLES.map(|r| r.to_string());
+            let claims = app_state.services.jwt_service.validate(&session.user_id, &access_key, &roles, &[] as &[String]).await?;
+            let role = claims.role().and_then(|r| Role::try_parse(r)).unwrap_or(Role::NonPrivileged);
+            let mut replies = vec![ServerMessage::Refreshed];
+            replies.extend(connection.set_role(role).into_iter().map(|topic| ServerMessage::Unsubscribed { topic }));
+            Ok((replies, true))
+        }
+    }
+}
+
+///Ключ, с которым открыто соединение, мог быть выдан раньше, поэтому соединение живет не дольше времени жизни нового ключа
+fn key_lifetime(app_state: &AppState) -> Duration
+{
+    Duration::from_secs(app_state.configuration.get().access_key_lifetime as u64 * 60)
+}
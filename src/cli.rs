@@ -0,0 +1,411 @@
+use std::{io::Write, path::Path, sync::Arc};
+use clap::{Args, Parser, Subcommand};
+use crate::{configuration::{Configuration, ConfigurationHandle}, db::{DatabaseService, UserDbo}, services::{backup_database, BackupService, JwtService}, state::AppState, Error, Role};
+
+#[derive(Parser, Debug)]
+#[command(name = "planner", version, about = "Сервер планировщика")]
+pub struct Cli
+{
+    ///путь к файлу настроек
+    #[arg(short, long, global = true, default_value = "configuration.toml")]
+    pub config: String,
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command
+{
+    ///запуск сервера (команда по умолчанию)
+    Serve,
+    ///применение миграций баз данных
+    Migrate,
+    ///создание пользователя с правами администратора
+    CreateAdmin(CreateAdminArgs),
+    ///сброс пароля пользователя
+    ResetPassword(ResetPasswordArgs),
+    ///список пользователей
+    ListUsers,
+    ///удаление сессий пользователя
+    RevokeSessions(RevokeSessionsArgs),
+    ///генерация нового ключа подписи, старый ключ сохраняется рядом в файле `<key_path>.<uuid>.bak`
+    RotateKeys,
+    ///резервная копия баз данных в `backup_directory`, можно выполнять при работающем сервере
+    Backup,
+    ///восстановление базы данных из резервной копии, сервер должен быть остановлен
+    Restore(RestoreArgs)
+}
+
+///Флаги имеют приоритет над файлом настроек и переменными окружения `PLANNER_*`
+#[derive(Args, Debug, Default, Clone)]
+pub struct ConfigOverrides
+{
+    ///адрес на котором сервер принимает соединения
+    #[arg(long, global = true)]
+    pub bind_address: Option<String>,
+    ///порт сервера
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+    ///путь к основной базе данных
+    #[arg(long, global = true)]
+    pub database_path: Option<String>,
+    ///путь к базе данных сессий
+    #[arg(long, global = true)]
+    pub sessions_database_path: Option<String>,
+    ///хранить сессии в основной базе данных
+    #[arg(long, global = true)]
+    pub unified_database: bool,
+    ///путь к ключу подписи
+    #[arg(long, global = true)]
+    pub key_path: Option<String>
+}
+impl ConfigOverrides
+{
+    fn apply(self, cfg: &mut Configuration)
+    {
+        if let Some(bind_address) = self.bind_address
+        {
+            cfg.bind_address = bind_address;
+        }
+        if let Some(port) = self.port
+        {
+            cfg.server_port = port;
+        }
+        if let Some(database_path) = self.database_path
+        {
+            cfg.database_path = database_path;
+        }
+        if let Some(sessions_database_path) = self.sessions_database_path
+        {
+            cfg.sessions_database_path = sessions_database_path;
+        }
+        if self.unified_database
+        {
+            cfg.unified_database = true;
+        }
+        if let Some(key_path) = self.key_path
+        {
+            cfg.key_path = key_path;
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CreateAdminArgs
+{
+    ///если не указано будет запрошено интерактивно
+    #[arg(short, long)]
+    pub username: Option<String>,
+    ///если не указан будет запрошен интерактивно
+    #[arg(short, long)]
+    pub password: Option<String>
+}
+
+#[derive(Args, Debug)]
+pub struct ResetPasswordArgs
+{
+    pub username: String,
+    ///если не указан будет запрошен интерактивно
+    #[arg(short, long)]
+    pub password: Option<String>
+}
+
+#[derive(Args, Debug)]
+pub struct RevokeSessionsArgs
+{
+    ///имя пользователя, сессии которого будут удалены
+    #[arg(required_unless_present = "all")]
+    pub username: Option<String>,
+    ///удалить сессии всех пользователей
+    #[arg(long)]
+    pub all: bool
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreArgs
+{
+    ///файл резервной копии
+    pub file: String,
+    ///база данных `planner` или `sessions`, по умолчанию определяется по имени файла
+    #[arg(short, long)]
+    pub database: Option<String>
+}
+
+pub async fn run(cli: Cli) -> Result<(), Error>
+{
+    let mut cfg = Configuration::load_from(&cli.config)?;
+    let overrides = cli.overrides;
+    overrides.clone().apply(&mut cfg);
+    cfg.validate()?;
+    //при перезагрузке настроек флаги применяются повторно
+    let cfg = ConfigurationHandle::with_source(cfg, &cli.config, move |c| overrides.clone().apply(c));
+    match cli.command.unwrap_or(Command::Serve)
+    {
+        Command::Serve =>
+        {
+            let state = Arc::new(AppState::initialize_with(cfg).await?);
+            crate::api::start(state).await
+        },
+        Command::Migrate =>
+        {
+            for (name, version) in crate::db::migrate(&cfg.get()).await?
+            {
+                println!("База данных `{}`: версия схемы {}", name, version);
+            }
+            Ok(())
+        },
+        Command::CreateAdmin(args) => create_admin(&cfg.get(), args).await,
+        Command::ResetPassword(args) => reset_password(&cfg.get(), args).await,
+        Command::ListUsers => list_users(&cfg.get()).await,
+        Command::RevokeSessions(args) => revoke_sessions(&cfg.get(), args).await,
+        Command::RotateKeys => rotate_keys(&cfg.get()),
+        Command::Backup => backup(cfg).await,
+        Command::Restore(args) => restore(&cfg.get(), args).await
+    }
+}
+
+fn prompt(message: &str) -> Result<String, Error>
+{
+    print!("{}: ", message);
+    std::io::stdout().flush()?;
+    let mut value = String::new();
+    std::io::stdin().read_line(&mut value)?;
+    Ok(value.trim().to_owned())
+}
+fn prompt_password() -> Result<String, Error>
+{
+    let password = rpassword::prompt_password("Пароль: ")?;
+    let confirm = rpassword::prompt_password("Повторите пароль: ")?;
+    if password != confirm
+    {
+        Err(Error::ValidationError("Пароли не совпадают".to_owned()))
+    }
+    else
+    {
+        Ok(password)
+    }
+}
+fn check_password(password: &str) -> Result<(), Error>
+{
+    if password.len() < 6
+    {
+        Err(Error::ValidationError("Пароль должен быть не короче 6 символов".to_owned()))
+    }
+    else
+    {
+        Ok(())
+    }
+}
+
+///Одноразовым командам нужна только база данных: сервисы сервера не создаются, фоновые задачи не регистрируются
+async fn open_database(cfg: &Configuration) -> Result<DatabaseService, Error>
+{
+    DatabaseService::new(cfg).await
+}
+
+async fn create_admin(cfg: &Configuration, args: CreateAdminArgs) -> Result<(), Error>
+{
+    let username = if let Some(u) = args.username { u } else { prompt("Имя пользователя")? };
+    let password = if let Some(p) = args.password { p } else { prompt_password()? };
+    let database = open_database(cfg).await?;
+    let id = insert_admin(&database, username, password).await;
+    database.close().await;
+    println!("Администратор создан, id: {}", id?);
+    Ok(())
+}
+async fn insert_admin(database: &DatabaseService, username: String, password: String) -> Result<uuid::Uuid, Error>
+{
+    if username.is_empty()
+    {
+        return Err(Error::ValidationError("Не указано имя пользователя".to_owned()));
+    }
+    check_password(&password)?;
+    let repository = &database.user_repository;
+    if repository.username_is_busy(&username).await?
+    {
+        return Err(Error::ValidationError(["Имя пользователя `", &username, "` уже занято"].concat()));
+    }
+    let user = UserDbo
+    {
+        id: uuid::Uuid::now_v7(),
+        username,
+        password,
+        is_active: true,
+        role: Role::Administrator,
+        audiences: Vec::new(),
+        avatar: None,
+        profile: None,
+        contacts: Vec::new()
+    };
+    let id = user.id;
+    repository.create(user).await?;
+    Ok(id)
+}
+
+async fn reset_password(cfg: &Configuration, args: ResetPasswordArgs) -> Result<(), Error>
+{
+    let password = if let Some(p) = args.password { p } else { prompt_password()? };
+    check_password(&password)?;
+    let database = open_database(cfg).await?;
+    let result = change_password(&database, &args.username, &password).await;
+    database.close().await;
+    result?;
+    println!("Пароль пользователя `{}` изменен", &args.username);
+    Ok(())
+}
+async fn change_password(database: &DatabaseService, username: &str, password: &str) -> Result<(), Error>
+{
+    let user = database.user_repository.get_user_by_username(username).await?;
+    database.user_repository.set_password(&user.id, password).await
+}
+
+async fn list_users(cfg: &Configuration) -> Result<(), Error>
+{
+    let database = open_database(cfg).await?;
+    let users = database.user_repository.get_users().await?;
+    database.close().await;
+    println!("{:<38} {:<24} {:<14} {:<8} {}", "id", "username", "role", "active", "contacts");
+    for u in users
+    {
+        let contacts: Vec<String> = u.contacts.iter().map(|c| c.contact.clone()).collect();
+        println!("{:<38} {:<24} {:<14} {:<8} {}", u.id.to_string(), u.username, u.role.to_string(), u.is_active, contacts.join(", "));
+    }
+    Ok(())
+}
+
+async fn revoke_sessions(cfg: &Configuration, args: RevokeSessionsArgs) -> Result<(), Error>
+{
+    let database = open_database(cfg).await?;
+    let count = delete_sessions(&database, args).await;
+    database.close().await;
+    println!("Удалено сессий: {}", count?);
+    Ok(())
+}
+async fn delete_sessions(database: &DatabaseService, args: RevokeSessionsArgs) -> Result<u64, Error>
+{
+    use crate::db::ISessionRepository;
+    let users = if args.all
+    {
+        database.user_repository.get_users().await?
+    }
+    else
+    {
+        vec![database.user_repository.get_user_by_username(args.username.as_deref().unwrap_or_default()).await?]
+    };
+    let mut count = 0;
+    for u in users
+    {
+        count += database.session_repository.delete_all_sessions(&u.id).await?;
+    }
+    Ok(count)
+}
+
+fn rotate_keys(cfg: &Configuration) -> Result<(), Error>
+{
+    let key = Path::new(&cfg.key_path);
+    let backup = if key.exists()
+    {
+        let backup = [cfg.key_path.as_str(), ".", &uuid::Uuid::now_v7().simple().to_string(), ".bak"].concat();
+        std::fs::rename(key, &backup)?;
+        Some(backup)
+    }
+    else
+    {
+        None
+    };
+    //при отсуствии файла ключа сервис генерирует новый ключ
+    let _ = JwtService::new(&cfg.key_path);
+    if !key.exists()
+    {
+        if let Some(backup) = backup.as_ref()
+        {
+            std::fs::rename(backup, key)?;
+        }
+        return Err(Error::AuthError("Не удалось сгенерировать новый ключ, старый ключ восстановлен".to_owned()));
+    }
+    if let Some(backup) = backup
+    {
+        println!("Старый ключ сохранен в `{}`", backup);
+    }
+    println!("Новый ключ создан, выданные ранее ключи доступа станут недействительны, сессии пользователей сохранятся. Перезапустите сервер");
+    Ok(())
+}
+
+async fn backup(cfg: ConfigurationHandle) -> Result<(), Error>
+{
+    let database = Arc::new(open_database(&cfg.get()).await?);
+    let files = BackupService::new(Arc::clone(&database), cfg).backup().await;
+    database.close().await;
+    for file in files?
+    {
+        println!("База данных `{}`: резервная копия `{}` ({} байт)", file.database, file.path, file.size);
+    }
+    Ok(())
+}
+
+async fn restore(cfg: &Configuration, args: RestoreArgs) -> Result<(), Error>
+{
+    let file = Path::new(&args.file);
+    let database = args.database.as_deref()
+        .or_else(|| backup_database(file))
+        .ok_or_else(|| Error::ValidationError(["Не удалось определить базу данных по имени файла `", &args.file, "`, укажите ее через `--database`"].concat()))?;
+    let restored = crate::db::restore(cfg, database, file).await?;
+    println!("База данных `{}` восстановлена из `{}` в `{}`, предыдущий файл сохранен с расширением `.before-restore`", database, &args.file, restored);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::{configuration::Configuration, db::{DatabaseService, ISessionRepository}, Error, Role};
+    use super::{change_password, delete_sessions, insert_admin, RevokeSessionsArgs};
+
+    async fn database() -> DatabaseService
+    {
+        DatabaseService::in_memory(&Configuration::default()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_create_admin()
+    {
+        let database = database().await;
+        let id = insert_admin(&database, "admin".to_owned(), "password".to_owned()).await.unwrap();
+        let user = database.user_repository.get_user(&id).await.unwrap();
+        assert_eq!((user.role, user.is_active), (Role::Administrator, true));
+        assert_eq!(database.user_repository.login("admin", "password").await.unwrap().id, id);
+        assert!(matches!(insert_admin(&database, "admin".to_owned(), "password".to_owned()).await, Err(Error::ValidationError(_))));
+        assert!(matches!(insert_admin(&database, String::new(), "password".to_owned()).await, Err(Error::ValidationError(_))));
+        assert!(matches!(insert_admin(&database, "admin2".to_owned(), "12345".to_owned()).await, Err(Error::ValidationError(_))));
+        assert_eq!(database.user_repository.get_users().await.unwrap().len(), 1);
+    }
+    #[tokio::test]
+    async fn test_reset_password()
+    {
+        let database = database().await;
+        let id = insert_admin(&database, "admin".to_owned(), "password".to_owned()).await.unwrap();
+        change_password(&database, "admin", "new_password").await.unwrap();
+        assert!(database.user_repository.login("admin", "password").await.is_err());
+        assert_eq!(database.user_repository.login("admin", "new_password").await.unwrap().id, id);
+        assert!(change_password(&database, "unknown", "new_password").await.is_err());
+    }
+    #[tokio::test]
+    async fn test_revoke_sessions()
+    {
+        let database = database().await;
+        let first = insert_admin(&database, "first".to_owned(), "password".to_owned()).await.unwrap();
+        let second = insert_admin(&database, "second".to_owned(), "password".to_owned()).await.unwrap();
+        for user_id in [&first, &first, &second]
+        {
+            let _ = database.session_repository.create_session(user_id, 1, 3, "127.0.0.1", "fingerprint", "test").await.unwrap();
+        }
+        let count = delete_sessions(&database, RevokeSessionsArgs { username: Some("first".to_owned()), all: false }).await.unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(database.session_repository.sessions_count(&first).await.unwrap(), 0);
+        assert_eq!(database.session_repository.sessions_count(&second).await.unwrap(), 1);
+        let count = delete_sessions(&database, RevokeSessionsArgs { username: None, all: true }).await.unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(database.session_repository.sessions_count(&second).await.unwrap(), 0);
+    }
+}
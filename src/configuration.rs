@@ -1,9 +1,85 @@
+use std::{collections::BTreeMap, fmt::Display, net::IpAddr, path::Path, str::FromStr, sync::Arc, time::{Duration, SystemTime}};
+use arc_swap::ArcSwap;
+use tokio_util::sync::CancellationToken;
+use axum::http::{HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
+use crate::{i18n::{tr, tr_args}, Error};
 
 
 const FILENAME: &str = "configuration.toml";
+///префикс переменных окружения, например `PLANNER_SERVER_PORT`
+const ENV_PREFIX: &str = "PLANNER_";
+///параметры, которые нельзя изменить без перезапуска сервера
+const RESTART_REQUIRED: &[&str] = &[
+    "server_port",
+    "bind_address",
+    "database_path",
+    "sessions_database_path",
+    "unified_database",
+    "database_max_connections",
+    "database_acquire_timeout",
+    "database_busy_timeout",
+    "database_synchronous",
+    "database_foreign_keys",
+    "key_path",
+    "auto_migrate",
+    "session_cookie_name",
+    "fingerprint_header_name",
+    "avatars_directory",
+    "avatar_max_size_kb",
+    "api_docs_ui",
+    "tls_cert_path",
+    "tls_key_path",
+    "http_redirect_port",
+    "frontend_directory",
+    "backup_interval",
+    "sse_replay_buffer"
+];
+///интервал проверки изменения файла настроек
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+///путь к базе данных, при котором база создается в памяти и не сохраняется между запусками
+pub const IN_MEMORY_DATABASE: &str = ":memory:";
+///группы маршрутов, для которых можно задать ограничение частоты запросов
+pub const RATE_LIMIT_GROUPS: &[&str] = &["auth", "session", "api", "admin", "public"];
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+///По какому признаку клиента считаются запросы
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKey
+{
+    ///авторизованный пользователь, для запросов без авторизации - ip адрес
+    User,
+    ///сессия пользователя, для запросов без авторизации - ip адрес
+    Session,
+    Ip,
+    ///ключ доступа из заголовка `Authorization`, проверенный `AuthLayer`, при его отсутствии - ip адрес
+    Token
+}
+///Ограничение по алгоритму token bucket: клиент может сделать `capacity` запросов подряд,
+/// после чего запросы восстанавливаются со скоростью `refill_per_minute` в минуту
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, utoipa::ToSchema)]
+pub struct RateLimit
+{
+    pub key: RateLimitKey,
+    pub capacity: u32,
+    pub refill_per_minute: u32
+}
+impl RateLimit
+{
+    fn new(key: RateLimitKey, capacity: u32, refill_per_minute: u32) -> Self
+    {
+        Self
+        {
+            key,
+            capacity,
+            refill_per_minute
+        }
+    }
+}
+
+///Неизвестные параметры в файле настроек считаются ошибкой, чтобы опечатка в имени параметра не игнорировалась
+#[derive(Serialize, Deserialize, Debug, Clone, utoipa::ToSchema)]
+#[serde(default, deny_unknown_fields)]
 pub struct Configuration
 {
     ///session life time in days
@@ -17,6 +93,79 @@ pub struct Configuration
     pub fingerprint_header_name: String,
     pub origins: Vec<String>,
     pub server_port: u16,
+    ///address server listens on
+    pub bind_address: String,
+    ///path to main database file, `:memory:` keeps database in memory until shutdown
+    pub database_path: String,
+    ///path to sessions database file, ignored when `unified_database` is set
+    pub sessions_database_path: String,
+    ///store sessions in main database instead of separate file
+    pub unified_database: bool,
+    ///maximum open connections in each database pool
+    pub database_max_connections: u32,
+    ///seconds to wait for free connection from pool
+    pub database_acquire_timeout: u16,
+    ///milliseconds to wait for locked database before returning error
+    pub database_busy_timeout: u32,
+    ///sqlite `synchronous` mode: `off`, `normal`, `full` or `extra`
+    pub database_synchronous: String,
+    ///enforce foreign key constraints
+    pub database_foreign_keys: bool,
+    ///path to signing key, generated if file does not exist
+    pub key_path: String,
+    ///directory for user avatars
+    pub avatars_directory: String,
+    ///maximum size of uploaded avatar in kilobytes
+    pub avatar_max_size_kb: u32,
+    ///allow verified email or phone as login instead of username
+    pub login_by_contact: bool,
+    ///allow login by one-time code or link sent to verified contact
+    pub passwordless_login: bool,
+    ///one-time login code lifetime in minutes
+    pub login_code_lifetime: u8,
+    ///maximum one-time codes requested by one user in `login_code_requests_window` minutes
+    pub login_code_max_requests: u8,
+    pub login_code_requests_window: u8,
+    ///maximum wrong code attempts before code is invalidated
+    pub login_code_max_attempts: u8,
+    ///public url of frontend, used in links sent to users
+    pub public_url: String,
+    ///apply pending database migrations on startup, otherwise run `migrate` command before start
+    pub auto_migrate: bool,
+    ///log level: `error`, `warn`, `info`, `debug`, `trace` or `off`
+    pub log_level: String,
+    ///seconds to wait for in-flight requests on shutdown
+    pub shutdown_timeout: u16,
+    ///expose prometheus metrics on `/metrics`
+    pub metrics_enabled: bool,
+    ///serve interactive api docs on `/api/docs`, specification on `/api/openapi.json` is always available
+    pub api_docs_ui: bool,
+    ///clients that are never rate limited: ip addresses, user ids or session ids
+    pub rate_limit_exempt: Vec<String>,
+    ///path to tls certificate chain in PEM, tls is enabled when certificate and key paths are set.
+    /// Files are reloaded on change without restart
+    pub tls_cert_path: String,
+    ///path to tls private key in PEM
+    pub tls_key_path: String,
+    ///port of plain http listener redirecting to https, 0 disables redirect
+    pub http_redirect_port: u16,
+    ///`max-age` of `Strict-Transport-Security` header in seconds, sent only with tls, 0 disables the header
+    pub hsts_max_age: u32,
+    ///directory with built frontend (`index.html` and assets), empty disables frontend, api is served under `/api`
+    pub frontend_directory: String,
+    ///directory for database backups
+    pub backup_directory: String,
+    ///hours between scheduled backups, 0 disables scheduled backups; the `backup` job is rescheduled on restart
+    pub backup_interval: u16,
+    ///number of backups kept for each database, older backups are deleted
+    pub backup_retention: u16,
+    ///seconds between heartbeat comments in `/api/events` streams
+    pub sse_heartbeat_interval: u16,
+    ///number of recent events kept for `Last-Event-ID` resume
+    pub sse_replay_buffer: u32,
+    ///request rate limits by route group (`auth`, `session`, `api`, `admin`, `public`), group without limit is not limited.
+    /// Must stay the last field: toml tables are written after plain values
+    pub rate_limits: BTreeMap<String, RateLimit>,
 }
 impl Default for Configuration
 {
@@ -32,38 +181,537 @@ impl Default for Configuration
             origins: vec![
                 "http://localhost:8888".to_owned()
             ],
-            server_port: 8888
+            server_port: 8888,
+            bind_address: "0.0.0.0".to_owned(),
+            database_path: "planner.sq3".to_owned(),
+            sessions_database_path: "sessions.sq3".to_owned(),
+            unified_database: false,
+            database_max_connections: 5,
+            database_acquire_timeout: 3,
+            database_busy_timeout: 5000,
+            database_synchronous: "normal".to_owned(),
+            database_foreign_keys: true,
+            key_path: "key.pkcs8".to_owned(),
+            avatars_directory: "avatars".to_owned(),
+            avatar_max_size_kb: 5120,
+            login_by_contact: false,
+            passwordless_login: false,
+            login_code_lifetime: 5,
+            login_code_max_requests: 3,
+            login_code_requests_window: 15,
+            login_code_max_attempts: 5,
+            public_url: "http://localhost:8888".to_owned(),
+            auto_migrate: true,
+            log_level: "info".to_owned(),
+            shutdown_timeout: 30,
+            metrics_enabled: true,
+            api_docs_ui: false,
+            rate_limits: BTreeMap::from([
+                ("auth".to_owned(), RateLimit::new(RateLimitKey::Ip, 20, 10)),
+                ("session".to_owned(), RateLimit::new(RateLimitKey::Session, 10, 6)),
+                ("api".to_owned(), RateLimit::new(RateLimitKey::User, 120, 120)),
+                ("admin".to_owned(), RateLimit::new(RateLimitKey::User, 60, 60)),
+                ("public".to_owned(), RateLimit::new(RateLimitKey::Ip, 300, 300))
+            ]),
+            rate_limit_exempt: Vec::new(),
+            tls_cert_path: String::new(),
+            tls_key_path: String::new(),
+            http_redirect_port: 0,
+            hsts_max_age: 31_536_000,
+            frontend_directory: "frontend/dist".to_owned(),
+            backup_directory: "backups".to_owned(),
+            backup_interval: 24,
+            backup_retention: 7,
+            sse_heartbeat_interval: 15,
+            sse_replay_buffer: 1000
         }
     }
 }
+///Ошибка в значении параметра настроек
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigurationIssue
+{
+    pub field: String,
+    pub message: String
+}
+impl ConfigurationIssue
+{
+    fn new<F: ToString, M: ToString>(field: F, message: M) -> Self
+    {
+        Self
+        {
+            field: field.to_string(),
+            message: message.to_string()
+        }
+    }
+}
+impl Display for ConfigurationIssue
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "`{}`: {}", self.field, self.message)
+    }
+}
+
 impl Configuration
 {
-    pub fn load() -> Self
+    ///Настройки из `configuration.toml` с учетом переменных окружения
+    pub fn load() -> Result<Self, Error>
     {
-        let cfg = utilites::deserialize(FILENAME, false, utilites::Serializer::Toml);
-        if cfg.is_err()
+        Self::load_from(FILENAME)
+    }
+    ///Настройки собираются по слоям: значения по умолчанию, файл `path` (если существует), переменные окружения `PLANNER_*`.
+    /// Флаги командной строки применяются поверх, после чего настройки нужно проверить через [`Configuration::validate`]
+    pub fn load_from(path: &str) -> Result<Self, Error>
+    {
+        let cfg = if Path::new(path).exists()
+        {
+            utilites::deserialize(path, false, utilites::Serializer::Toml)
+            .map_err(|e| Error::ConfigurationError(vec![ConfigurationIssue::new(path, e.to_string())]))?
+        }
+        else
         {
-            logger::error!("Ошибка десериализации настроек, {}, будут установлены настройки по умолчанию", cfg.err().unwrap());
+            logger::warn!("Файл настроек `{}` не найден, будут использованы настройки по умолчанию", path);
             Self::default()
+        };
+        cfg.apply_env(std::env::vars())
+    }
+    ///Переопределение параметров из переменных окружения вида `PLANNER_<ИМЯ_ПАРАМЕТРА>`,
+    /// списки задаются через запятую, составные параметры (например `rate_limits`) - в формате json.
+    /// Переменные с неизвестным именем пропускаются с предупреждением
+    pub fn apply_env<I: IntoIterator<Item = (String, String)>>(self, vars: I) -> Result<Self, Error>
+    {
+        let mut value = serde_json::to_value(&self)?;
+        let fields = value.as_object_mut().expect("configuration serialized as object");
+        let mut issues = Vec::new();
+        for (key, raw) in vars
+        {
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else { continue };
+            let name = name.to_lowercase();
+            //переменные с тем же префиксом могут принадлежать окружению, а не приложению
+            let Some(field) = fields.get_mut(&name) else
+            {
+                logger::warn!("Переменная окружения `{}` не соответствует ни одному параметру настроек и будет пропущена", key);
+                continue;
+            };
+            let parsed = match field
+            {
+                serde_json::Value::String(_) => Some(serde_json::Value::String(raw.clone())),
+                serde_json::Value::Bool(_) => raw.parse::<bool>().ok().map(serde_json::Value::Bool),
+                serde_json::Value::Number(_) => raw.parse::<u64>().ok().map(|n| serde_json::Value::Number(n.into())),
+                serde_json::Value::Array(_) => Some(serde_json::Value::Array(raw.split(',')
+                    .map(|v| v.trim())
+                    .filter(|v| !v.is_empty())
+                    .map(|v| serde_json::Value::String(v.to_owned()))
+                    .collect())),
+                serde_json::Value::Object(_) => serde_json::from_str::<serde_json::Value>(&raw).ok().filter(|v| v.is_object()),
+                _ => None
+            };
+            //значение проверяется отдельно, чтобы ошибка (например переполнение числа) относилась к своей переменной
+            let parsed = parsed.filter(|v|
+            {
+                let single = serde_json::Value::Object([(name.clone(), v.clone())].into_iter().collect());
+                serde_json::from_value::<Configuration>(single).is_ok()
+            });
+            match parsed
+            {
+                Some(v) => *field = v,
+                None => issues.push(ConfigurationIssue::new(&key, tr_args("config_invalid_value", &[&raw])))
+            }
+        }
+        if !issues.is_empty()
+        {
+            return Err(Error::ConfigurationError(issues));
+        }
+        serde_json::from_value(value).map_err(|e| Error::ConfigurationError(vec![ConfigurationIssue::new(ENV_PREFIX, e.to_string())]))
+    }
+    ///Проверка значений, при ошибках сервер не запускается
+    pub fn validate(&self) -> Result<(), Error>
+    {
+        let mut issues = Vec::new();
+        for (field, value) in [
+            ("session_life_time", self.session_life_time),
+            ("access_key_lifetime", self.access_key_lifetime),
+            ("max_sessions_count", self.max_sessions_count),
+            ("login_code_lifetime", self.login_code_lifetime),
+            ("login_code_max_requests", self.login_code_max_requests),
+            ("login_code_requests_window", self.login_code_requests_window),
+            ("login_code_max_attempts", self.login_code_max_attempts)
+        ]
+        {
+            if value == 0
+            {
+                issues.push(ConfigurationIssue::new(field, tr("config_must_be_positive")));
+            }
+        }
+        if self.server_port == 0
+        {
+            issues.push(ConfigurationIssue::new("server_port", tr("config_must_be_positive")));
+        }
+        if self.shutdown_timeout == 0
+        {
+            issues.push(ConfigurationIssue::new("shutdown_timeout", tr("config_must_be_positive")));
+        }
+        if self.avatar_max_size_kb == 0
+        {
+            issues.push(ConfigurationIssue::new("avatar_max_size_kb", tr("config_must_be_positive")));
+        }
+        if self.backup_retention == 0
+        {
+            issues.push(ConfigurationIssue::new("backup_retention", tr("config_must_be_positive")));
+        }
+        if self.sse_heartbeat_interval == 0
+        {
+            issues.push(ConfigurationIssue::new("sse_heartbeat_interval", tr("config_must_be_positive")));
+        }
+        if self.sse_replay_buffer == 0
+        {
+            issues.push(ConfigurationIssue::new("sse_replay_buffer", tr("config_must_be_positive")));
+        }
+        if self.database_max_connections == 0
+        {
+            issues.push(ConfigurationIssue::new("database_max_connections", tr("config_must_be_positive")));
+        }
+        if self.database_acquire_timeout == 0
+        {
+            issues.push(ConfigurationIssue::new("database_acquire_timeout", tr("config_must_be_positive")));
+        }
+        if sqlx::sqlite::SqliteSynchronous::from_str(&self.database_synchronous).is_err()
+        {
+            issues.push(ConfigurationIssue::new("database_synchronous", tr_args("config_invalid_synchronous", &[&self.database_synchronous])));
+        }
+        if self.origins.is_empty()
+        {
+            issues.push(ConfigurationIssue::new("origins", tr("config_no_origins")));
+        }
+        for origin in &self.origins
+        {
+            if !is_http_url(origin) || origin.ends_with('/') || HeaderValue::from_str(origin).is_err()
+            {
+                issues.push(ConfigurationIssue::new("origins", tr_args("config_invalid_origin", &[origin])));
+            }
+        }
+        if !is_http_url(&self.public_url)
+        {
+            issues.push(ConfigurationIssue::new("public_url", tr_args("config_invalid_url", &[&self.public_url])));
+        }
+        if self.fingerprint_header_name.parse::<HeaderName>().is_err()
+        {
+            issues.push(ConfigurationIssue::new("fingerprint_header_name", tr_args("config_invalid_header_name", &[&self.fingerprint_header_name])));
+        }
+        if self.session_cookie_name.is_empty() || !self.session_cookie_name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        {
+            issues.push(ConfigurationIssue::new("session_cookie_name", tr_args("config_invalid_cookie_name", &[&self.session_cookie_name])));
+        }
+        if self.bind_address.parse::<IpAddr>().is_err()
+        {
+            issues.push(ConfigurationIssue::new("bind_address", tr_args("config_invalid_ip", &[&self.bind_address])));
         }
-        else 
+        for (field, value) in [
+            ("database_path", &self.database_path),
+            ("sessions_database_path", &self.sessions_database_path),
+            ("key_path", &self.key_path),
+            ("avatars_directory", &self.avatars_directory),
+            ("backup_directory", &self.backup_directory)
+        ]
         {
-            cfg.unwrap()    
+            if value.trim().is_empty()
+            {
+                issues.push(ConfigurationIssue::new(field, tr("config_path_missing")));
+            }
+        }
+        if self.log_level.parse::<log::LevelFilter>().is_err()
+        {
+            issues.push(ConfigurationIssue::new("log_level", tr_args("config_invalid_log_level", &[&self.log_level])));
+        }
+        for (group, limit) in &self.rate_limits
+        {
+            let field = ["rate_limits.", group].concat();
+            if !RATE_LIMIT_GROUPS.contains(&group.as_str())
+            {
+                issues.push(ConfigurationIssue::new(&field, tr_args("config_unknown_rate_limit_group", &[&RATE_LIMIT_GROUPS.join(", ")])));
+            }
+            if limit.capacity == 0 || limit.refill_per_minute == 0
+            {
+                issues.push(ConfigurationIssue::new(&field, tr("config_rate_limit_positive")));
+            }
+        }
+        if self.tls_cert_path.trim().is_empty() != self.tls_key_path.trim().is_empty()
+        {
+            issues.push(ConfigurationIssue::new("tls_key_path", tr("config_tls_paths")));
+        }
+        if self.http_redirect_port != 0
+        {
+            if !self.tls_enabled()
+            {
+                issues.push(ConfigurationIssue::new("http_redirect_port", tr("config_redirect_without_tls")));
+            }
+            if self.http_redirect_port == self.server_port
+            {
+                issues.push(ConfigurationIssue::new("http_redirect_port", tr_args("config_must_differ", &["server_port"])));
+            }
+        }
+        //две базы в памяти независимы, одинаковый путь к файлу означает одну базу - для этого есть `unified_database`
+        if !self.unified_database && self.database_path != IN_MEMORY_DATABASE && self.database_path == self.sessions_database_path
+        {
+            issues.push(ConfigurationIssue::new("sessions_database_path", tr_args("config_must_differ", &["database_path"])));
+        }
+        if issues.is_empty()
+        {
+            Ok(())
+        }
+        else
+        {
+            Err(Error::ConfigurationError(issues))
         }
     }
     pub fn save(&self)
     {
         let _ = utilites::serialize(&self, FILENAME, false, utilites::Serializer::Toml);
     }
+    ///Имена параметров, значения которых отличаются от `other`
+    pub fn changed_fields(&self, other: &Configuration) -> Vec<String>
+    {
+        let current = serde_json::to_value(self).unwrap_or_default();
+        let other = serde_json::to_value(other).unwrap_or_default();
+        match (current.as_object(), other.as_object())
+        {
+            (Some(current), Some(other)) => current.iter()
+                .filter(|(k, v)| other.get(*k) != Some(*v))
+                .map(|(k, _)| k.clone())
+                .collect(),
+            _ => Vec::new()
+        }
+    }
+    ///Сервер принимает соединения по https, cookie выставляются с флагом `Secure`
+    pub fn tls_enabled(&self) -> bool
+    {
+        !self.tls_cert_path.trim().is_empty() && !self.tls_key_path.trim().is_empty()
+    }
+    pub fn apply_log_level(&self)
+    {
+        if let Ok(level) = self.log_level.parse::<log::LevelFilter>()
+        {
+            log::set_max_level(level);
+        }
+    }
+}
+
+type Overrides = dyn Fn(&mut Configuration) + Send + Sync;
+///Текущие настройки с возможностью перезагрузки без перезапуска сервера.
+/// Каждый запрос должен получать снимок через [`ConfigurationHandle::get`] один раз, чтобы видеть согласованные значения
+#[derive(Clone)]
+pub struct ConfigurationHandle
+{
+    current: Arc<ArcSwap<Configuration>>,
+    path: Arc<String>,
+    ///флаги командной строки, применяются заново при каждой перезагрузке
+    overrides: Arc<Overrides>
+}
+impl ConfigurationHandle
+{
+    pub fn new(cfg: Configuration) -> Self
+    {
+        Self::with_source(cfg, FILENAME, |_| {})
+    }
+    pub fn with_source<F: Fn(&mut Configuration) + Send + Sync + 'static>(cfg: Configuration, path: &str, overrides: F) -> Self
+    {
+        cfg.apply_log_level();
+        Self
+        {
+            current: Arc::new(ArcSwap::from_pointee(cfg)),
+            path: Arc::new(path.to_owned()),
+            overrides: Arc::new(overrides)
+        }
+    }
+    pub fn get(&self) -> Arc<Configuration>
+    {
+        self.current.load_full()
+    }
+    ///Перечитать настройки, возвращает имена измененных параметров.
+    /// Если изменились параметры требующие перезапуска, настройки не применяются
+    pub fn reload(&self) -> Result<Vec<String>, Error>
+    {
+        let mut cfg = Configuration::load_from(&self.path)?;
+        (self.overrides)(&mut cfg);
+        cfg.validate()?;
+        let current = self.get();
+        let changed = current.changed_fields(&cfg);
+        let restart: Vec<ConfigurationIssue> = changed.iter()
+            .filter(|f| RESTART_REQUIRED.contains(&f.as_str()))
+            .map(|f| ConfigurationIssue::new(f, tr("config_restart_required")))
+            .collect();
+        if !restart.is_empty()
+        {
+            return Err(Error::ConfigurationError(restart));
+        }
+        if !changed.is_empty()
+        {
+            cfg.apply_log_level();
+            self.current.store(Arc::new(cfg));
+            logger::info!("Настройки перезагружены, изменены: {}", changed.join(", "));
+        }
+        Ok(changed)
+    }
+    fn reload_logged(&self) -> bool
+    {
+        if let Err(e) = self.reload()
+        {
+            logger::error!("Ошибка перезагрузки настроек: {}", e.to_string());
+            false
+        }
+        else
+        {
+            true
+        }
+    }
+    ///Перезагрузка настроек при изменении файла и по сигналу SIGHUP, задача завершается при отмене `shutdown`
+    /// `on_reload` вызывается с результатом каждой перезагрузки
+    pub fn spawn_watcher<F: Fn(bool) + Send + 'static>(&self, shutdown: CancellationToken, on_reload: F)
+    {
+        let handle = self.clone();
+        tokio::spawn(async move
+        {
+            let modified = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+            let mut last_modified: Option<SystemTime> = modified(&handle.path);
+            let mut interval = tokio::time::interval(WATCH_INTERVAL);
+            #[cfg(unix)]
+            let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            {
+                Ok(s) => Some(s),
+                Err(e) =>
+                {
+                    logger::error!("Не удалось подписаться на SIGHUP: {}", e.to_string());
+                    None
+                }
+            };
+            loop
+            {
+                #[cfg(unix)]
+                let hangup_received = async
+                {
+                    match hangup.as_mut()
+                    {
+                        Some(s) => s.recv().await,
+                        None => std::future::pending().await
+                    }
+                };
+                #[cfg(not(unix))]
+                let hangup_received = std::future::pending::<Option<()>>();
+                tokio::select!
+                {
+                    _ = shutdown.cancelled() => break,
+                    _ = hangup_received =>
+                    {
+                        logger::info!("Получен SIGHUP, перезагрузка настроек");
+                        on_reload(handle.reload_logged());
+                    },
+                    _ = interval.tick() =>
+                    {
+                        let current = modified(&handle.path);
+                        if current != last_modified
+                        {
+                            last_modified = current;
+                            logger::info!("Файл настроек `{}` изменен, перезагрузка настроек", handle.path.as_str());
+                            on_reload(handle.reload_logged());
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn is_http_url(url: &str) -> bool
+{
+    let host = url.strip_prefix("http://").or_else(|| url.strip_prefix("https://"));
+    host.is_some_and(|h| !h.is_empty() && !h.contains(char::is_whitespace))
 }
 
 #[cfg(test)]
 mod tests
 {
+    use super::Configuration;
+    use crate::Error;
+
     #[test]
     fn save_cfg()
     {
         let cfg = super::Configuration::default();
         cfg.save();
     }
+    #[test]
+    fn test_env_override()
+    {
+        let vars = [
+            ("PLANNER_SERVER_PORT", "9000"),
+            ("PLANNER_ORIGINS", "http://localhost:9000, https://planner.example"),
+            ("PLANNER_PASSWORDLESS_LOGIN", "true"),
+            ("HOME", "/root")
+        ].map(|(k, v)| (k.to_owned(), v.to_owned()));
+        let cfg = Configuration::default().apply_env(vars).unwrap();
+        assert_eq!(cfg.server_port, 9000);
+        assert_eq!(cfg.origins, vec!["http://localhost:9000".to_owned(), "https://planner.example".to_owned()]);
+        assert!(cfg.passwordless_login);
+        let vars = [("PLANNER_RATE_LIMITS", r#"{"api": {"key": "token", "capacity": 5, "refill_per_minute": 1}}"#)].map(|(k, v)| (k.to_owned(), v.to_owned()));
+        let cfg = Configuration::default().apply_env(vars).unwrap();
+        assert_eq!(cfg.rate_limits.len(), 1);
+        assert_eq!(cfg.rate_limits["api"].key, super::RateLimitKey::Token);
+        //неизвестная переменная пропускается
+        let unknown = [("PLANNER_SERVER_PROT", "1")].map(|(k, v)| (k.to_owned(), v.to_owned()));
+        assert_eq!(Configuration::default().apply_env(unknown).unwrap().server_port, Configuration::default().server_port);
+        let wrong = [("PLANNER_SERVER_PORT", "port"), ("PLANNER_MAX_SESSIONS_COUNT", "1"), ("PLANNER_SERVER_PORT", "70000")].map(|(k, v)| (k.to_owned(), v.to_owned()));
+        let Err(Error::ConfigurationError(issues)) = Configuration::default().apply_env(wrong) else { panic!("expected configuration error") };
+        assert_eq!(issues.iter().map(|i| i.field.as_str()).collect::<Vec<_>>(), vec!["PLANNER_SERVER_PORT", "PLANNER_SERVER_PORT"]);
+    }
+    #[test]
+    fn test_unknown_file_field()
+    {
+        let path = std::env::temp_dir().join(["planner_unknown_", &uuid::Uuid::now_v7().to_string(), ".toml"].concat());
+        let path = path.to_str().unwrap().to_owned();
+        std::fs::write(&path, "server_port = 9000\norgins = [\"http://localhost:9000\"]\n").unwrap();
+        let result = Configuration::load_from(&path);
+        let _ = std::fs::remove_file(&path);
+        let Err(Error::ConfigurationError(issues)) = result else { panic!("expected configuration error") };
+        assert_eq!(issues[0].field, path);
+    }
+    #[test]
+    fn test_reload()
+    {
+        let path = std::env::temp_dir().join(["planner_reload_", &uuid::Uuid::now_v7().to_string(), ".toml"].concat());
+        let path = path.to_str().unwrap().to_owned();
+        let mut cfg = Configuration::default();
+        let _ = utilites::serialize(&cfg, &path, false, utilites::Serializer::Toml);
+        let handle = super::ConfigurationHandle::with_source(cfg.clone(), &path, |_| {});
+        cfg.access_key_lifetime = 10;
+        let _ = utilites::serialize(&cfg, &path, false, utilites::Serializer::Toml);
+        assert_eq!(handle.reload().unwrap(), vec!["access_key_lifetime".to_owned()]);
+        assert_eq!(handle.get().access_key_lifetime, 10);
+        cfg.server_port = 9999;
+        let _ = utilites::serialize(&cfg, &path, false, utilites::Serializer::Toml);
+        assert!(handle.reload().is_err());
+        assert_eq!(handle.get().server_port, 8888);
+        let _ = std::fs::remove_file(&path);
+    }
+    #[test]
+    fn test_validate()
+    {
+        assert!(Configuration::default().validate().is_ok());
+        let mut cfg = Configuration::default();
+        cfg.origins = vec!["localhost:8888".to_owned()];
+        cfg.access_key_lifetime = 0;
+        cfg.fingerprint_header_name = "x unique".to_owned();
+        let Err(Error::ConfigurationError(issues)) = cfg.validate() else { panic!("expected configuration error") };
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(fields, vec!["access_key_lifetime", "origins", "fingerprint_header_name"]);
+        let mut cfg = Configuration::default();
+        cfg.sessions_database_path = cfg.database_path.clone();
+        cfg.database_synchronous = "fast".to_owned();
+        let Err(Error::ConfigurationError(issues)) = cfg.validate() else { panic!("expected configuration error") };
+        let fields: Vec<&str> = issues.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(fields, vec!["database_synchronous", "sessions_database_path"]);
+        cfg.database_synchronous = "FULL".to_owned();
+        cfg.unified_database = true;
+        assert!(cfg.validate().is_ok());
+    }
 }
\ No newline at end of file
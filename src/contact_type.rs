@@ -0,0 +1,143 @@
+use std::{fmt::Display, str::FromStr};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::{i18n::tr_args, Error};
+
+///код страны по умолчанию для телефонов записанных без него
+const DEFAULT_PHONE_COUNTRY_CODE: &str = "7";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ContactType
+{
+    Email,
+    Phone,
+    Messenger
+}
+impl Display for ContactType
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            ContactType::Email => f.write_str("email"),
+            ContactType::Phone => f.write_str("phone"),
+            ContactType::Messenger => f.write_str("messenger")
+        }
+    }
+}
+///кроме основных названий понимает старые текстовые типы (`e-mail`, `мобильный телефон`)
+impl FromStr for ContactType
+{
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let lc = s.trim().to_lowercase();
+        match lc.as_str()
+        {
+            "email" | "e-mail" | "почта" | "электронная почта" => Ok(ContactType::Email),
+            "phone" | "телефон" | "мобильный телефон" => Ok(ContactType::Phone),
+            "messenger" | "мессенджер" | "telegram" => Ok(ContactType::Messenger),
+            _ => Err(Error::ValidationError(tr_args("unknown_contact_type", &[s])))
+        }
+    }
+}
+
+impl ContactType
+{
+    ///Проверка и приведение контакта к единому виду:
+    /// email - в нижнем регистре, телефон - в формате E.164 (`+79001112233`), мессенджер - `@имя` в нижнем регистре
+    pub fn normalize(&self, contact: &str) -> Result<String, Error>
+    {
+        let contact = contact.trim();
+        match self
+        {
+            ContactType::Email =>
+            {
+                let email = contact.to_lowercase();
+                let valid = email.split_once('@')
+                .map(|(name, domain)| !name.is_empty() && !domain.contains('@') && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.'))
+                .unwrap_or(false);
+                if valid && !email.contains(char::is_whitespace)
+                {
+                    Ok(email)
+                }
+                else
+                {
+                    Err(Error::ValidationError(tr_args("invalid_email", &[contact])))
+                }
+            },
+            ContactType::Phone =>
+            {
+                if !contact.chars().all(|c| c.is_ascii_digit() || " +-()".contains(c))
+                {
+                    return Err(Error::ValidationError(tr_args("invalid_phone", &[contact])));
+                }
+                let digits: String = contact.chars().filter(|c| c.is_ascii_digit()).collect();
+                let digits = if contact.starts_with('+')
+                {
+                    digits
+                }
+                //российский формат 8XXXXXXXXXX
+                else if digits.len() == 11 && digits.starts_with('8')
+                {
+                    [DEFAULT_PHONE_COUNTRY_CODE, &digits[1..]].concat()
+                }
+                else if digits.len() == 10
+                {
+                    [DEFAULT_PHONE_COUNTRY_CODE, &digits].concat()
+                }
+                else
+                {
+                    digits
+                };
+                if digits.len() < 8 || digits.len() > 15 || digits.starts_with('0')
+                {
+                    Err(Error::ValidationError(tr_args("invalid_phone", &[contact])))
+                }
+                else
+                {
+                    Ok(["+", &digits].concat())
+                }
+            },
+            ContactType::Messenger =>
+            {
+                let name = contact.trim_start_matches('@').to_lowercase();
+                if name.len() < 3 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+                {
+                    Err(Error::ValidationError(tr_args("invalid_messenger_name", &[contact])))
+                }
+                else
+                {
+                    Ok(["@", &name].concat())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::i18n::{with_locale, Locale};
+    use super::ContactType;
+
+    #[test]
+    fn test_normalize()
+    {
+        assert_eq!(ContactType::Email.normalize(" Test@Test.RU ").unwrap(), "test@test.ru");
+        assert!(ContactType::Email.normalize("test@test").is_err());
+        assert_eq!(ContactType::Phone.normalize("8 (900) 111-22-33").unwrap(), "+79001112233");
+        assert_eq!(ContactType::Phone.normalize("+44 20 7946 0958").unwrap(), "+442079460958");
+        assert!(ContactType::Phone.normalize("111-22").is_err());
+        assert_eq!(ContactType::Messenger.normalize("@Planner_Bot").unwrap(), "@planner_bot");
+        assert_eq!("мобильный телефон".parse::<ContactType>().unwrap(), ContactType::Phone);
+    }
+
+    #[tokio::test]
+    async fn test_error_locale()
+    {
+        let problem = with_locale(Locale::En, async { ContactType::Email.normalize("test@test").unwrap_err().to_problem() }).await;
+        assert_eq!(problem.detail, "Validation error: Invalid e-mail format `test@test`");
+    }
+}
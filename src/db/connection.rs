@@ -1,22 +1,70 @@
-use std::{path::Path, time::Duration};
-use sqlx::{sqlite::{SqliteConnectOptions, SqlitePoolOptions}, SqlitePool};
-use crate::error::Error;
+use std::{path::Path, str::FromStr, time::Duration};
+use sqlx::{sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous}, SqlitePool};
+use crate::{configuration::{Configuration, IN_MEMORY_DATABASE}, error::Error};
 
-pub async fn new_connection<P: AsRef<Path>>(db_name: P) -> Result<SqlitePool, Error>
+///`db_path` - путь к файлу базы данных, относительный путь считается от рабочей директории,
+/// `:memory:` - база данных в памяти, существует пока открыт пул.
+/// Размер пула, таймауты, режим синхронизации и проверка внешних ключей берутся из настроек
+pub async fn new_connection<P: AsRef<Path>>(db_path: P, cfg: &Configuration) -> Result<SqlitePool, Error>
 {
-    let local_path = Path::new(&std::env::current_dir().unwrap()).join([db_name.as_ref().to_str().unwrap(), ".", "sq3"].concat());
+    let synchronous = SqliteSynchronous::from_str(&cfg.database_synchronous)
+        .map_err(|e| Error::ValidationError(["database_synchronous: ", &e.to_string()].concat()))?;
+    let options = SqliteConnectOptions::new()
+    .busy_timeout(Duration::from_millis(cfg.database_busy_timeout as u64))
+    .synchronous(synchronous)
+    .foreign_keys(cfg.database_foreign_keys);
+    let pool_options = SqlitePoolOptions::new()
+    .acquire_timeout(Duration::from_secs(cfg.database_acquire_timeout as u64));
+    if db_path.as_ref() == Path::new(IN_MEMORY_DATABASE)
+    {
+        //у каждого соединения своя база в памяти, поэтому соединение одно и оно не закрывается пока жив пул
+        let options = options
+        .in_memory(true)
+        .journal_mode(SqliteJournalMode::Memory);
+        let pool = pool_options
+        .max_connections(1)
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(options)
+        .await?;
+        return Ok(pool);
+    }
+    let local_path = std::env::current_dir()?.join(db_path);
     if !local_path.exists()
     {
+        if let Some(parent) = local_path.parent()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
         std::fs::File::create(&local_path)?;
     }
-    let options = SqliteConnectOptions::new()
+    let options = options
     .filename(local_path)
-    .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+    .journal_mode(SqliteJournalMode::Wal);
 
-    let pool = SqlitePoolOptions::new()
-    .max_connections(5)
-    .acquire_timeout(Duration::from_secs(3))
+    let pool = pool_options
+    .max_connections(cfg.database_max_connections)
     .connect_with(options)
     .await?;
     Ok(pool)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::configuration::{Configuration, IN_MEMORY_DATABASE};
+
+    #[tokio::test]
+    async fn test_memory_connection()
+    {
+        let cfg = Configuration::default();
+        let pool = super::new_connection(IN_MEMORY_DATABASE, &cfg).await.unwrap();
+        let _ = sqlx::query("CREATE TABLE parent (id TEXT PRIMARY KEY)").execute(&pool).await.unwrap();
+        let _ = sqlx::query("CREATE TABLE child (parent_id TEXT NOT NULL REFERENCES parent (id))").execute(&pool).await.unwrap();
+        //таблица видна в следующем запросе - запросы идут в одну и ту же базу
+        let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys").fetch_one(&pool).await.unwrap();
+        assert!(foreign_keys);
+        assert!(sqlx::query("INSERT INTO child (parent_id) VALUES ('missing')").execute(&pool).await.is_err());
+    }
+}
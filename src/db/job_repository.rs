@@ -0,0 +1,377 @@
+use std::{pin::Pin, sync::Arc};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{sqlite::SqliteRow, FromRow, Row, SqlitePool};
+use crate::Error;
+
+///формат времени в таблице `jobs`: всегда UTC, строки сравниваются в том же порядке что и время
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+pub fn format_time(time: &DateTime<Utc>) -> String
+{
+    time.format(TIME_FORMAT).to_string()
+}
+pub fn parse_time(time: &str) -> Option<DateTime<Utc>>
+{
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok().map(|t| t.and_utc())
+}
+
+///Фоновая задача
+#[derive(Debug, Clone)]
+pub struct JobDbo
+{
+    ///имя периодической задачи или uuid отложенной
+    pub id: String,
+    ///тип задачи, по нему выбирается обработчик
+    pub kind: String,
+    ///расписание периодической задачи, `None` у отложенной задачи
+    pub schedule: Option<String>,
+    pub payload: serde_json::Value,
+    ///`None` - задача больше не запускается
+    pub next_run: Option<DateTime<Utc>>,
+    ///неудачные попытки подряд
+    pub attempts: u32,
+    ///задача выполняется до этого времени, блокировка продлевается пока задача работает
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_success: Option<bool>,
+    pub last_error: Option<String>,
+    ///длительность последнего запуска в миллисекундах
+    pub last_duration: Option<u64>,
+    pub created: DateTime<Utc>
+}
+impl JobDbo
+{
+    pub fn new(id: String, kind: &str, schedule: Option<String>, payload: serde_json::Value, next_run: Option<DateTime<Utc>>) -> Self
+    {
+        Self
+        {
+            id,
+            kind: kind.to_owned(),
+            schedule,
+            payload,
+            next_run,
+            attempts: 0,
+            locked_until: None,
+            last_run: None,
+            last_success: None,
+            last_error: None,
+            last_duration: None,
+            created: Utc::now()
+        }
+    }
+}
+
+impl FromRow<'_, SqliteRow> for JobDbo
+{
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self>
+    {
+        let time = |column: &str| -> sqlx::Result<Option<DateTime<Utc>>>
+        {
+            let value: Option<&str> = row.try_get(column)?;
+            Ok(value.and_then(parse_time))
+        };
+        let payload: &str = row.try_get("payload")?;
+        let last_duration: Option<i64> = row.try_get("last_duration")?;
+        let obj = JobDbo
+        {
+            id: row.try_get("id")?,
+            kind: row.try_get("kind")?,
+            schedule: row.try_get("schedule")?,
+            payload: serde_json::from_str(payload).unwrap_or_default(),
+            next_run: time("next_run")?,
+            attempts: row.try_get("attempts")?,
+            locked_until: time("locked_until")?,
+            last_run: time("last_run")?,
+            last_success: row.try_get("last_success")?,
+            last_error: row.try_get("last_error")?,
+            last_duration: last_duration.map(|d| d as u64),
+            created: time("created")?.unwrap_or_default()
+        };
+        Ok(obj)
+    }
+}
+
+///Результат запуска задачи
+#[derive(Debug, Clone)]
+pub struct JobRunDbo
+{
+    pub next_run: Option<DateTime<Utc>>,
+    pub attempts: u32,
+    pub started: DateTime<Utc>,
+    pub success: bool,
+    pub error: Option<String>,
+    pub duration: u64
+}
+
+const JOB_COLUMNS: &str = "id, kind, schedule, payload, next_run, attempts, locked_until, last_run, last_success, last_error, last_duration, created";
+
+pub trait IJobRepository
+{
+    ///Добавление периодической задачи или изменение ее расписания,
+    /// время следующего запуска сохраняется если расписание не изменилось
+    fn upsert_scheduled<'a>(&'a self, job: &'a JobDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    fn create<'a>(&'a self, job: &'a JobDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    fn get<'a>(&'a self, id: &'a str) -> Pin<Box<dyn Future<Output = Result<Option<JobDbo>, Error>> + Send + 'a>>;
+    fn list<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<Vec<JobDbo>, Error>> + Send + 'a>>;
+    fn delete<'a>(&'a self, id: &'a str) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>;
+    ///задачи, время запуска которых наступило и которые сейчас не выполняются
+    fn due<'a>(&'a self, now: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<Vec<JobDbo>, Error>> + Send + 'a>>;
+    ///блокировка задачи до `until`, `false` если задача уже выполняется
+    fn lock<'a>(&'a self, id: &'a str, now: &'a DateTime<Utc>, until: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>;
+    fn extend_lock<'a>(&'a self, id: &'a str, until: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///снятие блокировки без записи результата, задача запустится снова в то же время
+    fn release<'a>(&'a self, id: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///запись результата запуска и снятие блокировки
+    fn finish<'a>(&'a self, id: &'a str, run: &'a JobRunDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///`false` если задачи нет
+    fn set_next_run<'a>(&'a self, id: &'a str, next_run: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>;
+    ///удаление завершенных отложенных задач, последний запуск которых был раньше `before`
+    fn delete_finished<'a>(&'a self, before: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>;
+}
+
+pub struct JobRepository
+{
+    connection: Arc<SqlitePool>
+}
+impl JobRepository
+{
+    pub async fn new(pool: Arc<SqlitePool>) -> Result<Self, Error>
+    {
+        Ok(Self
+        {
+            connection: pool
+        })
+    }
+}
+
+impl IJobRepository for JobRepository
+{
+    fn upsert_scheduled<'a>(&'a self, job: &'a JobDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "INSERT INTO jobs (id, kind, schedule, payload, next_run, created) VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT(id) DO UPDATE SET
+            next_run = CASE WHEN jobs.schedule IS excluded.schedule AND jobs.next_run IS NOT NULL THEN jobs.next_run ELSE excluded.next_run END,
+            kind = excluded.kind,
+            schedule = excluded.schedule,
+            payload = excluded.payload";
+            let _ = sqlx::query(sql)
+            .bind(&job.id)
+            .bind(&job.kind)
+            .bind(&job.schedule)
+            .bind(job.payload.to_string())
+            .bind(job.next_run.as_ref().map(format_time))
+            .bind(format_time(&job.created))
+            .execute(&*connection).await?;
+            Ok(())
+        })
+    }
+    fn create<'a>(&'a self, job: &'a JobDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "INSERT INTO jobs (id, kind, schedule, payload, next_run, created) VALUES ($1, $2, $3, $4, $5, $6)";
+            let _ = sqlx::query(sql)
+            .bind(&job.id)
+            .bind(&job.kind)
+            .bind(&job.schedule)
+            .bind(job.payload.to_string())
+            .bind(job.next_run.as_ref().map(format_time))
+            .bind(format_time(&job.created))
+            .execute(&*connection).await?;
+            Ok(())
+        })
+    }
+    fn get<'a>(&'a self, id: &'a str) -> Pin<Box<dyn Future<Output = Result<Option<JobDbo>, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = ["SELECT ", JOB_COLUMNS, " FROM jobs WHERE id = $1"].concat();
+            let job = sqlx::query_as::<_, JobDbo>(&sql)
+            .bind(id)
+            .fetch_optional(&*connection).await?;
+            Ok(job)
+        })
+    }
+    fn list<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<Vec<JobDbo>, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = ["SELECT ", JOB_COLUMNS, " FROM jobs ORDER BY schedule IS NULL, kind, created"].concat();
+            let jobs = sqlx::query_as::<_, JobDbo>(&sql)
+            .fetch_all(&*connection).await?;
+            Ok(jobs)
+        })
+    }
+    fn delete<'a>(&'a self, id: &'a str) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let result = sqlx::query("DELETE FROM jobs WHERE id = $1")
+            .bind(id)
+            .execute(&*connection).await?;
+            Ok(result.rows_affected() == 1)
+        })
+    }
+    fn due<'a>(&'a self, now: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<Vec<JobDbo>, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = ["SELECT ", JOB_COLUMNS, " FROM jobs WHERE next_run <= $1 AND (locked_until IS NULL OR locked_until < $1) ORDER BY next_run"].concat();
+            let jobs = sqlx::query_as::<_, JobDbo>(&sql)
+            .bind(format_time(now))
+            .fetch_all(&*connection).await?;
+            Ok(jobs)
+        })
+    }
+    fn lock<'a>(&'a self, id: &'a str, now: &'a DateTime<Utc>, until: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            //условие по locked_until делает блокировку атомарной, в том числе между процессами с общей базой
+            let sql = "UPDATE jobs SET locked_until = $3 WHERE id = $1 AND (locked_until IS NULL OR locked_until < $2)";
+            let result = sqlx::query(sql)
+            .bind(id)
+            .bind(format_time(now))
+            .bind(format_time(until))
+            .execute(&*connection).await?;
+            Ok(result.rows_affected() == 1)
+        })
+    }
+    fn extend_lock<'a>(&'a self, id: &'a str, until: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let _ = sqlx::query("UPDATE jobs SET locked_until = $2 WHERE id = $1 AND locked_until IS NOT NULL")
+            .bind(id)
+            .bind(format_time(until))
+            .execute(&*connection).await?;
+            Ok(())
+        })
+    }
+    fn release<'a>(&'a self, id: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let _ = sqlx::query("UPDATE jobs SET locked_until = NULL WHERE id = $1")
+            .bind(id)
+            .execute(&*connection).await?;
+            Ok(())
+        })
+    }
+    fn finish<'a>(&'a self, id: &'a str, run: &'a JobRunDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "UPDATE jobs SET locked_until = NULL, next_run = $2, attempts = $3, last_run = $4, last_success = $5, last_error = $6, last_duration = $7 WHERE id = $1";
+            let _ = sqlx::query(sql)
+            .bind(id)
+            .bind(run.next_run.as_ref().map(format_time))
+            .bind(run.attempts)
+            .bind(format_time(&run.started))
+            .bind(run.success)
+            .bind(&run.error)
+            .bind(run.duration as i64)
+            .execute(&*connection).await?;
+            Ok(())
+        })
+    }
+    fn set_next_run<'a>(&'a self, id: &'a str, next_run: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let result = sqlx::query("UPDATE jobs SET next_run = $2 WHERE id = $1")
+            .bind(id)
+            .bind(format_time(next_run))
+            .execute(&*connection).await?;
+            Ok(result.rows_affected() == 1)
+        })
+    }
+    fn delete_finished<'a>(&'a self, before: &'a DateTime<Utc>) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "DELETE FROM jobs WHERE schedule IS NULL AND next_run IS NULL AND locked_until IS NULL AND last_run < $1";
+            let result = sqlx::query(sql)
+            .bind(format_time(before))
+            .execute(&*connection).await?;
+            Ok(result.rows_affected())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::Arc;
+    use chrono::{Duration, Utc};
+    use crate::{configuration::{Configuration, IN_MEMORY_DATABASE}, db::{connection::new_connection, migrations}};
+    use super::{format_time, parse_time, IJobRepository, JobDbo, JobRepository};
+
+    async fn test_repository() -> JobRepository
+    {
+        let pool = new_connection(IN_MEMORY_DATABASE, &Configuration::default()).await.unwrap();
+        migrations::migrate(&pool, migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE).await.unwrap();
+        JobRepository::new(Arc::new(pool)).await.unwrap()
+    }
+
+    #[test]
+    fn test_time_format()
+    {
+        let now = Utc::now();
+        let parsed = parse_time(&format_time(&now)).unwrap();
+        assert_eq!(parsed.timestamp(), now.timestamp());
+        assert!(format_time(&now) < format_time(&(now + Duration::seconds(1))));
+    }
+
+    #[tokio::test]
+    async fn test_lock()
+    {
+        let repository = test_repository().await;
+        let now = Utc::now();
+        let job = JobDbo::new("cleanup".to_owned(), "cleanup", Some("@hourly".to_owned()), serde_json::Value::Null, Some(now - Duration::seconds(1)));
+        repository.upsert_scheduled(&job).await.unwrap();
+        assert_eq!(repository.due(&now).await.unwrap().len(), 1);
+        let until = now + Duration::seconds(60);
+        assert!(repository.lock("cleanup", &now, &until).await.unwrap());
+        //заблокированная задача не запускается второй раз
+        assert!(!repository.lock("cleanup", &now, &until).await.unwrap());
+        assert!(repository.due(&now).await.unwrap().is_empty());
+        //блокировка процесса, который завершился не сняв ее, истекает
+        let later = until + Duration::seconds(1);
+        assert!(repository.lock("cleanup", &later, &(later + Duration::seconds(60))).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_upsert_keeps_next_run()
+    {
+        let repository = test_repository().await;
+        let next_run = Utc::now() + Duration::hours(1);
+        let job = JobDbo::new("backup".to_owned(), "backup", Some("@every 24h".to_owned()), serde_json::Value::Null, Some(next_run));
+        repository.upsert_scheduled(&job).await.unwrap();
+        let restarted = JobDbo::new("backup".to_owned(), "backup", Some("@every 24h".to_owned()), serde_json::Value::Null, Some(next_run + Duration::hours(5)));
+        repository.upsert_scheduled(&restarted).await.unwrap();
+        let stored = repository.get("backup").await.unwrap().unwrap();
+        assert_eq!(stored.next_run.unwrap().timestamp(), next_run.timestamp());
+        let changed = JobDbo::new("backup".to_owned(), "backup", Some("@every 12h".to_owned()), serde_json::Value::Null, Some(next_run + Duration::hours(5)));
+        repository.upsert_scheduled(&changed).await.unwrap();
+        let stored = repository.get("backup").await.unwrap().unwrap();
+        assert_eq!(stored.schedule.as_deref(), Some("@every 12h"));
+        assert_eq!(stored.next_run.unwrap().timestamp(), (next_run + Duration::hours(5)).timestamp());
+        assert!(repository.delete("backup").await.unwrap());
+        assert!(repository.list().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+use std::{pin::Pin, sync::Arc};
+use sqlx::{sqlite::SqliteRow, FromRow, Row, SqlitePool};
+use utilites::Date;
+use crate::Error;
+
+///Одноразовый код для входа без пароля
+#[derive(Debug, Clone)]
+pub struct LoginCodeDbo
+{
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub contact_id: uuid::Uuid,
+    ///хэш кода который пользователь вводит вручную
+    pub code_hash: String,
+    ///хэш токена из ссылки
+    pub token_hash: String,
+    ///отпечаток клиента который запросил код, войти можно только с него
+    pub fingerprint: String,
+    pub created: Date,
+    pub expiration_time: Date,
+    pub attempts: u32,
+    ///код одноразовый, после входа помечается использованным
+    pub used: bool
+}
+
+
+impl FromRow<'_, SqliteRow> for LoginCodeDbo
+{
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self>
+    {
+        let id: &str =  row.try_get("id")?;
+        let user_id: &str =  row.try_get("user_id")?;
+        let contact_id: &str =  row.try_get("contact_id")?;
+        let created: &str = row.try_get("created")?;
+        let expiration_time: &str = row.try_get("expiration_time")?;
+        let obj = LoginCodeDbo
+        {
+            id: id.parse().unwrap(),
+            user_id: user_id.parse().unwrap(),
+            contact_id: contact_id.parse().unwrap(),
+            code_hash: row.try_get("code_hash")?,
+            token_hash: row.try_get("token_hash")?,
+            fingerprint: row.try_get("fingerprint")?,
+            created: Date::parse(created).unwrap(),
+            expiration_time: Date::parse(expiration_time).unwrap(),
+            attempts: row.try_get("attempts")?,
+            used: row.try_get("used")?
+        };
+        Ok(obj)
+    }
+}
+
+pub trait ILoginCodeRepository
+{
+    fn create<'a>(&'a self, code: &'a LoginCodeDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    fn get<'a>(&'a self, id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<LoginCodeDbo, Error>> + Send + 'a>>;
+    ///number of codes requested by user since `since`
+    fn requests_count<'a>(&'a self, user_id: &'a uuid::Uuid, since: &'a Date) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + 'a>>;
+    fn increment_attempts<'a>(&'a self, id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///returns false if code was already used
+    fn mark_used<'a>(&'a self, id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>;
+    ///remove codes expired before `before`
+    fn delete_expired<'a>(&'a self, before: &'a Date) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>;
+}
+
+pub struct LoginCodeRepository
+{
+    connection: Arc<SqlitePool>
+}
+impl LoginCodeRepository
+{
+    pub async fn new(pool: Arc<SqlitePool>) -> Result<Self, Error>
+    {
+        Ok(Self
+        {
+            connection: pool
+        })
+    }
+}
+
+impl ILoginCodeRepository for LoginCodeRepository
+{
+    fn create<'a>(&'a self, code: &'a LoginCodeDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "INSERT INTO login_codes (id, user_id, contact_id, code_hash, token_hash, fingerprint, created, expiration_time, attempts) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)";
+            let _ = sqlx::query(&sql)
+            .bind(code.id.to_string())
+            .bind(code.user_id.to_string())
+            .bind(code.contact_id.to_string())
+            .bind(&code.code_hash)
+            .bind(&code.token_hash)
+            .bind(&code.fingerprint)
+            .bind(code.created.format(utilites::DateFormat::Serialize))
+            .bind(code.expiration_time.format(utilites::DateFormat::Serialize))
+            .bind(code.attempts)
+            .execute(&*connection).await?;
+            Ok(())
+        })
+    }
+    fn get<'a>(&'a self, id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<LoginCodeDbo, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "SELECT id, user_id, contact_id, code_hash, token_hash, fingerprint, created, expiration_time, attempts, used FROM login_codes WHERE id = $1";
+            let code = sqlx::query_as::<_, LoginCodeDbo>(&sql)
+            .bind(id.to_string())
+            .fetch_optional(&*connection).await?;
+            code.ok_or(Error::VerificationNotFound)
+        })
+    }
+    fn requests_count<'a>(&'a self, user_id: &'a uuid::Uuid, since: &'a Date) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "SELECT COUNT(*) FROM login_codes WHERE user_id = $1 AND created > $2";
+            let count: u32 = sqlx::query_scalar(&sql)
+            .bind(user_id.to_string())
+            .bind(since.format(utilites::DateFormat::Serialize))
+            .fetch_one(&*connection).await?;
+            Ok(count)
+        })
+    }
+    fn increment_attempts<'a>(&'a self, id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "UPDATE login_codes SET attempts = attempts + 1 WHERE id = $1";
+            let _ = sqlx::query(&sql)
+            .bind(id.to_string())
+            .execute(&*connection).await?;
+            Ok(())
+        })
+    }
+    fn mark_used<'a>(&'a self, id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            //условие по used защищает от одновременного использования одного кода
+            let sql = "UPDATE login_codes SET used = 1 WHERE id = $1 AND used = 0";
+            let result = sqlx::query(&sql)
+            .bind(id.to_string())
+            .execute(&*connection).await?;
+            Ok(result.rows_affected() == 1)
+        })
+    }
+    fn delete_expired<'a>(&'a self, before: &'a Date) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move
+        {
+            let sql = "DELETE FROM login_codes WHERE expiration_time < $1";
+            let result = sqlx::query(&sql)
+            .bind(before.format(utilites::DateFormat::Serialize))
+            .execute(&*connection).await?;
+            Ok(result.rows_affected())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::Arc;
+    use utilites::Date;
+    use crate::{configuration::{Configuration, IN_MEMORY_DATABASE}, db::{connection, migrations, IUserRepository, UserDbo, UserRepository}, Role};
+    use super::{ILoginCodeRepository, LoginCodeDbo, LoginCodeRepository};
+
+    ///репозиторий кодов и пользователь, которому они выдаются
+    async fn test_repository() -> (LoginCodeRepository, uuid::Uuid)
+    {
+        let pool = Arc::new(connection::new_connection(IN_MEMORY_DATABASE, &Configuration::default()).await.unwrap());
+        migrations::migrate(&pool, migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE).await.unwrap();
+        let user = UserDbo
+        {
+            id: uuid::Uuid::now_v7(),
+            username: "TestUser".to_owned(),
+            password: "test_password".to_owned(),
+            is_active: true,
+            role: Role::User,
+            audiences: Vec::new(),
+            avatar: None,
+            profile: None,
+            contacts: Vec::new()
+        };
+        let user_id = user.id;
+        UserRepository::new(Arc::clone(&pool)).await.unwrap().create(user).await.unwrap();
+        (LoginCodeRepository::new(pool).await.unwrap(), user_id)
+    }
+    fn test_code(user_id: uuid::Uuid, expiration_time: Date) -> LoginCodeDbo
+    {
+        LoginCodeDbo
+        {
+            id: uuid::Uuid::now_v7(),
+            user_id,
+            contact_id: uuid::Uuid::now_v7(),
+            code_hash: "code_hash".to_owned(),
+            token_hash: "token_hash".to_owned(),
+            fingerprint: "fingerprint".to_owned(),
+            created: Date::now(),
+            expiration_time,
+            attempts: 0,
+            used: false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_issue()
+    {
+        let (repository, user_id) = test_repository().await;
+        let code = test_code(user_id, Date::now().add_minutes(5));
+        repository.create(&code).await.unwrap();
+        let stored = repository.get(&code.id).await.unwrap();
+        assert_eq!((stored.user_id, stored.fingerprint.as_str(), stored.attempts, stored.used), (user_id, "fingerprint", 0, false));
+        assert_eq!(stored.code_hash, "code_hash");
+        repository.increment_attempts(&code.id).await.unwrap();
+        assert_eq!(repository.get(&code.id).await.unwrap().attempts, 1);
+        assert_eq!(repository.requests_count(&user_id, &Date::now().add_minutes(-15)).await.unwrap(), 1);
+        assert_eq!(repository.requests_count(&user_id, &Date::now().add_minutes(1)).await.unwrap(), 0);
+        assert!(repository.get(&uuid::Uuid::now_v7()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_single_use()
+    {
+        let (repository, user_id) = test_repository().await;
+        let code = test_code(user_id, Date::now().add_minutes(5));
+        repository.create(&code).await.unwrap();
+        assert!(repository.mark_used(&code.id).await.unwrap());
+        assert!(!repository.mark_used(&code.id).await.unwrap());
+        assert!(repository.get(&code.id).await.unwrap().used);
+    }
+
+    #[tokio::test]
+    async fn test_delete_expired()
+    {
+        let (repository, user_id) = test_repository().await;
+        let expired = test_code(user_id, Date::now().add_minutes(-1));
+        let active = test_code(user_id, Date::now().add_minutes(5));
+        repository.create(&expired).await.unwrap();
+        repository.create(&active).await.unwrap();
+        assert_eq!(repository.delete_expired(&Date::now()).await.unwrap(), 1);
+        assert!(repository.get(&expired.id).await.is_err());
+        assert!(repository.get(&active.id).await.is_ok());
+    }
+}
@@ -0,0 +1,558 @@
+use std::{collections::HashSet, pin::Pin};
+use sqlx::{SqliteConnection, SqlitePool};
+use utilites::Date;
+use crate::{ContactType, Error};
+
+///Преобразование данных, которое нельзя выразить на sql, выполняется в транзакции миграции
+pub type DataStep = for<'a> fn(&'a mut SqliteConnection) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+///Шаг миграции
+pub enum Step
+{
+    Sql(&'static str),
+    ///в sqlite нет `ADD COLUMN IF NOT EXISTS`, колонки могли быть добавлены в базы созданные до появления миграций
+    AddColumn
+    {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str
+    },
+    ///в контрольную сумму входит только имя, поэтому код шага после применения менять нельзя, как и sql
+    Data
+    {
+        name: &'static str,
+        apply: DataStep
+    },
+    ///шаг перенесен в более позднюю миграцию и не выполняется, исходный sql остается в контрольной сумме,
+    /// чтобы миграция не считалась измененной в базах, где она уже применена
+    Moved(&'static str)
+}
+impl Step
+{
+    fn checksum_source(&self) -> String
+    {
+        match self
+        {
+            Step::Sql(sql) | Step::Moved(sql) => sql.to_string(),
+            Step::AddColumn { table, column, definition } => ["ADD COLUMN ", *table, ".", *column, " ", *definition].concat(),
+            Step::Data { name, .. } => ["DATA ", *name].concat()
+        }
+    }
+}
+
+///Миграция применяется один раз, после применения ее нельзя изменять - изменения схемы оформляются новой миграцией
+pub struct Migration
+{
+    pub version: u32,
+    pub name: &'static str,
+    pub steps: &'static [Step]
+}
+impl Migration
+{
+    pub fn checksum(&self) -> String
+    {
+        let source: String = self.steps.iter().map(|s| s.checksum_source()).collect::<Vec<String>>().join("\n");
+        utilites::Hasher::hash_from_strings([source.as_str()])
+    }
+}
+
+///Миграции основной базы данных `planner`
+pub const PLANNER_MIGRATIONS: &[Migration] = &[
+    Migration
+    {
+        version: 1,
+        name: "users",
+        steps: &[
+            Step::Sql("CREATE TABLE IF NOT EXISTS users (
+            id TEXT NOT NULL,
+            username TEXT NOT NULL,
+            password TEXT NOT NULL,
+            is_active INTEGER NOT NULL DEFAULT 0,
+            role TEXT NOT NULL,
+            audiences BLOB,
+            PRIMARY KEY(id)
+            );"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS 'users_idx' ON users (id, username, is_active, role);"),
+            Step::Sql("CREATE TABLE IF NOT EXISTS contacts (
+            id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            contact_type TEXT NOT NULL,
+            verified INTEGER NOT NULL DEFAULT 0,
+            contact TEXT NOT NULL,
+            PRIMARY KEY(id),
+            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+            );"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS 'contacts_idx' ON contacts (id, user_id, contact, verified);"),
+            Step::Sql("CREATE TABLE IF NOT EXISTS contacts_verification (
+            contact_id TEXT NOT NULL,
+            code INTEGER NOT NULL,
+            expiration_time TEXT NOT NULL,
+            PRIMARY KEY(contact_id)
+            );"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS 'contacts_verification_idx' ON contacts_verification (contact_id, code, expiration_time);")
+        ]
+    },
+    Migration
+    {
+        version: 2,
+        name: "avatars",
+        steps: &[
+            Step::AddColumn { table: "users", column: "avatar", definition: "TEXT" }
+        ]
+    },
+    Migration
+    {
+        version: 3,
+        name: "profiles",
+        steps: &[
+            Step::Sql("CREATE TABLE IF NOT EXISTS profiles (
+            user_id TEXT NOT NULL,
+            last_name TEXT NOT NULL DEFAULT '',
+            first_name TEXT NOT NULL DEFAULT '',
+            middle_name TEXT NOT NULL DEFAULT '',
+            position TEXT NOT NULL DEFAULT '',
+            department TEXT NOT NULL DEFAULT '',
+            timezone TEXT NOT NULL DEFAULT 'UTC',
+            locale TEXT NOT NULL DEFAULT 'ru',
+            PRIMARY KEY(user_id),
+            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+            );")
+        ]
+    },
+    Migration
+    {
+        version: 4,
+        name: "primary and unique verified contacts",
+        steps: &[
+            Step::AddColumn { table: "contacts", column: "is_primary", definition: "INTEGER NOT NULL DEFAULT 0" },
+            //в старых базах одинаковые контакты могут быть подтверждены у нескольких пользователей,
+            // индекс создается миграцией 7 после нормализации и удаления повторов
+            Step::Moved("CREATE UNIQUE INDEX IF NOT EXISTS 'contacts_verified_idx' ON contacts (contact_type, contact) WHERE verified = 1;")
+        ]
+    },
+    Migration
+    {
+        version: 5,
+        name: "login codes",
+        steps: &[
+            Step::Sql("CREATE TABLE IF NOT EXISTS login_codes (
+            id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            contact_id TEXT NOT NULL,
+            code_hash TEXT NOT NULL,
+            token_hash TEXT NOT NULL,
+            fingerprint TEXT NOT NULL,
+            created TEXT NOT NULL,
+            expiration_time TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            used INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY(id),
+            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+            );"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS 'login_codes_idx' ON login_codes (user_id, created);")
+        ]
+    },
+    Migration
+    {
+        version: 6,
+        name: "jobs",
+        steps: &[
+            Step::Sql("CREATE TABLE IF NOT EXISTS jobs (
+            id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            schedule TEXT,
+            payload TEXT NOT NULL DEFAULT 'null',
+            next_run TEXT,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            locked_until TEXT,
+            last_run TEXT,
+            last_success INTEGER,
+            last_error TEXT,
+            last_duration INTEGER,
+            created TEXT NOT NULL,
+            PRIMARY KEY(id)
+            );"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS 'jobs_next_run_idx' ON jobs (next_run);")
+        ]
+    },
+    Migration
+    {
+        version: 7,
+        name: "normalize contacts",
+        steps: &[
+            //после приведения к единому виду подтвержденные контакты разных пользователей могут совпасть
+            Step::Sql("DROP INDEX IF EXISTS 'contacts_verified_idx';"),
+            Step::Data { name: "normalize_contacts", apply: normalize_contacts },
+            Step::Sql("CREATE UNIQUE INDEX IF NOT EXISTS 'contacts_verified_idx' ON contacts (contact_type, contact) WHERE verified = 1;")
+        ]
+    }
+];
+
+///Старые текстовые типы контактов (`мобильный телефон`, `e-mail`) заменяются на `phone`, `email`, `messenger`,
+/// значения контактов приводятся к единому виду. Тип, который не удалось распознать, определяется по значению контакта.
+/// Если одинаковый контакт подтвержден у нескольких пользователей, подтверждение остается только у первого
+fn normalize_contacts<'a>(connection: &'a mut SqliteConnection) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+{
+    Box::pin(async move
+    {
+        let rows: Vec<(String, String, String, bool)> = sqlx::query_as("SELECT id, contact_type, contact, verified FROM contacts ORDER BY verified DESC, id")
+        .fetch_all(&mut *connection).await?;
+        let mut verified_contacts: HashSet<(ContactType, String)> = HashSet::new();
+        let mut unverified = 0;
+        for (id, contact_type, contact, verified) in rows
+        {
+            let (contact_type, contact) = legacy_contact(&contact_type, &contact);
+            let still_verified = verified && verified_contacts.insert((contact_type, contact.clone()));
+            if verified && !still_verified
+            {
+                unverified += 1;
+            }
+            let _ = sqlx::query("UPDATE contacts SET contact_type = $2, contact = $3, verified = $4 WHERE id = $1")
+            .bind(&id)
+            .bind(contact_type.to_string())
+            .bind(&contact)
+            .bind(still_verified)
+            .execute(&mut *connection).await?;
+        }
+        if unverified > 0
+        {
+            logger::warn!("Подтверждение снято с {} контактов, совпадающих с контактами других пользователей", unverified);
+        }
+        Ok(())
+    })
+}
+fn legacy_contact(contact_type: &str, contact: &str) -> (ContactType, String)
+{
+    let contact_type = contact_type.parse::<ContactType>().ok()
+    .or_else(|| [ContactType::Email, ContactType::Phone].into_iter().find(|t| t.normalize(contact).is_ok()))
+    .unwrap_or(ContactType::Messenger);
+    let contact = contact_type.normalize(contact).unwrap_or_else(|_| contact.trim().to_owned());
+    (contact_type, contact)
+}
+
+///Миграции базы данных сессий `sessions`
+pub const SESSIONS_MIGRATIONS: &[Migration] = &[
+    Migration
+    {
+        version: 1,
+        name: "sessions",
+        steps: &[
+            Step::Sql("CREATE TABLE IF NOT EXISTS sessions (
+            session_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            logged_in TEXT NOT NULL,
+            key_expiration_time TEXT NOT NULL,
+            ip_addr TEXT NOT NULL,
+            fingerprint TEXT,
+            device TEXT NOT NULL DEFAULT 'unknown',
+            PRIMARY KEY(user_id, session_id)
+            );"),
+            Step::Sql("CREATE INDEX IF NOT EXISTS 'session_idx' ON sessions (user_id, session_id);")
+        ]
+    }
+];
+
+///Таблица версий схемы отдельной базы данных
+pub const VERSION_TABLE: &str = "schema_version";
+///Таблица версий схемы сессий, если сессии хранятся в основной базе (`unified_database`),
+/// у каждого набора миграций своя нумерация версий
+pub const UNIFIED_SESSIONS_VERSION_TABLE: &str = "sessions_schema_version";
+
+///Последняя версия схемы для набора миграций
+pub fn latest_version(migrations: &[Migration]) -> u32
+{
+    migrations.iter().map(|m| m.version).max().unwrap_or_default()
+}
+
+fn create_schema_version_table_sql(version_table: &str) -> String
+{
+    ["CREATE TABLE IF NOT EXISTS ", version_table, " (
+    version INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    applied TEXT NOT NULL,
+    PRIMARY KEY(version)
+    );"].concat()
+}
+
+///Текущая версия схемы базы данных, 0 если миграции не применялись
+pub async fn schema_version(pool: &SqlitePool, version_table: &str) -> Result<u32, Error>
+{
+    let _ = sqlx::query(&create_schema_version_table_sql(version_table)).execute(pool).await?;
+    let version: Option<u32> = sqlx::query_scalar(&["SELECT MAX(version) FROM ", version_table].concat())
+    .fetch_one(pool).await?;
+    Ok(version.unwrap_or_default())
+}
+
+///Проверка что примененные миграции совпадают с известными приложению, возвращает версию схемы
+pub async fn applied_version(pool: &SqlitePool, migrations: &[Migration], version_table: &str) -> Result<u32, Error>
+{
+    let _ = sqlx::query(&create_schema_version_table_sql(version_table)).execute(pool).await?;
+    let applied: Vec<(u32, String)> = sqlx::query_as(&["SELECT version, checksum FROM ", version_table, " ORDER BY version"].concat())
+    .fetch_all(pool).await?;
+    let mut current = 0;
+    for (version, checksum) in applied
+    {
+        let migration = migrations.iter().find(|m| m.version == version)
+        .ok_or_else(|| Error::MigrationError(["версия схемы ", &version.to_string(), " новее чем поддерживает приложение"].concat()))?;
+        if migration.checksum() != checksum
+        {
+            return Err(Error::MigrationError(["миграция ", &version.to_string(), " `", migration.name, "` была изменена после применения"].concat()));
+        }
+        current = version;
+    }
+    Ok(current)
+}
+
+async fn apply_step(connection: &mut SqliteConnection, step: &Step) -> Result<(), Error>
+{
+    match step
+    {
+        Step::Sql(sql) =>
+        {
+            let _ = sqlx::query(*sql).execute(&mut *connection).await?;
+        },
+        Step::AddColumn { table, column, definition } =>
+        {
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM pragma_table_info($1) WHERE name = $2)")
+            .bind(*table)
+            .bind(*column)
+            .fetch_one(&mut *connection).await?;
+            if !exists
+            {
+                let sql = ["ALTER TABLE ", *table, " ADD COLUMN ", *column, " ", *definition].concat();
+                let _ = sqlx::query(&sql).execute(&mut *connection).await?;
+            }
+        },
+        Step::Data { apply, .. } => apply(&mut *connection).await?,
+        Step::Moved(_) => ()
+    }
+    Ok(())
+}
+
+///Применение всех недостающих миграций, каждая миграция выполняется в отдельной транзакции.
+/// Возвращает версию схемы после применения, примененные миграции записываются в `version_table`
+pub async fn migrate(pool: &SqlitePool, migrations: &[Migration], version_table: &str) -> Result<u32, Error>
+{
+    let mut current = applied_version(pool, migrations, version_table).await?;
+    let mut pending: Vec<&Migration> = migrations.iter().filter(|m| m.version > current).collect();
+    pending.sort_by_key(|m| m.version);
+    for migration in pending
+    {
+        let mut tx = pool.begin().await?;
+        for step in migration.steps
+        {
+            apply_step(&mut tx, step).await?;
+        }
+        let sql = ["INSERT INTO ", version_table, " (version, name, checksum, applied) VALUES ($1, $2, $3, $4)"].concat();
+        let _ = sqlx::query(&sql)
+        .bind(migration.version)
+        .bind(migration.name)
+        .bind(migration.checksum())
+        .bind(Date::now().format(utilites::DateFormat::Serialize))
+        .execute(&mut *tx).await?;
+        tx.commit().await?;
+        logger::info!("Применена миграция {} `{}`", migration.version, migration.name);
+        current = migration.version;
+    }
+    Ok(current)
+}
+
+///Проверка что схема актуальна, используется если миграции при запуске отключены
+pub async fn ensure_latest(pool: &SqlitePool, migrations: &[Migration], version_table: &str) -> Result<u32, Error>
+{
+    let current = applied_version(pool, migrations, version_table).await?;
+    let latest = latest_version(migrations);
+    if current < latest
+    {
+        Err(Error::MigrationError(["версия схемы ", &current.to_string(), ", требуется ", &latest.to_string(), ", выполните команду `migrate`"].concat()))
+    }
+    else
+    {
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::Arc;
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+    use crate::{db::{IUserRepository, UserRepository}, ContactType};
+    use super::{latest_version, migrate, schema_version, Migration, Step, PLANNER_MIGRATIONS, SESSIONS_MIGRATIONS, UNIFIED_SESSIONS_VERSION_TABLE, VERSION_TABLE};
+
+    async fn memory_pool() -> SqlitePool
+    {
+        //у каждого соединения своя база в памяти, поэтому соединение одно
+        SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap()
+    }
+    async fn columns(pool: &SqlitePool, table: &str) -> Vec<String>
+    {
+        sqlx::query_scalar("SELECT name FROM pragma_table_info($1)")
+        .bind(table)
+        .fetch_all(pool).await.unwrap()
+    }
+    async fn exists(pool: &SqlitePool, kind: &str, name: &str) -> bool
+    {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = $1 AND name = $2)")
+        .bind(kind)
+        .bind(name)
+        .fetch_one(pool).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_migrate_empty_database()
+    {
+        let pool = memory_pool().await;
+        assert_eq!(schema_version(&pool, VERSION_TABLE).await.unwrap(), 0);
+        let version = migrate(&pool, PLANNER_MIGRATIONS, VERSION_TABLE).await.unwrap();
+        assert_eq!(version, latest_version(PLANNER_MIGRATIONS));
+        assert_eq!(schema_version(&pool, VERSION_TABLE).await.unwrap(), version);
+        for table in ["users", "contacts", "contacts_verification", "profiles", "login_codes"]
+        {
+            assert!(exists(&pool, "table", table).await, "table {} not created", table);
+        }
+        for index in ["users_idx", "contacts_idx", "contacts_verification_idx", "contacts_verified_idx", "login_codes_idx"]
+        {
+            assert!(exists(&pool, "index", index).await, "index {} not created", index);
+        }
+        assert!(columns(&pool, "users").await.contains(&"avatar".to_owned()));
+        assert!(columns(&pool, "contacts").await.contains(&"is_primary".to_owned()));
+        //повторный запуск ничего не меняет
+        assert_eq!(migrate(&pool, PLANNER_MIGRATIONS, VERSION_TABLE).await.unwrap(), version);
+    }
+    #[tokio::test]
+    async fn test_migrate_sessions()
+    {
+        let pool = memory_pool().await;
+        let version = migrate(&pool, SESSIONS_MIGRATIONS, VERSION_TABLE).await.unwrap();
+        assert_eq!(version, latest_version(SESSIONS_MIGRATIONS));
+        assert!(columns(&pool, "sessions").await.contains(&"device".to_owned()));
+    }
+    #[tokio::test]
+    async fn test_changed_migration_rejected()
+    {
+        const ORIGINAL: &[Migration] = &[Migration { version: 1, name: "test", steps: &[Step::Sql("CREATE TABLE t (id INTEGER);")] }];
+        const CHANGED: &[Migration] = &[Migration { version: 1, name: "test", steps: &[Step::Sql("CREATE TABLE t (id TEXT);")] }];
+        let pool = memory_pool().await;
+        migrate(&pool, ORIGINAL, VERSION_TABLE).await.unwrap();
+        assert!(migrate(&pool, CHANGED, VERSION_TABLE).await.is_err());
+        assert!(migrate(&pool, &[], VERSION_TABLE).await.is_err());
+    }
+    #[tokio::test]
+    async fn test_moved_step_keeps_checksum()
+    {
+        const ORIGINAL: &[Migration] = &[Migration { version: 1, name: "test", steps: &[Step::Sql("CREATE TABLE t (id INTEGER);")] }];
+        const MOVED: &[Migration] = &[Migration { version: 1, name: "test", steps: &[Step::Moved("CREATE TABLE t (id INTEGER);")] }];
+        assert_eq!(ORIGINAL[0].checksum(), MOVED[0].checksum());
+        let pool = memory_pool().await;
+        migrate(&pool, ORIGINAL, VERSION_TABLE).await.unwrap();
+        assert_eq!(migrate(&pool, MOVED, VERSION_TABLE).await.unwrap(), 1);
+        //перенесенный шаг не выполняется
+        let pool = memory_pool().await;
+        migrate(&pool, MOVED, VERSION_TABLE).await.unwrap();
+        assert!(!exists(&pool, "table", "t").await);
+    }
+    #[tokio::test]
+    async fn test_migrate_unified()
+    {
+        let pool = memory_pool().await;
+        let planner = migrate(&pool, PLANNER_MIGRATIONS, VERSION_TABLE).await.unwrap();
+        let sessions = migrate(&pool, SESSIONS_MIGRATIONS, UNIFIED_SESSIONS_VERSION_TABLE).await.unwrap();
+        assert_eq!(planner, latest_version(PLANNER_MIGRATIONS));
+        assert_eq!(sessions, latest_version(SESSIONS_MIGRATIONS));
+        assert!(exists(&pool, "table", "sessions").await);
+        assert!(exists(&pool, "table", "users").await);
+        assert_eq!(schema_version(&pool, VERSION_TABLE).await.unwrap(), planner);
+    }
+    #[tokio::test]
+    async fn test_normalize_legacy_contacts()
+    {
+        let pool = memory_pool().await;
+        //схема до нормализации контактов
+        migrate(&pool, &PLANNER_MIGRATIONS[..4], VERSION_TABLE).await.unwrap();
+        let users = [("0195ae79-6004-76b2-8dd4-8e94d6e5bddb", "Ivanov"), ("0195ae79-dcb1-7943-ba11-99dccc909833", "Petrov")];
+        for (id, username) in users
+        {
+            sqlx::query("INSERT INTO users (id, username, password, is_active, role) VALUES ($1, $2, '', 1, 'User')")
+            .bind(id)
+            .bind(username)
+            .execute(&pool).await.unwrap();
+        }
+        let contacts = [
+            ("0195ae7a-0000-7000-8000-000000000001", users[0].0, "мобильный телефон", "8 (900) 111-22-33", true),
+            ("0195ae7a-0000-7000-8000-000000000002", users[0].0, "e-mail", "Ivanov@Test.ru", true),
+            ("0195ae7a-0000-7000-8000-000000000003", users[0].0, "skype", "+7 900 444-55-66", false),
+            //тот же адрес в другом виде подтвержден у второго пользователя
+            ("0195ae7a-0000-7000-8000-000000000004", users[1].0, "почта", " IVANOV@test.ru ", true)
+        ];
+        for (id, user_id, contact_type, contact, verified) in contacts
+        {
+            sqlx::query("INSERT INTO contacts (id, user_id, contact_type, contact, verified) VALUES ($1, $2, $3, $4, $5)")
+            .bind(id)
+            .bind(user_id)
+            .bind(contact_type)
+            .bind(contact)
+            .bind(verified)
+            .execute(&pool).await.unwrap();
+        }
+        migrate(&pool, PLANNER_MIGRATIONS, VERSION_TABLE).await.unwrap();
+        let repository = UserRepository::new(Arc::new(pool)).await.unwrap();
+        let user = repository.get_user(&users[0].0.parse().unwrap()).await.unwrap();
+        let mut contacts: Vec<(ContactType, String, bool)> = user.contacts.into_iter().map(|c| (c.contact_type, c.contact, c.verified)).collect();
+        contacts.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(contacts, vec![
+            (ContactType::Phone, "+79001112233".to_owned(), true),
+            (ContactType::Phone, "+79004445566".to_owned(), false),
+            (ContactType::Email, "ivanov@test.ru".to_owned(), true)
+        ]);
+        let petrov = repository.get_user(&users[1].0.parse().unwrap()).await.unwrap();
+        assert!(!petrov.contacts[0].verified);
+        assert_eq!(repository.find_username_by_verified_contact(ContactType::Phone, "+79001112233").await.unwrap().as_deref(), Some("Ivanov"));
+        assert_eq!(repository.find_username_by_verified_contact(ContactType::Email, "ivanov@test.ru").await.unwrap().as_deref(), Some("Ivanov"));
+    }
+    #[tokio::test]
+    async fn test_migrate_duplicate_verified_contacts()
+    {
+        let pool = memory_pool().await;
+        //схема до появления уникального индекса подтвержденных контактов
+        migrate(&pool, &PLANNER_MIGRATIONS[..3], VERSION_TABLE).await.unwrap();
+        let users = [("0195ae79-6004-76b2-8dd4-8e94d6e5bddb", "Ivanov"), ("0195ae79-dcb1-7943-ba11-99dccc909833", "Petrov"), ("0195ae79-f1c2-7a10-9c3e-5b2f0d8e7a41", "Sidorov")];
+        for (id, username) in users
+        {
+            sqlx::query("INSERT INTO users (id, username, password, is_active, role) VALUES ($1, $2, '', 1, 'User')")
+            .bind(id)
+            .bind(username)
+            .execute(&pool).await.unwrap();
+        }
+        let contacts = [
+            //одинаковый адрес подтвержден у двух пользователей
+            ("0195ae7a-0000-7000-8000-000000000001", users[0].0, "e-mail", "ivanov@test.ru"),
+            ("0195ae7a-0000-7000-8000-000000000002", users[1].0, "e-mail", "ivanov@test.ru"),
+            //телефоны совпадают только после приведения к единому виду
+            ("0195ae7a-0000-7000-8000-000000000003", users[0].0, "мобильный телефон", "8 (900) 111-22-33"),
+            ("0195ae7a-0000-7000-8000-000000000004", users[2].0, "телефон", "+7 900 111 22 33")
+        ];
+        for (id, user_id, contact_type, contact) in contacts
+        {
+            sqlx::query("INSERT INTO contacts (id, user_id, contact_type, contact, verified) VALUES ($1, $2, $3, $4, 1)")
+            .bind(id)
+            .bind(user_id)
+            .bind(contact_type)
+            .bind(contact)
+            .execute(&pool).await.unwrap();
+        }
+        assert_eq!(migrate(&pool, PLANNER_MIGRATIONS, VERSION_TABLE).await.unwrap(), latest_version(PLANNER_MIGRATIONS));
+        assert!(exists(&pool, "index", "contacts_verified_idx").await);
+        let verified: Vec<String> = sqlx::query_scalar("SELECT id FROM contacts WHERE verified = 1 ORDER BY id")
+        .fetch_all(&pool).await.unwrap();
+        assert_eq!(verified, vec![contacts[0].0.to_owned(), contacts[2].0.to_owned()]);
+        let repository = UserRepository::new(Arc::new(pool)).await.unwrap();
+        assert_eq!(repository.find_username_by_verified_contact(ContactType::Email, "ivanov@test.ru").await.unwrap().as_deref(), Some("Ivanov"));
+        assert_eq!(repository.find_username_by_verified_contact(ContactType::Phone, "+79001112233").await.unwrap().as_deref(), Some("Ivanov"));
+    }
+}
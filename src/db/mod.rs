@@ -1,27 +1,298 @@
 mod user_repository;
 mod connection;
 mod session_repository;
-pub use session_repository::{Session, SessionRepository, ISessionRepository};
-use std::sync::Arc;
-pub use user_repository::{UserRepository, IUserRepository, UserDbo, ContactDbo, ContactVerificationDbo};
+mod login_code_repository;
+mod job_repository;
+pub mod migrations;
+pub use login_code_repository::{LoginCodeRepository, ILoginCodeRepository, LoginCodeDbo};
+pub use job_repository::{JobRepository, IJobRepository, JobDbo, JobRunDbo, format_time};
+pub use session_repository::{Session, SessionDbo, SessionRepository, ISessionRepository};
+use std::{fs::{File, TryLockError}, path::{Path, PathBuf}, sync::Arc};
+use sqlx::SqlitePool;
+pub use user_repository::{UserRepository, IUserRepository, UserDbo, ContactDbo, ContactVerificationDbo, ProfileDbo};
 
-use crate::Error;
+use crate::{configuration::{Configuration, IN_MEMORY_DATABASE}, Error};
+use migrations::Migration;
+
+///Имя основной базы данных
+pub const PLANNER_DATABASE: &str = "planner";
+///Имя базы данных сессий
+pub const SESSIONS_DATABASE: &str = "sessions";
 pub struct DatabaseService
 {
     pub user_repository: Box<dyn IUserRepository + Sync + Send>,
-    pub session_repository: SessionRepository
+    pub session_repository: Box<dyn ISessionRepository + Sync + Send>,
+    pub login_code_repository: Box<dyn ILoginCodeRepository + Sync + Send>,
+    pub job_repository: Box<dyn IJobRepository + Sync + Send>,
+    pool: Arc<SqlitePool>,
+    sessions_pool: Arc<SqlitePool>,
+    ///разделяемые блокировки файлов баз данных, пока они удерживаются базу нельзя восстановить из резервной копии
+    locks: Vec<File>
 }
 impl DatabaseService
 {
-    pub async fn new(max_sessions_count: u8) -> Result<Self, Error>
+    pub async fn new(cfg: &Configuration) -> Result<Self, Error>
+    {
+        let mut locks = vec![lock_database(&cfg.database_path, false)?];
+        let pool = Arc::new(connection::new_connection(&cfg.database_path, cfg).await?);
+        let sessions_pool = if cfg.unified_database
+        {
+            Arc::clone(&pool)
+        }
+        else
+        {
+            locks.push(lock_database(&cfg.sessions_database_path, false)?);
+            Arc::new(connection::new_connection(&cfg.sessions_database_path, cfg).await?)
+        };
+        let mut service = Self::with_pools(cfg, pool, sessions_pool).await?;
+        service.locks = locks.into_iter().flatten().collect();
+        Ok(service)
+    }
+    ///Основная база и сессии в одной базе данных в памяти, для тестов
+    pub async fn in_memory(cfg: &Configuration) -> Result<Self, Error>
     {
-        let pool = Arc::new(connection::new_connection("planner").await?);
+        let pool = Arc::new(connection::new_connection(IN_MEMORY_DATABASE, cfg).await?);
+        Self::with_pools(cfg, Arc::clone(&pool), pool).await
+    }
+    ///Сервис поверх открытых пулов, для общей базы в `pool` и `sessions_pool` передается один и тот же пул.
+    /// К пулам применяются миграции (или проверяется актуальность схемы, если `auto_migrate` отключен)
+    pub async fn with_pools(cfg: &Configuration, pool: Arc<SqlitePool>, sessions_pool: Arc<SqlitePool>) -> Result<Self, Error>
+    {
+        let sessions_version_table = sessions_version_table(Arc::ptr_eq(&pool, &sessions_pool));
+        //если миграции при запуске отключены только проверяется что схема актуальна
+        if cfg.auto_migrate
+        {
+            migrations::migrate(&pool, migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE).await?;
+            migrations::migrate(&sessions_pool, migrations::SESSIONS_MIGRATIONS, sessions_version_table).await?;
+        }
+        else
+        {
+            migrations::ensure_latest(&pool, migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE).await?;
+            migrations::ensure_latest(&sessions_pool, migrations::SESSIONS_MIGRATIONS, sessions_version_table).await?;
+        }
         let user_repository = UserRepository::new(pool.clone()).await?;
-        let session_repository = session_repository::SessionRepository::new(max_sessions_count).await?;
+        let login_code_repository = LoginCodeRepository::new(pool.clone()).await?;
+        let job_repository = JobRepository::new(pool.clone()).await?;
+        let session_repository = SessionRepository::new(sessions_pool.clone()).await?;
         Ok(Self
         {
             user_repository: Box::new(user_repository),
-            session_repository: session_repository
+            session_repository: Box::new(session_repository),
+            login_code_repository: Box::new(login_code_repository),
+            job_repository: Box::new(job_repository),
+            pool,
+            sessions_pool,
+            locks: Vec::new()
         })
     }
-}
\ No newline at end of file
+    ///Проверка доступности основной базы данных
+    pub async fn ping(&self) -> Result<(), Error>
+    {
+        let _ = sqlx::query("SELECT 1").execute(&*self.pool).await?;
+        Ok(())
+    }
+    ///Проверка доступности базы данных сессий
+    pub async fn ping_sessions(&self) -> Result<(), Error>
+    {
+        let _ = sqlx::query("SELECT 1").execute(&*self.sessions_pool).await?;
+        Ok(())
+    }
+    ///Сессии хранятся в основной базе данных
+    pub fn is_unified(&self) -> bool
+    {
+        Arc::ptr_eq(&self.pool, &self.sessions_pool)
+    }
+    ///Имена и пулы баз данных, общая база возвращается один раз
+    fn pools(&self) -> Vec<(&'static str, &SqlitePool)>
+    {
+        let mut pools = vec![(PLANNER_DATABASE, &*self.pool)];
+        if !self.is_unified()
+        {
+            pools.push((SESSIONS_DATABASE, &*self.sessions_pool));
+        }
+        pools
+    }
+    ///Имя базы данных, количество открытых и свободных соединений пула.
+    /// Для общей базы возвращается один пул
+    pub fn pool_stats(&self) -> Vec<(&'static str, u32, usize)>
+    {
+        self.pools().into_iter().map(|(name, pool)| (name, pool.size(), pool.num_idle())).collect()
+    }
+    ///Имена баз данных, для которых создаются резервные копии
+    pub fn databases(&self) -> Vec<&'static str>
+    {
+        self.pools().into_iter().map(|(name, _)| name).collect()
+    }
+    ///Согласованная копия базы данных `database` в файл `target` через `VACUUM INTO`,
+    /// выполняется без остановки сервера. Файл `target` не должен существовать
+    pub async fn vacuum_into(&self, database: &str, target: &Path) -> Result<(), Error>
+    {
+        let Some((_, pool)) = self.pools().into_iter().find(|(name, _)| *name == database) else
+        {
+            return Err(Error::ValidationError(["неизвестная база данных `", database, "`"].concat()));
+        };
+        let _ = sqlx::query("VACUUM INTO $1")
+        .bind(target.to_string_lossy().into_owned())
+        .execute(pool).await?;
+        Ok(())
+    }
+    ///Закрытие пулов, ожидает возврата всех соединений
+    pub async fn close(&self)
+    {
+        self.pool.close().await;
+        self.sessions_pool.close().await;
+    }
+}
+
+///Таблица версий схемы сессий, в общей базе у сессий своя таблица версий
+fn sessions_version_table(unified: bool) -> &'static str
+{
+    if unified
+    {
+        migrations::UNIFIED_SESSIONS_VERSION_TABLE
+    }
+    else
+    {
+        migrations::VERSION_TABLE
+    }
+}
+
+///Блокировка файла `<база>.lock`: разделяемая у открытых баз данных и исключительная на время восстановления.
+/// Блокировку снимает операционная система при закрытии файла, в том числе при аварийном завершении процесса.
+/// Для базы в памяти возвращается `None`
+fn lock_database(path: &str, exclusive: bool) -> Result<Option<File>, Error>
+{
+    if path == IN_MEMORY_DATABASE
+    {
+        return Ok(None);
+    }
+    let lock_path = PathBuf::from([path, ".lock"].concat());
+    let file = File::options().read(true).write(true).create(true).truncate(false).open(&lock_path)?;
+    let locked = if exclusive { file.try_lock() } else { file.try_lock_shared() };
+    match locked
+    {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) if exclusive => Err(Error::ValidationError(["база данных `", path, "` используется, остановите сервер перед восстановлением"].concat())),
+        Err(TryLockError::WouldBlock) => Err(Error::ValidationError(["база данных `", path, "` восстанавливается из резервной копии"].concat())),
+        Err(TryLockError::Error(e)) => Err(e.into())
+    }
+}
+
+///Применение миграций ко всем базам данных, возвращает путь к базе и версию схемы
+pub async fn migrate(cfg: &Configuration) -> Result<Vec<(String, u32)>, Error>
+{
+    let sessions_path = if cfg.unified_database { &cfg.database_path } else { &cfg.sessions_database_path };
+    let mut versions = Vec::new();
+    for (path, migrations, version_table) in [
+        (&cfg.database_path, migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE),
+        (sessions_path, migrations::SESSIONS_MIGRATIONS, sessions_version_table(cfg.unified_database))
+    ]
+    {
+        let pool = connection::new_connection(path, cfg).await?;
+        let version = migrations::migrate(&pool, migrations, version_table).await?;
+        pool.close().await;
+        versions.push((path.clone(), version));
+    }
+    Ok(versions)
+}
+
+///Путь к файлу базы данных `database` и наборы миграций, которые в ней хранятся
+fn database_schemas<'a>(cfg: &'a Configuration, database: &str) -> Result<(&'a str, Vec<(&'static [Migration], &'static str)>), Error>
+{
+    match database
+    {
+        PLANNER_DATABASE if cfg.unified_database => Ok((cfg.database_path.as_str(), vec![
+            (migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE),
+            (migrations::SESSIONS_MIGRATIONS, migrations::UNIFIED_SESSIONS_VERSION_TABLE)
+        ])),
+        PLANNER_DATABASE => Ok((cfg.database_path.as_str(), vec![(migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE)])),
+        SESSIONS_DATABASE if cfg.unified_database => Err(Error::ValidationError("сессии хранятся в основной базе данных, восстанавливается база `planner`".to_owned())),
+        SESSIONS_DATABASE => Ok((cfg.sessions_database_path.as_str(), vec![(migrations::SESSIONS_MIGRATIONS, migrations::VERSION_TABLE)])),
+        _ => Err(Error::ValidationError(["неизвестная база данных `", database, "`, допустимы: planner, sessions"].concat()))
+    }
+}
+
+///Восстановление базы данных `database` из резервной копии `backup`, сервер должен быть остановлен:
+/// пока база открыта другим процессом восстановление не выполняется.
+/// Копия проверяется до замены файлов: схема не должна быть новее чем поддерживает приложение,
+/// недостающие миграции будут применены при запуске. Текущий файл базы вместе с журналом сохраняется рядом с расширением `.before-restore`.
+/// Возвращает путь к восстановленной базе
+pub async fn restore(cfg: &Configuration, database: &str, backup: &Path) -> Result<String, Error>
+{
+    let (path, schemas) = database_schemas(cfg, database)?;
+    if path == IN_MEMORY_DATABASE
+    {
+        return Err(Error::ValidationError("база данных в памяти не может быть восстановлена".to_owned()));
+    }
+    if !backup.is_file()
+    {
+        return Err(Error::ValidationError(["файл `", &backup.to_string_lossy(), "` не найден"].concat()));
+    }
+    //блокировка удерживается до конца восстановления, сервер не сможет открыть базу во время замены файлов
+    let _lock = lock_database(path, true)?;
+    let target = std::env::current_dir()?.join(path);
+    let with_suffix = |suffix: &str| target.with_file_name([&target.file_name().unwrap_or_default().to_string_lossy(), suffix].concat());
+    //проверяется копия файла, чтобы не изменять саму резервную копию
+    let candidate = with_suffix(".restore");
+    std::fs::copy(backup, &candidate)?;
+    let checked = check_restore_candidate(cfg, &candidate, &schemas).await;
+    if let Err(e) = checked
+    {
+        let _ = std::fs::remove_file(&candidate);
+        return Err(e);
+    }
+    if target.exists()
+    {
+        //транзакции из журнала wal переносятся в файл базы, иначе сохраненная база будет неполной
+        let pool = connection::new_connection(&target, cfg).await?;
+        let checkpoint = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&pool).await;
+        pool.close().await;
+        checkpoint?;
+        std::fs::rename(&target, with_suffix(".before-restore"))?;
+    }
+    //если журнал остался, он сохраняется вместе с базой под именем, которое sqlite найдет для `.before-restore`
+    for suffix in ["-wal", "-shm"]
+    {
+        let file = with_suffix(suffix);
+        if file.exists()
+        {
+            std::fs::rename(file, with_suffix(&[".before-restore", suffix].concat()))?;
+        }
+    }
+    std::fs::rename(&candidate, &target)?;
+    Ok(target.to_string_lossy().into_owned())
+}
+
+async fn check_restore_candidate(cfg: &Configuration, candidate: &Path, schemas: &[(&'static [Migration], &'static str)]) -> Result<(), Error>
+{
+    let pool = connection::new_connection(candidate, cfg).await?;
+    let mut result = Ok(());
+    for &(set, version_table) in schemas
+    {
+        match migrations::applied_version(&pool, set, version_table).await
+        {
+            Ok(0) =>
+            {
+                result = Err(Error::MigrationError(["в резервной копии нет схемы `", version_table, "`"].concat()));
+                break;
+            },
+            Ok(version) if version > migrations::latest_version(set) =>
+            {
+                result = Err(Error::MigrationError(["версия схемы `", version_table, "` резервной копии ", &version.to_string(), " новее чем поддерживает приложение"].concat()));
+                break;
+            },
+            Ok(version) if version < migrations::latest_version(set) =>
+            {
+                logger::warn!("Версия схемы резервной копии {}, недостающие миграции будут применены при запуске", version);
+            },
+            Ok(_) => {},
+            Err(e) =>
+            {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    pool.close().await;
+    result
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{pin::Pin, sync::Arc};
 use sqlx::{query::Query, sqlite::{SqliteArguments, SqliteRow}, FromRow, Row, Sqlite, SqlitePool};
 use utilites::Date;
 use crate::error::Error;
@@ -6,55 +6,34 @@ use crate::error::Error;
 #[derive(Clone)]
 pub struct SessionRepository
 {
-    connection: Arc<SqlitePool>,
-    max_sessions_count: u8
+    connection: Arc<SqlitePool>
 }
 
 impl SessionRepository
 {
-    pub async fn new(max_sessions_count: u8) -> Result<Self, Error>
+    pub async fn new(pool: Arc<SqlitePool>) -> Result<Self, Error>
     {
-        let pool = Arc::new(super::connection::new_connection("sessions").await?);
-        let r1 = sqlx::query(create_table_sql()).execute(&*pool).await;
-        if r1.is_err()
-        {
-            logger::error!("{}", r1.as_ref().err().unwrap());
-            let _ = r1?;
-        };
         Ok(Self
         {
-            connection: pool,
-            max_sessions_count
+            connection: pool
         })
     }
 }
 pub trait ISessionRepository
 {
-    fn create_session(&self, user_id: &uuid::Uuid, refresh_key_lifetime_days: u8, ip_addr: &str, fingerprint: &str, device: &str) -> impl std::future::Future<Output = Result<Session, Error>> + Send;
-    fn get_session(&self, session_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<Session, Error>> + Send;
-    fn insert_or_replace_session(&self, session: &SessionDbo) -> impl std::future::Future<Output = Result<(), Error>> + Send;
-    fn sessions_count(&self, user_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<u32, Error>> + Send;
-    fn delete_all_sessions(&self, user_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<u64, Error>> + Send;
-    fn delete_session(&self, session_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<(), Error>> + Send;
-    fn update_session_key(&self, session_id: &uuid::Uuid, refresh_key_lifetime_days: u8) -> impl std::future::Future<Output = Result<(), Error>>;
+    fn create_session<'a>(&'a self, user_id: &'a uuid::Uuid, refresh_key_lifetime_days: u8, max_sessions_count: u8, ip_addr: &'a str, fingerprint: &'a str, device: &'a str) -> Pin<Box<dyn Future<Output = Result<Session, Error>> + Send + 'a>>;
+    fn get_session<'a>(&'a self, session_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<Session, Error>> + Send + 'a>>;
+    fn insert_or_replace_session<'a>(&'a self, session: &'a SessionDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    fn sessions_count<'a>(&'a self, user_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + 'a>>;
+    ///количество сессий с неистекшим ключом
+    fn active_sessions_count<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>;
+    fn delete_all_sessions<'a>(&'a self, user_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>;
+    fn delete_session<'a>(&'a self, session_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    fn update_session_key<'a>(&'a self, session_id: &'a uuid::Uuid, refresh_key_lifetime_days: u8) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///удаление сессий с истекшим ключом, возвращает количество удаленных
+    fn delete_expired<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>;
 }
 
-fn create_table_sql<'a>() -> &'a str
-{
-    "BEGIN;
-    CREATE TABLE IF NOT EXISTS sessions (
-    session_id TEXT NOT NULL,
-    user_id TEXT NOT NULL,
-    logged_in TEXT NOT NULL,
-    key_expiration_time TEXT NOT NULL,
-    ip_addr TEXT NOT NULL,
-    fingerprint TEXT,
-    device TEXT NOT NULL DEFAULT 'unknown',
-    PRIMARY KEY(user_id, session_id)
-    );
-    CREATE INDEX IF NOT EXISTS 'session_idx' ON sessions (user_id, session_id);
-    COMMIT;"
-}
 
 enum SessionTable
 {
@@ -210,7 +189,7 @@ impl FromRow<'_, SqliteRow> for SessionDbo
 
 impl ISessionRepository for SessionRepository
 {
-    fn create_session(&self, user_id: &uuid::Uuid, refresh_key_lifetime_days: u8, ip_addr: &str, fingerprint: &str, device: &str) -> impl std::future::Future<Output = Result<Session, Error>> + Send
+    fn create_session<'a>(&'a self, user_id: &'a uuid::Uuid, refresh_key_lifetime_days: u8, max_sessions_count: u8, ip_addr: &'a str, fingerprint: &'a str, device: &'a str) -> Pin<Box<dyn Future<Output = Result<Session, Error>> + Send + 'a>>
     {
         Box::pin(async move 
         {
@@ -227,7 +206,7 @@ impl ISessionRepository for SessionRepository
                 Ok(session.into())
             }
             //sessions count bigger than 3, replace older session with updated session
-            else if current_sessions.len() > self.max_sessions_count as usize
+            else if current_sessions.len() > max_sessions_count as usize
             {
                 let old_session = current_sessions.swap_remove(0);
                 //if fingerprint equalis
@@ -244,7 +223,7 @@ impl ISessionRepository for SessionRepository
                     self.delete_session(&old_session.session_id).await?;
                     let session = new_session(user_id, refresh_key_lifetime_days, ip_addr, fingerprint, device);
                     let _ = self.insert_or_replace_session(&session).await?;
-                    logger::warn!("Превышено максимальное количество одновременных сессий `{}` сессия `{}` заменена на {}", self.max_sessions_count, &old_session.session_id.to_string(), &session.session_id.to_string());
+                    logger::warn!("Превышено максимальное количество одновременных сессий `{}` сессия `{}` заменена на {}", max_sessions_count, &old_session.session_id.to_string(), &session.session_id.to_string());
                     Ok(session.into())
                 }
             }
@@ -270,7 +249,7 @@ impl ISessionRepository for SessionRepository
         })
     }
     //update current session lifetime
-    fn update_session_key(&self, session_id: &uuid::Uuid, refresh_key_lifetime_days: u8) -> impl std::future::Future<Output = Result<(), Error>>
+    fn update_session_key<'a>(&'a self, session_id: &'a uuid::Uuid, refresh_key_lifetime_days: u8) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
     {
         Box::pin(async move 
         {
@@ -287,7 +266,7 @@ impl ISessionRepository for SessionRepository
             }
         })
     }
-    fn get_session(&self, session_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<Session, Error>> + Send
+    fn get_session<'a>(&'a self, session_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<Session, Error>> + Send + 'a>>
     {
         Box::pin(async move 
         {
@@ -308,7 +287,7 @@ impl ISessionRepository for SessionRepository
         })
         
     }
-    fn insert_or_replace_session(&self, session: &SessionDbo) -> impl std::future::Future<Output = Result<(), Error>> + Send
+    fn insert_or_replace_session<'a>(&'a self, session: &'a SessionDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
     {
         Box::pin(async move 
         {
@@ -320,7 +299,7 @@ impl ISessionRepository for SessionRepository
         })
     }
 
-    fn sessions_count(&self, user_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<u32, Error>> + Send
+    fn sessions_count<'a>(&'a self, user_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + 'a>>
     {
         Box::pin(async move 
         {
@@ -332,7 +311,19 @@ impl ISessionRepository for SessionRepository
             Ok(count)
         })
     }
-    fn delete_all_sessions(&self, user_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<u64, Error>> + Send
+    fn active_sessions_count<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
+    {
+        Box::pin(async move 
+        {
+            let connection = Arc::clone(&self.connection);
+            let sql = ["SELECT COUNT(*) FROM sessions WHERE ", SessionTable::KeyExpirationTime.as_ref(), " > $1"].concat();
+            let count: i64 = sqlx::query_scalar(&sql)
+            .bind(Date::now().to_string())
+            .fetch_one(&*connection).await?;
+            Ok(count as u64)
+        })
+    }
+    fn delete_all_sessions<'a>(&'a self, user_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
     {
         Box::pin(async move 
         {
@@ -346,7 +337,19 @@ impl ISessionRepository for SessionRepository
             Ok(count)
         })
     }
-    fn delete_session(&self, session_id: &uuid::Uuid) -> impl std::future::Future<Output = Result<(), Error>> + Send
+    fn delete_expired<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send + 'a>>
+    {
+        Box::pin(async move 
+        {
+            let connection = Arc::clone(&self.connection);
+            let sql = ["DELETE FROM sessions WHERE ", SessionTable::KeyExpirationTime.as_ref(), " <= $1"].concat();
+            let count = sqlx::query(&sql)
+            .bind(Date::now().to_string())
+            .execute(&*connection).await?;
+            Ok(count.rows_affected())
+        })
+    }
+    fn delete_session<'a>(&'a self, session_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
     {
         Box::pin(async move 
         {
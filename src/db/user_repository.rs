@@ -2,7 +2,7 @@ use std::{pin::Pin, sync::Arc};
 use serde::{Deserialize, Serialize};
 use sqlx::{sqlite::SqliteRow, FromRow, Pool, Row, Sqlite, SqlitePool};
 use utilites::Date;
-use crate::{error, Error, Role};
+use crate::{error, i18n::tr_args, ContactType, Error, Role};
 
 pub struct UserRepository
 {
@@ -15,25 +15,59 @@ pub struct ContactDbo
 {
     pub id: uuid::Uuid,
     pub user_id: uuid::Uuid,
-    pub contact_type: String,
+    pub contact_type: ContactType,
     pub verified: bool,
+    ///основной контакт пользователя, на него отправляются уведомления
+    pub is_primary: bool,
     pub contact: String,
 }
+const CONTACT_FIELDS: &str = "id, user_id, contact_type, verified, is_primary, contact";
 
-fn create_contacts_table_sql<'a>() -> &'a str
+///Добавление или обновление контакта, контакт другого пользователя не изменяется,
+/// при изменении значения контакта сбрасывается его подтверждение
+fn upsert_contact_query<'a>(contact: &'a ContactDbo) -> sqlx::query::Query<'a, Sqlite, sqlx::sqlite::SqliteArguments<'a>>
 {
-    "BEGIN;
-    CREATE TABLE IF NOT EXISTS contacts (
-    id TEXT NOT NULL,
-    user_id TEXT NOT NULL,
-    contact_type TEXT NOT NULL,
-    verified INTEGER NOT NULL DEFAULT 0,
-    contact TEXT NOT NULL,
-    PRIMARY KEY(id),
-    FOREIGN KEY (user_id)  REFERENCES users (Id) ON DELETE CASCADE
-    );
-    CREATE INDEX IF NOT EXISTS 'contacts_idx' ON contacts (id, user_id, contact, verified);
-    COMMIT;"
+    let sql = "INSERT INTO contacts (id, user_id, contact_type, is_primary, contact) VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT(id) DO UPDATE SET 
+    contact_type = excluded.contact_type,
+    contact = excluded.contact,
+    is_primary = excluded.is_primary,
+    verified = CASE WHEN contacts.contact = excluded.contact AND contacts.contact_type = excluded.contact_type THEN contacts.verified ELSE 0 END
+    WHERE contacts.user_id = excluded.user_id";
+    sqlx::query(sql)
+    .bind(contact.id.to_string())
+    .bind(contact.user_id.to_string())
+    .bind(contact.contact_type.to_string())
+    .bind(contact.is_primary)
+    .bind(&contact.contact)
+}
+///Сохранение контактов пользователя, основным остается ровно один контакт:
+/// отмеченный в `contacts`, иначе текущий основной, а если его нет - самый ранний контакт пользователя
+async fn save_contacts(tx: &mut sqlx::SqliteConnection, user_id: &uuid::Uuid, contacts: &[ContactDbo]) -> Result<(), Error>
+{
+    let current: Option<String> = sqlx::query_scalar("SELECT id FROM contacts WHERE user_id = $1 AND is_primary = 1 ORDER BY id LIMIT 1")
+    .bind(user_id.to_string())
+    .fetch_optional(&mut *tx).await?;
+    for c in contacts
+    {
+        let _ = upsert_contact_query(c)
+        .execute(&mut *tx).await?;
+    }
+    let primary = match contacts.iter().find(|c| c.is_primary).map(|c| c.id.to_string()).or(current)
+    {
+        Some(id) => Some(id),
+        None => sqlx::query_scalar("SELECT id FROM contacts WHERE user_id = $1 ORDER BY id LIMIT 1")
+        .bind(user_id.to_string())
+        .fetch_optional(&mut *tx).await?
+    };
+    if let Some(primary) = primary
+    {
+        let _ = sqlx::query("UPDATE contacts SET is_primary = (id = $1) WHERE user_id = $2")
+        .bind(primary)
+        .bind(user_id.to_string())
+        .execute(&mut *tx).await?;
+    }
+    Ok(())
 }
 impl FromRow<'_, SqliteRow> for ContactDbo 
 {
@@ -41,8 +75,10 @@ impl FromRow<'_, SqliteRow> for ContactDbo
     {
         let id: &str =  row.try_get("id")?;
         let user_id: &str =  row.try_get("user_id")?;
-        let contact_type: String =  row.try_get("contact_type")?;
+        let contact_type: &str =  row.try_get("contact_type")?;
+        let contact_type: ContactType = contact_type.parse().map_err(|e: Error| sqlx::Error::Decode(e.into()))?;
         let verified: bool = row.try_get("verified")?;
+        let is_primary: bool = row.try_get("is_primary")?;
         let contact: String = row.try_get("contact")?;
         let obj = ContactDbo   
         {
@@ -50,6 +86,7 @@ impl FromRow<'_, SqliteRow> for ContactDbo
             user_id: user_id.parse().unwrap(),
             contact_type,
             verified,
+            is_primary,
             contact
         };
         Ok(obj)
@@ -63,18 +100,6 @@ pub struct ContactVerificationDbo
     code: u32,
     expiration_time: Date
 }
-fn create_verification_table_sql<'a>() -> &'a str
-{
-    "BEGIN;
-    CREATE TABLE IF NOT EXISTS contacts_verification (
-    contact_id TEXT NOT NULL,
-    code INTEGER NOT NULL,
-    expiration_time TEXT NOT NULL,
-    PRIMARY KEY(contact_id)
-    );
-    CREATE INDEX IF NOT EXISTS 'users_idx' ON users (contact_id, code, expiration_time);
-    COMMIT;"
-}
 impl FromRow<'_, SqliteRow> for ContactVerificationDbo 
 {
     fn from_row(row: &SqliteRow) -> sqlx::Result<Self> 
@@ -93,6 +118,81 @@ impl FromRow<'_, SqliteRow> for ContactVerificationDbo
     }
 }
 
+///персональные данные пользователя
+#[derive(Debug, Clone, Default)]
+pub struct ProfileDbo
+{
+    pub last_name: String,
+    pub first_name: String,
+    pub middle_name: String,
+    pub position: String,
+    pub department: String,
+    ///IANA timezone, например `Europe/Moscow`
+    pub timezone: String,
+    ///предпочитаемый язык, например `ru` или `en-US`
+    pub locale: String
+}
+impl FromRow<'_, SqliteRow> for ProfileDbo 
+{
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> 
+    {
+        let obj = ProfileDbo   
+        {
+            last_name: row.try_get("last_name")?,
+            first_name: row.try_get("first_name")?,
+            middle_name: row.try_get("middle_name")?,
+            position: row.try_get("position")?,
+            department: row.try_get("department")?,
+            timezone: row.try_get("timezone")?,
+            locale: row.try_get("locale")?
+        };
+        Ok(obj)
+    }
+}
+async fn get_profile(connection: &SqlitePool, user_id: &uuid::Uuid) -> Result<Option<ProfileDbo>, Error>
+{
+    let sql = "SELECT last_name, first_name, middle_name, position, department, timezone, locale FROM profiles WHERE user_id = $1";
+    let profile = sqlx::query_as::<_, ProfileDbo>(&sql)
+    .bind(user_id.to_string())
+    .fetch_optional(connection).await?;
+    Ok(profile)
+}
+async fn upsert_profile(tx: &mut sqlx::SqliteConnection, user_id: &uuid::Uuid, profile: &ProfileDbo) -> Result<(), Error>
+{
+    let sql = "INSERT OR REPLACE INTO profiles (user_id, last_name, first_name, middle_name, position, department, timezone, locale) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)";
+    let _ = sqlx::query(&sql)
+    .bind(user_id.to_string())
+    .bind(&profile.last_name)
+    .bind(&profile.first_name)
+    .bind(&profile.middle_name)
+    .bind(&profile.position)
+    .bind(&profile.department)
+    .bind(&profile.timezone)
+    .bind(&profile.locale)
+    .execute(tx).await?;
+    Ok(())
+}
+
+async fn insert_user(tx: &mut sqlx::SqliteConnection, user: &UserDbo) -> Result<(), Error>
+{
+    let pass_and_sailt = utilites::Hasher::hash_from_strings([&user.password, &user.id.to_string()]);
+    let sql = "INSERT INTO users (id, username, password, is_active, role, audiences) VALUES ($1, $2, $3, $4, $5, jsonb($6))";
+    let _ = sqlx::query(&sql)
+    .bind(user.id.to_string())
+    .bind(&user.username)
+    .bind(&pass_and_sailt)
+    .bind(user.is_active)
+    .bind(user.role.to_string())
+    .bind(serde_json::to_string(&user.audiences).unwrap())
+    .execute(&mut *tx).await?;
+    save_contacts(&mut *tx, &user.id, &user.contacts).await?;
+    if let Some(profile) = user.profile.as_ref()
+    {
+        upsert_profile(&mut *tx, &user.id, profile).await?;
+    }
+    Ok(())
+}
+
 ///юзеры
 #[derive(Debug, Clone)]
 pub struct UserDbo
@@ -103,39 +203,29 @@ pub struct UserDbo
     pub is_active: bool,
     pub role: Role,
     pub audiences: Vec<String>,
+    ///версия текущего аватара, `None` если аватар не загружен
+    pub avatar: Option<String>,
+    ///при обновлении `None` означает что профиль не меняется
+    pub profile: Option<ProfileDbo>,
     pub contacts: Vec<ContactDbo>
 }
 impl UserDbo
 {
-    pub fn add_contact(mut self, contact_type: &str, contact: &str) -> Self
+    pub fn add_contact(mut self, contact_type: ContactType, contact: &str) -> Self
     {
         let contact = ContactDbo
         {
             id: uuid::Uuid::now_v7(),
             user_id: self.id.clone(),
-            contact_type: contact_type.to_owned(),
+            contact_type,
             verified: false,
+            is_primary: self.contacts.is_empty(),
             contact: contact.to_owned()
         };
         self.contacts.push(contact);
         self
     }
 }
-fn create_users_table_sql<'a>() -> &'a str
-{
-    "BEGIN;
-    CREATE TABLE IF NOT EXISTS users (
-    id TEXT NOT NULL,
-    username TEXT NOT NULL,
-    password TEXT NOT NULL,
-    is_active INTEGER NOT NULL DEFAULT 0,
-    role TEXT NOT NULL,
-    audiences BLOB,
-    PRIMARY KEY(id)
-    );
-    CREATE INDEX IF NOT EXISTS 'users_idx' ON users (id, username, is_active, role);
-    COMMIT;"
-}
 
 impl FromRow<'_, SqliteRow> for UserDbo 
 {
@@ -148,6 +238,7 @@ impl FromRow<'_, SqliteRow> for UserDbo
         let role: &str = row.try_get("role")?;
         let audiences: &str = row.try_get("audiences")?;
         let audiences: Vec<String> = serde_json::from_str(&audiences).unwrap();
+        let avatar: Option<String> = row.try_get("avatar")?;
         let obj = UserDbo   
         {
             id: id.parse().unwrap(),
@@ -156,6 +247,8 @@ impl FromRow<'_, SqliteRow> for UserDbo
             is_active,
             role: role.parse().unwrap(),
             audiences,
+            avatar,
+            profile: None,
             contacts: Vec::new()
         };
         Ok(obj)
@@ -168,11 +261,29 @@ pub trait IUserRepository
     ///self user info update
     fn update_info<'a>(&'a self, user: UserDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
     fn update_password<'a>(&'a self, user_id: &'a uuid::Uuid, old_password: &'a str, new_password: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///set new password without old password check (admin reset)
+    fn set_password<'a>(&'a self, user_id: &'a uuid::Uuid, new_password: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
     ///update user info by admin privilegy
     fn update<'a>(&'a self, user: UserDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
     fn create<'a>(&'a self, user: UserDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///create users in one transaction, on error no user is created
+    fn create_many<'a>(&'a self, users: &'a [UserDbo]) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
     fn username_is_busy<'a>(&'a self, username: &'a str) -> Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>;
     fn get_user<'a>(&'a self, user_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<UserDbo, Error>> + Send + 'a>>;
+    fn get_user_by_username<'a>(&'a self, username: &'a str) -> Pin<Box<dyn Future<Output = Result<UserDbo, Error>> + Send + 'a>>;
+    ///all users with contacts, used for export
+    fn get_users<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<Vec<UserDbo>, Error>> + Send + 'a>>;
+    ///profile locale, `None` if user has no profile
+    fn get_locale<'a>(&'a self, user_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<Option<String>, Error>> + Send + 'a>>;
+    ///set or clear (`None`) current avatar version
+    fn update_avatar<'a>(&'a self, user_id: &'a uuid::Uuid, avatar: Option<&'a str>) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    fn get_contact<'a>(&'a self, contact_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<ContactDbo, Error>> + Send + 'a>>;
+    ///username of user owning this verified contact
+    fn find_username_by_verified_contact<'a>(&'a self, contact_type: ContactType, contact: &'a str) -> Pin<Box<dyn Future<Output = Result<Option<String>, Error>> + Send + 'a>>;
+    fn add_contact<'a>(&'a self, contact: ContactDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    fn delete_contact<'a>(&'a self, user_id: &'a uuid::Uuid, contact_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+    ///mark contact as primary, other user contacts lose primary flag
+    fn set_primary_contact<'a>(&'a self, user_id: &'a uuid::Uuid, contact_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
     fn contact_verification_request<'a>(&'a self, contact_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + 'a>>;
     fn contact_verification_accept<'a>(&'a self, contact_id: &'a uuid::Uuid, code: u32) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
 }
@@ -184,7 +295,7 @@ impl IUserRepository for UserRepository
         let connection = Arc::clone(&self.connection);
         Box::pin(async move 
         {
-            let sql = "SELECT id, username, password, is_active, role, json(audiences) as audiences FROM users WHERE username = $1";
+            let sql = "SELECT id, username, password, is_active, role, json(audiences) as audiences, avatar FROM users WHERE username = $1";
             let user = sqlx::query_as::<_, UserDbo>(&sql)
             .bind(username)
             .fetch_one(&*connection).await;
@@ -193,26 +304,30 @@ impl IUserRepository for UserRepository
                 let pass_and_sailt = utilites::Hasher::hash_from_strings([password, &user.id.to_string()]);
                 if &pass_and_sailt == &user.password
                 {
-                    let sql = "SELECT id, user_id, contact_type, verified, contact FROM contacts WHERE user_id = $1";
+                    let sql = ["SELECT ", CONTACT_FIELDS, " FROM contacts WHERE user_id = $1"].concat();
                     let contacts = sqlx::query_as::<_, ContactDbo>(&sql)
                     .bind(user.id.to_string())
                     .fetch_all(&*connection).await?;
+                    let profile = get_profile(&connection, &user.id).await?;
                     let user = UserDbo 
                     {  
                         contacts,
+                        profile,
                         ..user
                     };
                     Ok(user)
                 }
                 else
                 {
-                    Err(error::Error::AuthError(["Ошибка пароля для `", username, "`"].concat()))
+                    logger::warn!("Неверный пароль для `{}`", username);
+                    Err(Error::AuthError("invalid_credentials".to_owned()))
                 }
             }
             else 
             {
-                logger::error!("{}", user.err().unwrap());
-                Err(error::Error::AuthError(["Пользователь `", username, "` не зарегистрирован"].concat()))
+                //ответ не отличается от неверного пароля, чтобы по нему нельзя было подобрать имена пользователей
+                logger::warn!("Вход незарегистрированного пользователя `{}`: {}", username, user.err().unwrap());
+                Err(Error::AuthError("invalid_credentials".to_owned()))
             }
         })
     }
@@ -239,11 +354,32 @@ impl IUserRepository for UserRepository
             }
             else
             {
-                Err(error::Error::AuthError("Неверный старый пароль, попробуйте еще раз".to_owned()))
+                Err(Error::AuthError("wrong_old_password".to_owned()))
+            }
+        })
+    }
+    fn set_password<'a>(&'a self, user_id: &'a uuid::Uuid, new_password: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let new_pass_and_sailt = utilites::Hasher::hash_from_strings([new_password, &user_id.to_string()]);
+            let sql = "UPDATE users SET password = $1 WHERE id = $2";
+            let result = sqlx::query(&sql)
+            .bind(new_pass_and_sailt)
+            .bind(user_id.to_string())
+            .execute(&*connection).await?;
+            if result.rows_affected() == 0
+            {
+                Err(Error::UserNotFound)
+            }
+            else 
+            {
+                Ok(())
             }
         })
     }
-    ///partialy user itself update (only contacts)
+    ///partialy user itself update (contacts and profile)
     fn update_info<'a>(&'a self, user: UserDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
     {
         let connection = Arc::clone(&self.connection);
@@ -255,23 +391,18 @@ impl IUserRepository for UserRepository
             .fetch_one(&*connection).await?;
             if exists
             {
-                let sql = "INSERT OR REPLACE INTO contacts (id, user_id, contact_type, contact) VALUES ($1, $2, $3, $4)";
                 let mut tx = connection.begin().await?;
-                for c in user.contacts
+                save_contacts(&mut *tx, &user.id, &user.contacts).await?;
+                if let Some(profile) = user.profile.as_ref()
                 {
-                    let _ = sqlx::query(&sql)
-                    .bind(c.id.to_string())
-                    .bind(c.user_id.to_string())
-                    .bind(&c.contact_type)
-                    .bind(&c.contact)
-                    .execute(&mut *tx).await?;
+                    upsert_profile(&mut *tx, &user.id, profile).await?;
                 }
-                tx.commit().await;
+                tx.commit().await?;
                 Ok(())
             }
             else 
             {
-                Err(error::Error::AuthError(["Ошибка обновления данных для  ", &user.username].concat()))
+                Err(Error::UserNotFound)
             }
         })
     }
@@ -281,66 +412,53 @@ impl IUserRepository for UserRepository
         let connection = Arc::clone(&self.connection);
         Box::pin(async move 
         {
-            let sql = "SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)";
-            let exists: bool = sqlx::query_scalar(&sql)
+            //роль, активность, контакты и профиль меняются вместе: при ошибке не остается частично обновленного пользователя
+            let mut tx = connection.begin().await?;
+            let sql = "UPDATE users SET is_active = $2, role = $3, audiences = jsonb($4) WHERE id = $1";
+            let result = sqlx::query(&sql)
             .bind(user.id.to_string())
-            .fetch_one(&*connection).await?;
-            if exists
+            .bind(user.is_active)
+            .bind(user.role.to_string())
+            .bind(serde_json::to_string(&user.audiences).unwrap())
+            .execute(&mut *tx).await?;
+            if result.rows_affected() > 0
             {
-                let sql = "UPDATE users SET is_active = $2, role = $3, audiences = jsonb($4) WHERE id = $1";
-                let _ = sqlx::query(&sql)
-                .bind(user.id.to_string())
-                .bind(user.is_active)
-                .bind(user.role.to_string())
-                .bind(serde_json::to_string(&user.audiences).unwrap())
-                .execute(&*connection).await?;
-                let sql = "INSERT OR REPLACE INTO contacts (id, user_id, contact_type, contact) VALUES ($1, $2, $3, $4)";
-                let mut tx = connection.begin().await?;
-                for c in user.contacts
+                save_contacts(&mut *tx, &user.id, &user.contacts).await?;
+                if let Some(profile) = user.profile.as_ref()
                 {
-                    let _ = sqlx::query(&sql)
-                    .bind(c.id.to_string())
-                    .bind(c.user_id.to_string())
-                    .bind(&c.contact_type)
-                    .bind(&c.contact)
-                    .execute(&mut *tx).await?;
+                    upsert_profile(&mut *tx, &user.id, profile).await?;
                 }
-                tx.commit().await;
+                tx.commit().await?;
                 Ok(())
             }
             else 
             {
-                Err(error::Error::AuthError(["Ошибка обновления данных для  ", &user.username].concat()))
+                Err(Error::UserNotFound)
             }
         })
     }
-    //is_active set by default - 0;
     fn create<'a>(&'a self, user: UserDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
     {
         let connection = Arc::clone(&self.connection);
         Box::pin(async move 
         {
-            let pass_and_sailt = utilites::Hasher::hash_from_strings([&user.password, &user.id.to_string()]);
-            let sql = "INSERT INTO users (id, username, password, role, audiences) VALUES ($1, $2, $3, $4, jsonb($5))";
-            let _ = sqlx::query(&sql)
-            .bind(user.id.to_string())
-            .bind(&user.username)
-            .bind(&pass_and_sailt)
-            .bind(user.role.to_string())
-            .bind(serde_json::to_string(&user.audiences).unwrap())
-            .execute(&*connection).await?;
-            let sql = "INSERT INTO contacts (id, user_id, contact_type, contact) VALUES ($1, $2, $3, $4)";
             let mut tx = connection.begin().await?;
-            for c in user.contacts
+            insert_user(&mut *tx, &user).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+    fn create_many<'a>(&'a self, users: &'a [UserDbo]) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let mut tx = connection.begin().await?;
+            for user in users
             {
-                let _ = sqlx::query(&sql)
-                .bind(c.id.to_string())
-                .bind(c.user_id.to_string())
-                .bind(&c.contact_type)
-                .bind(&c.contact)
-                .execute(&mut *tx).await?;
+                insert_user(&mut *tx, user).await?;
             }
-            tx.commit().await;
+            tx.commit().await?;
             Ok(())
         })
     }
@@ -362,27 +480,203 @@ impl IUserRepository for UserRepository
         let connection = Arc::clone(&self.connection);
         Box::pin(async move 
         {
-            let sql = "SELECT id, username, password, is_active, role, json(audiences) as audiences FROM users WHERE id = $1";
+            let sql = "SELECT id, username, password, is_active, role, json(audiences) as audiences, avatar FROM users WHERE id = $1";
             let user = sqlx::query_as::<_, UserDbo>(&sql)
             .bind(user_id.to_string())
-            .fetch_one(&*connection).await;
-            if let Ok(user) = user
+            .fetch_optional(&*connection).await?;
+            if let Some(user) = user
             {
-                let sql = "SELECT id, user_id, contact_type, verified, contact FROM contacts WHERE user_id = $1";
+                let sql = ["SELECT ", CONTACT_FIELDS, " FROM contacts WHERE user_id = $1"].concat();
                 let contacts = sqlx::query_as::<_, ContactDbo>(&sql)
                 .bind(user.id.to_string())
                 .fetch_all(&*connection).await?;
+                let profile = get_profile(&connection, &user.id).await?;
                 let user = UserDbo 
                 {  
                     contacts,
+                    profile,
                     ..user
                 };
                Ok(user)
             }
             else 
             {
-                logger::error!("{}", user.err().unwrap());
-                Err(error::Error::AuthError(["Пользователь `", &user_id.to_string(), "` не найден"].concat()))
+                Err(Error::UserNotFound)
+            }
+        })
+    }
+    fn get_user_by_username<'a>(&'a self, username: &'a str) -> Pin<Box<dyn Future<Output = Result<UserDbo, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let sql = "SELECT id FROM users WHERE username = $1";
+            let id: Option<String> = sqlx::query_scalar(&sql)
+            .bind(username)
+            .fetch_optional(&*connection).await?;
+            if let Some(id) = id
+            {
+                self.get_user(&id.parse().unwrap()).await
+            }
+            else 
+            {
+                Err(Error::UserNotFound)
+            }
+        })
+    }
+    fn get_users<'a>(&'a self) -> Pin<Box<dyn Future<Output = Result<Vec<UserDbo>, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let sql = "SELECT id, username, password, is_active, role, json(audiences) as audiences, avatar FROM users ORDER BY username";
+            let users = sqlx::query_as::<_, UserDbo>(&sql)
+            .fetch_all(&*connection).await?;
+            let sql = ["SELECT ", CONTACT_FIELDS, " FROM contacts"].concat();
+            let mut contacts = sqlx::query_as::<_, ContactDbo>(&sql)
+            .fetch_all(&*connection).await?;
+            let users = users.into_iter().map(|u|
+            {
+                let (user_contacts, other): (Vec<ContactDbo>, Vec<ContactDbo>) = contacts.drain(..).partition(|c| c.user_id == u.id);
+                contacts = other;
+                UserDbo 
+                {
+                    contacts: user_contacts,
+                    ..u
+                }
+            }).collect();
+            Ok(users)
+        })
+    }
+    fn get_locale<'a>(&'a self, user_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<Option<String>, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let sql = "SELECT locale FROM profiles WHERE user_id = $1";
+            let locale: Option<String> = sqlx::query_scalar(sql)
+            .bind(user_id.to_string())
+            .fetch_optional(&*connection).await?;
+            Ok(locale)
+        })
+    }
+    fn update_avatar<'a>(&'a self, user_id: &'a uuid::Uuid, avatar: Option<&'a str>) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let sql = "UPDATE users SET avatar = $2 WHERE id = $1";
+            let result = sqlx::query(&sql)
+            .bind(user_id.to_string())
+            .bind(avatar)
+            .execute(&*connection).await?;
+            if result.rows_affected() == 0
+            {
+                Err(Error::UserNotFound)
+            }
+            else 
+            {
+                Ok(())
+            }
+        })
+    }
+    fn get_contact<'a>(&'a self, contact_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<ContactDbo, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let sql = ["SELECT ", CONTACT_FIELDS, " FROM contacts WHERE id = $1"].concat();
+            let contact = sqlx::query_as::<_, ContactDbo>(&sql)
+            .bind(contact_id.to_string())
+            .fetch_optional(&*connection).await?;
+            contact.ok_or(Error::ContactNotFound)
+        })
+    }
+    fn find_username_by_verified_contact<'a>(&'a self, contact_type: ContactType, contact: &'a str) -> Pin<Box<dyn Future<Output = Result<Option<String>, Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            //подтвержденный контакт уникален благодаря contacts_verified_idx
+            let sql = "SELECT u.username FROM contacts c JOIN users u ON u.id = c.user_id WHERE c.contact_type = $1 AND c.contact = $2 AND c.verified = 1";
+            let username: Option<String> = sqlx::query_scalar(&sql)
+            .bind(contact_type.to_string())
+            .bind(contact)
+            .fetch_optional(&*connection).await?;
+            Ok(username)
+        })
+    }
+    fn add_contact<'a>(&'a self, contact: ContactDbo) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let sql = "SELECT EXISTS(SELECT 1 FROM contacts WHERE user_id = $1 AND contact_type = $2 AND contact = $3)";
+            let exists: bool = sqlx::query_scalar(&sql)
+            .bind(contact.user_id.to_string())
+            .bind(contact.contact_type.to_string())
+            .bind(&contact.contact)
+            .fetch_one(&*connection).await?;
+            if exists
+            {
+                return Err(Error::ValidationError(tr_args("contact_already_added", &[&contact.contact])));
+            }
+            let mut tx = connection.begin().await?;
+            save_contacts(&mut *tx, &contact.user_id, std::slice::from_ref(&contact)).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+    fn delete_contact<'a>(&'a self, user_id: &'a uuid::Uuid, contact_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let mut tx = connection.begin().await?;
+            let sql = "DELETE FROM contacts WHERE id = $1 AND user_id = $2";
+            let result = sqlx::query(&sql)
+            .bind(contact_id.to_string())
+            .bind(user_id.to_string())
+            .execute(&mut *tx).await?;
+            if result.rows_affected() == 0
+            {
+                return Err(Error::ContactNotFound);
+            }
+            let sql = "DELETE FROM contacts_verification WHERE contact_id = $1";
+            let _ = sqlx::query(&sql)
+            .bind(contact_id.to_string())
+            .execute(&mut *tx).await?;
+            //если удален основной контакт, основным становится самый ранний из оставшихся
+            save_contacts(&mut *tx, user_id, &[]).await?;
+            tx.commit().await?;
+            Ok(())
+        })
+    }
+    fn set_primary_contact<'a>(&'a self, user_id: &'a uuid::Uuid, contact_id: &'a uuid::Uuid) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        let connection = Arc::clone(&self.connection);
+        Box::pin(async move 
+        {
+            let mut tx = connection.begin().await?;
+            let sql = "UPDATE contacts SET is_primary = (id = $1) WHERE user_id = $2";
+            let _ = sqlx::query(&sql)
+            .bind(contact_id.to_string())
+            .bind(user_id.to_string())
+            .execute(&mut *tx).await?;
+            let sql = "SELECT EXISTS(SELECT 1 FROM contacts WHERE id = $1 AND user_id = $2)";
+            let exists: bool = sqlx::query_scalar(&sql)
+            .bind(contact_id.to_string())
+            .bind(user_id.to_string())
+            .fetch_one(&mut *tx).await?;
+            if exists
+            {
+                tx.commit().await?;
+                Ok(())
+            }
+            else 
+            {
+                tx.rollback().await?;
+                Err(Error::ContactNotFound)
             }
         })
     }
@@ -392,15 +686,15 @@ impl IUserRepository for UserRepository
         let connection = Arc::clone(&self.connection);
         Box::pin(async move 
         {
-            let mut rand = utilites::SimpleRand::new(666);
-            let code = rand.rand_range(1000, 9999);
-            let sql = "INSERT INTO contacts_verification (contact_id, code, expiration_time) VALUES ($1, $2, $3)";
+            let code: u32 = rand::Rng::random_range(&mut rand::rng(), 1000..=9999);
+            //повторный запрос заменяет предыдущий код
+            let sql = "INSERT OR REPLACE INTO contacts_verification (contact_id, code, expiration_time) VALUES ($1, $2, $3)";
             let _ = sqlx::query(&sql)
             .bind(contact_id.to_string())
             .bind(&code)
             .bind(Date::now().add_minutes(10).format(utilites::DateFormat::Serialize))
             .execute(&*connection).await?;
-            Ok(code as u32)
+            Ok(code)
         })
     }
     fn contact_verification_accept<'a>(&'a self, contact_id: &'a uuid::Uuid, code: u32) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
@@ -426,14 +720,28 @@ impl IUserRepository for UserRepository
                     }
                     else 
                     {
+                        let sql = "SELECT EXISTS(SELECT 1 FROM contacts c JOIN contacts v ON c.contact_type = v.contact_type AND c.contact = v.contact WHERE v.id = $1 AND c.id != v.id AND c.verified = 1)";
+                        let busy: bool = sqlx::query_scalar(&sql)
+                        .bind(v.contact_id.to_string())
+                        .fetch_one(&*connection).await?;
+                        if busy
+                        {
+                            return Err(Error::ContactAlreadyUsed);
+                        }
+                        let mut tx = connection.begin().await?;
                         let sql = "DELETE FROM contacts_verification WHERE contact_id = $1";
                         let _ = sqlx::query(&sql)
                         .bind(v.contact_id.to_string())
-                        .execute(&*connection).await?;
+                        .execute(&mut *tx).await?;
+                        let sql = "UPDATE contacts SET verified = 1 WHERE id = $1";
+                        let _ = sqlx::query(&sql)
+                        .bind(v.contact_id.to_string())
+                        .execute(&mut *tx).await?;
                         let sql = "UPDATE users SET is_active = 1 WHERE id = (SELECT user_id from contacts WHERE id = $1)";
                         let _ = sqlx::query(&sql)
                         .bind(v.contact_id.to_string())
-                        .execute(&*connection).await?;
+                        .execute(&mut *tx).await?;
+                        tx.commit().await?;
                         Ok(())
                     }
                 }
@@ -451,9 +759,6 @@ impl UserRepository
 {
     pub async fn new(pool: Arc<Pool<Sqlite>>) -> Result<Self, Error>
     {
-        let _ = sqlx::query(create_users_table_sql()).execute(&*pool).await?;
-        let _ = sqlx::query(create_contacts_table_sql()).execute(&*pool).await?;
-        let _ = sqlx::query(create_verification_table_sql()).execute(&*pool).await?;
         Ok(Self
         {
             connection: pool,
@@ -465,143 +770,233 @@ mod tests
 {
     use std::sync::Arc;
 
-    use crate::{db::{connection, user_repository::{UserDbo}, IUserRepository}, Role};
+    use crate::{configuration::{Configuration, IN_MEMORY_DATABASE}, db::{connection, migrations, user_repository::{ProfileDbo, UserDbo}, IUserRepository}, ContactType, Error, Role};
 
-    
-    #[tokio::test]
-    async fn test_create_1()
+    ///у каждого теста своя база в памяти, тесты не зависят друг от друга и от порядка запуска
+    async fn test_repository() -> Box<dyn IUserRepository + Send + Sync>
     {
-        let pool = Arc::new(connection::new_connection("planner").await.unwrap());
-        let repo: Box<dyn IUserRepository + Send + Sync> = Box::new(super::UserRepository::new(pool).await.unwrap());
-        let user = UserDbo
+        let pool = connection::new_connection(IN_MEMORY_DATABASE, &Configuration::default()).await.unwrap();
+        migrations::migrate(&pool, migrations::PLANNER_MIGRATIONS, migrations::VERSION_TABLE).await.unwrap();
+        Box::new(super::UserRepository::new(Arc::new(pool)).await.unwrap())
+    }
+    fn test_user(id: &str, username: &str, role: Role) -> UserDbo
+    {
+        UserDbo
         {
-            id: "0195ae79-6004-76b2-8dd4-8e94d6e5bddb".parse().unwrap(),
-            username: "TestUser1".to_owned(),
+            id: id.parse().unwrap(),
+            username: username.to_owned(),
             password: "test_password".to_owned(),
             is_active: true,
-            role: Role::Administrator,
+            role,
             audiences: Vec::new(),
+            avatar: None,
+            profile: None,
             contacts: Vec::new()
-        }.add_contact("мобильный телефон", "111-222-333")
-        .add_contact("e-mail", "aaa@bbb.ru");
-        let username_is_exists = repo.username_is_busy(&user.username).await.unwrap();
-        if !username_is_exists
-        {
-            let _ = repo.create(user).await.unwrap();
         }
     }
+
+    #[tokio::test]
+    async fn test_create_1()
+    {
+        let repo = test_repository().await;
+        let user = test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddb", "TestUser1", Role::Administrator)
+        .add_contact(ContactType::Phone, "111-222-333")
+        .add_contact(ContactType::Email, "aaa@bbb.ru");
+        assert!(!repo.username_is_busy(&user.username).await.unwrap());
+        let _ = repo.create(user).await.unwrap();
+        assert!(repo.username_is_busy("TestUser1").await.unwrap());
+    }
     #[tokio::test]
     async fn test_create_2()
     {
-        let pool = Arc::new(connection::new_connection("planner").await.unwrap());
-        let repo: Box<dyn IUserRepository + Send + Sync> = Box::new(super::UserRepository::new(pool).await.unwrap());
-        let user = UserDbo
-        {
-            id: "0195ae79-dcb1-7943-ba11-99dccc909833".parse().unwrap(),
-            username: "TestUser2".to_owned(),
-            password: "test_password".to_owned(),
-            is_active: true,
-            role: Role::Administrator,
-            audiences: Vec::new(),
-            contacts: Vec::new()
-        }.add_contact("мобильный телефон", "999-666-333")
-        .add_contact("e-mail", "test@test.ru");
-        let username_is_exists = repo.username_is_busy(&user.username).await.unwrap();
-        if !username_is_exists
-        {
-            let _ = repo.create(user).await.unwrap();
-        }
+        let repo = test_repository().await;
+        let user = test_user("0195ae79-dcb1-7943-ba11-99dccc909833", "TestUser2", Role::Administrator)
+        .add_contact(ContactType::Phone, "999-666-333")
+        .add_contact(ContactType::Email, "test@test.ru");
+        let _ = repo.create(user).await.unwrap();
+        let user = repo.get_user_by_username("TestUser2").await.unwrap();
+        assert_eq!(user.contacts.len(), 2);
     }
     #[tokio::test]
     async fn test_create_3()
     {
-        let pool = Arc::new(connection::new_connection("planner").await.unwrap());
-        let repo: Box<dyn IUserRepository + Send + Sync> = Box::new(super::UserRepository::new(pool).await.unwrap());
-        let user = UserDbo
-        {
-            id: "0195ae7a-3cda-7b11-aa6b-46992a3e209f".parse().unwrap(),
-            username: "TestUser3".to_owned(),
-            password: "test_password".to_owned(),
-            is_active: true,
-            role: Role::Administrator,
-            audiences: Vec::new(),
-            contacts: Vec::new()
-        }.add_contact("мобильный телефон", "000-000-000")
-        .add_contact("e-mail", "test222@test.ru");
-        let username_is_exists = repo.username_is_busy(&user.username).await.unwrap();
-        if !username_is_exists
-        {
-            let _ = repo.create(user).await.unwrap();
-        }
+        let repo = test_repository().await;
+        let user = test_user("0195ae7a-3cda-7b11-aa6b-46992a3e209f", "TestUser3", Role::Administrator)
+        .add_contact(ContactType::Phone, "000-000-000")
+        .add_contact(ContactType::Email, "test222@test.ru");
+        let _ = repo.create(user).await.unwrap();
+        let user = repo.get_user_by_username("TestUser3").await.unwrap();
+        assert_eq!(user.id.to_string(), "0195ae7a-3cda-7b11-aa6b-46992a3e209f");
+    }
+    #[tokio::test]
+    async fn test_create_inactive()
+    {
+        let repo = test_repository().await;
+        let mut user = test_user("0195ae7a-3cda-7b11-aa6b-46992a3e209f", "TestUser4", Role::User);
+        user.is_active = false;
+        let _ = repo.create(user).await.unwrap();
+        let user = repo.get_user_by_username("TestUser4").await.unwrap();
+        assert!(!user.is_active);
+        let user = test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddb", "TestUser5", Role::User);
+        let _ = repo.create(user).await.unwrap();
+        assert!(repo.get_user_by_username("TestUser5").await.unwrap().is_active);
+    }
+
+    #[tokio::test]
+    async fn test_user_not_found()
+    {
+        let repo = test_repository().await;
+        let user = test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddd", "TestUser1", Role::User);
+        assert!(matches!(repo.get_user(&user.id).await, Err(Error::UserNotFound)));
+        assert!(matches!(repo.update_info(user.clone()).await, Err(Error::UserNotFound)));
+        assert!(matches!(repo.update(user).await, Err(Error::UserNotFound)));
     }
 
     #[tokio::test]
     async fn test_update()
     {
-        let pool = Arc::new(connection::new_connection("planner").await.unwrap());
-        let repo: Box<dyn IUserRepository + Send + Sync> = Box::new(super::UserRepository::new(pool).await.unwrap());
-        let user = UserDbo
-        {
-            id: "0195ae79-6004-76b2-8dd4-8e94d6e5bddb".parse().unwrap(),
-            username: "TestUser1".to_owned(),
-            password: "test_password".to_owned(),
-            is_active: true,
-            role: Role::User,
-            audiences: vec!["www.111.ru".to_owned(), "www.222.ru".to_owned()],
-            contacts: Vec::new(),
-        }.add_contact("мобильный телефон", "111-222-333")
-        .add_contact("e-mail", "111@bbb.ru");
-        
+        let repo = test_repository().await;
+        let _ = repo.create(test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddb", "TestUser1", Role::Administrator)).await.unwrap();
+        let mut user = test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddb", "TestUser1", Role::User)
+        .add_contact(ContactType::Phone, "111-222-333")
+        .add_contact(ContactType::Email, "111@bbb.ru");
+        user.audiences = vec!["www.111.ru".to_owned(), "www.222.ru".to_owned()];
         let _ = repo.update(user).await.unwrap();
-        
+        let user = repo.get_user_by_username("TestUser1").await.unwrap();
+        assert!(matches!(user.role, Role::User));
+        assert_eq!(user.audiences.len(), 2);
     }
     #[tokio::test]
     async fn test_partialy_update()
     {
-        let pool = Arc::new(connection::new_connection("planner").await.unwrap());
-        let repo: Box<dyn IUserRepository + Send + Sync> = Box::new(super::UserRepository::new(pool).await.unwrap());
-        let user = UserDbo
+        let repo = test_repository().await;
+        let _ = repo.create(test_user("0195ae79-dcb1-7943-ba11-99dccc909833", "TestUser2", Role::Administrator)).await.unwrap();
+        let user = test_user("0195ae79-dcb1-7943-ba11-99dccc909833", "TestUser666", Role::Administrator)
+        .add_contact(ContactType::Phone, "999-666-333")
+        .add_contact(ContactType::Email, "abyrvalg@ebb.ru");
+        let _ = repo.update_info(user).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_profile()
+    {
+        let repo = test_repository().await;
+        let _ = repo.create(test_user("0195ae79-dcb1-7943-ba11-99dccc909833", "TestUser2", Role::Administrator)).await.unwrap();
+        let user_id: uuid::Uuid = "0195ae79-dcb1-7943-ba11-99dccc909833".parse().unwrap();
+        let mut user = repo.get_user(&user_id).await.unwrap();
+        user.profile = Some(ProfileDbo
         {
-            id: "0195ae79-dcb1-7943-ba11-99dccc909833".parse().unwrap(),
-            username: "TestUser666".to_owned(),
-            password: "test_password".to_owned(),
-            is_active: true,
-            role: Role::Administrator,
-            audiences: Vec::new(),
-            contacts: Vec::new(),
-        }.add_contact("мобильный телефон", "999-666-333")
-        .add_contact("e-mail", "abyrvalg@ebb.ru");
+            last_name: "Иванов".to_owned(),
+            first_name: "Иван".to_owned(),
+            middle_name: "Иванович".to_owned(),
+            position: "Инженер".to_owned(),
+            department: "Отдел разработки".to_owned(),
+            timezone: "Europe/Moscow".to_owned(),
+            locale: "ru".to_owned()
+        });
         let _ = repo.update_info(user).await.unwrap();
-        
+        let user = repo.get_user(&user_id).await.unwrap();
+        assert_eq!(user.profile.unwrap().last_name, "Иванов");
     }
 
     #[tokio::test]
     async fn test_change_password()
     {
-        let pool = Arc::new(connection::new_connection("planner").await.unwrap());
-        let repo: Box<dyn IUserRepository + Send + Sync> = Box::new(super::UserRepository::new(pool).await.unwrap());
-        let user = UserDbo
-        {
-            id: "0195ae7a-3cda-7b11-aa6b-46992a3e209f".parse().unwrap(),
-            username: "TestUser666".to_owned(),
-            password: "test_password".to_owned(),
-            is_active: true,
-            role: Role::User,
-            audiences: Vec::new(),
-            contacts: Vec::new(),
-        }.add_contact("мобильный телефон", "999-666-333")
-        .add_contact("e-mail", "abyrvalg@ebb.ru");
-        let _ = repo.update_password(&user.id, "test_password", "test_password2").await.unwrap();
-        
+        let repo = test_repository().await;
+        let user = test_user("0195ae7a-3cda-7b11-aa6b-46992a3e209f", "TestUser3", Role::User);
+        let id = user.id;
+        let _ = repo.create(user).await.unwrap();
+        let _ = repo.update_password(&id, "test_password", "test_password2").await.unwrap();
+        assert!(repo.update_password(&id, "test_password", "test_password3").await.is_err());
     }
     #[tokio::test]
     async fn test_login()
     {
         logger::StructLogger::new_default();
-        let pool = Arc::new(connection::new_connection("planner").await.unwrap());
-        let repo: Box<dyn IUserRepository + Send + Sync> = Box::new(super::UserRepository::new(pool).await.unwrap());
+        let repo = test_repository().await;
+        let user = test_user("0195ae7a-3cda-7b11-aa6b-46992a3e209f", "TestUser3", Role::Administrator);
+        let id = user.id;
+        let _ = repo.create(user).await.unwrap();
+        let _ = repo.update_password(&id, "test_password", "test_password2").await.unwrap();
         let user = repo.login("TestUser3", "test_password2").await.unwrap();
         assert_eq!(user.id.to_string(), "0195ae7a-3cda-7b11-aa6b-46992a3e209f");
-        
+        //неверный пароль и неизвестный пользователь неотличимы
+        let wrong_password = repo.login("TestUser3", "test_password").await.err().unwrap();
+        let unknown_user = repo.login("TestUser4", "test_password").await.err().unwrap();
+        assert!(matches!(&wrong_password, Error::AuthError(m) if m == "invalid_credentials"));
+        assert_eq!(wrong_password.to_problem().detail, unknown_user.to_problem().detail);
+    }
+    #[tokio::test]
+    async fn test_single_primary_contact()
+    {
+        let repo = test_repository().await;
+        let user = test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddb", "TestUser1", Role::User)
+        .add_contact(ContactType::Phone, "+79001112233")
+        .add_contact(ContactType::Email, "aaa@bbb.ru");
+        let id = user.id;
+        let _ = repo.create(user).await.unwrap();
+        let primary = |user: &UserDbo| user.contacts.iter().filter(|c| c.is_primary).map(|c| c.contact.clone()).collect::<Vec<_>>();
+        let mut user = repo.get_user(&id).await.unwrap();
+        assert_eq!(primary(&user), vec!["+79001112233"]);
+        //основным становится e-mail, телефон перестает быть основным
+        for c in user.contacts.iter_mut()
+        {
+            c.is_primary = c.contact_type == ContactType::Email;
+        }
+        let _ = repo.update_info(user).await.unwrap();
+        let mut user = repo.get_user(&id).await.unwrap();
+        assert_eq!(primary(&user), vec!["aaa@bbb.ru"]);
+        //если основной контакт не отмечен, остается текущий
+        for c in user.contacts.iter_mut()
+        {
+            c.is_primary = false;
+        }
+        let _ = repo.update_info(user).await.unwrap();
+        assert_eq!(primary(&repo.get_user(&id).await.unwrap()), vec!["aaa@bbb.ru"]);
     }
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn test_update_rollback()
+    {
+        let repo = test_repository().await;
+        let user = test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddc", "TestUser1", Role::User)
+        .add_contact(ContactType::Email, "aaa@bbb.ru");
+        let id = user.id;
+        let _ = repo.create(user).await.unwrap();
+        let mut user = repo.get_user(&id).await.unwrap();
+        user.role = Role::Administrator;
+        user.is_active = false;
+        //контакт несуществующего пользователя нарушает внешний ключ, изменение роли тоже отменяется
+        user.contacts[0].id = uuid::Uuid::now_v7();
+        user.contacts[0].user_id = uuid::Uuid::now_v7();
+        user.contacts[0].contact = "ccc@ddd.ru".to_owned();
+        assert!(repo.update(user).await.is_err());
+        let user = repo.get_user(&id).await.unwrap();
+        assert_eq!((user.role, user.is_active), (Role::User, true));
+        assert_eq!(user.contacts.len(), 1);
+        assert_eq!(user.contacts[0].contact, "aaa@bbb.ru");
+    }
+    #[tokio::test]
+    async fn test_delete_primary_contact()
+    {
+        let repo = test_repository().await;
+        let mut user = test_user("0195ae79-6004-76b2-8dd4-8e94d6e5bddd", "TestUser1", Role::User)
+        .add_contact(ContactType::Phone, "+79001112233")
+        .add_contact(ContactType::Email, "aaa@bbb.ru")
+        .add_contact(ContactType::Email, "ccc@ddd.ru");
+        //идентификаторы по порядку создания контактов
+        for (c, id) in user.contacts.iter_mut().zip(["0195ae7a-0000-7000-8000-000000000001", "0195ae7a-0000-7000-8000-000000000002", "0195ae7a-0000-7000-8000-000000000003"])
+        {
+            c.id = id.parse().unwrap();
+            c.is_primary = c.contact == "ccc@ddd.ru";
+        }
+        let id = user.id;
+        let _ = repo.create(user).await.unwrap();
+        let primary = |user: &UserDbo| user.contacts.iter().filter(|c| c.is_primary).map(|c| c.contact.clone()).collect::<Vec<_>>();
+        assert_eq!(primary(&repo.get_user(&id).await.unwrap()), vec!["ccc@ddd.ru"]);
+        repo.delete_contact(&id, &"0195ae7a-0000-7000-8000-000000000003".parse().unwrap()).await.unwrap();
+        assert_eq!(primary(&repo.get_user(&id).await.unwrap()), vec!["+79001112233"]);
+        //удаление не основного контакта основной не меняет
+        repo.delete_contact(&id, &"0195ae7a-0000-7000-8000-000000000002".parse().unwrap()).await.unwrap();
+        assert_eq!(primary(&repo.get_user(&id).await.unwrap()), vec!["+79001112233"]);
+    }
+}
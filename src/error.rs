@@ -1,13 +1,18 @@
-use axum::response::{IntoResponse, Response};
-use hyper::{HeaderMap, StatusCode};
-use jwt_authentification::{Cookie, CookieJar, Duration as CookieMaxLife};
+use axum::{extract::rejection::{JsonRejection, MultipartRejection, PathRejection, QueryRejection}, http::{header::{CONTENT_TYPE, SET_COOKIE}, HeaderValue}, response::{IntoResponse, Response}};
+use hyper::StatusCode;
+use jwt_authentification::{Cookie, Duration as CookieMaxLife};
+use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
+use crate::{i18n::{tr, tr_args}, middleware::current_request_id};
 
 #[derive(Error, Debug)]
 pub enum Error 
 {
     #[error(transparent)]
     DeserializeError(#[from] serde_json::Error),
+    #[error("Неверный формат данных: {0}")]
+    JsonError(String),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
     #[error(transparent)]
@@ -31,7 +36,218 @@ pub enum Error
     #[error("Отпечаток сессии не совпадает, сессия будет удалена, необходимо зайти заново")]
     WrongFingerprintError(String),
     #[error("Уникальный идетификатор клиента не найден или имеет неверный формат")]
-    FingerprintNotFound
+    FingerprintNotFound,
+    #[error("Ошибка импорта: {0}")]
+    ImportError(String),
+    #[error("Ошибка загрузки аватара: {0}")]
+    AvatarError(String),
+    #[error(transparent)]
+    ImageError(#[from] image::ImageError),
+    #[error("Ошибка проверки данных: {0}")]
+    ValidationError(String),
+    #[error("Контакт не найден")]
+    ContactNotFound,
+    #[error("Аватар не загружен")]
+    AvatarNotFound,
+    #[error("Задача `{0}` не найдена")]
+    JobNotFound(String),
+    #[error("Этот контакт уже подтвержден другим пользователем")]
+    ContactAlreadyUsed,
+    #[error("Идентификатор `{0}` соответствует нескольким пользователям, войдите по имени пользователя")]
+    AmbiguousLogin(String),
+    #[error("Слишком много запросов, попробуйте позже")]
+    TooManyRequests,
+    #[error("Функция `{0}` отключена в настройках сервера")]
+    FeatureDisabled(String),
+    #[error("Ошибка миграции базы данных: {0}")]
+    MigrationError(String),
+    #[error("Ошибка в настройках: {}", .0.iter().map(|i| i.to_string()).collect::<Vec<String>>().join("; "))]
+    ConfigurationError(Vec<crate::configuration::ConfigurationIssue>),
+    #[error("Ошибка проверки данных: {}", .0.iter().map(|e| [e.field.as_str(), ": ", e.message.as_str()].concat()).collect::<Vec<String>>().join("; "))]
+    InvalidFields(Vec<FieldError>),
+    #[error("Внутренняя ошибка сервера: {0}")]
+    Internal(String)
+}
+
+///Ошибка в конкретном поле запроса
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct FieldError
+{
+    pub field: String,
+    pub message: String
+}
+impl FieldError
+{
+    pub fn new<F: ToString, M: ToString>(field: F, message: M) -> Self
+    {
+        Self
+        {
+            field: field.to_string(),
+            message: message.to_string()
+        }
+    }
+}
+
+impl Error
+{
+    ///Стабильный машиночитаемый код ошибки, не меняется при изменении текста сообщения
+    pub fn code(&self) -> &'static str
+    {
+        match self
+        {
+            Error::DeserializeError(_) 
+            | Error::JsonError(_) => "invalid_json",
+            Error::IoError(_) => "io_error",
+            Error::SqlxError(_) => "database_error",
+            Error::AuthError(_) => "unauthorized",
+            Error::SessionExpired => "session_expired",
+            Error::UserNotFound => "user_not_found",
+            Error::SessionNotFound => "session_not_found",
+            Error::VerificationCodeExpired => "verification_code_expired",
+            Error::VerificationCodeWrong => "verification_code_wrong",
+            Error::VerificationNotFound => "verification_not_found",
+            Error::JwtError(_) => "invalid_access_key",
+            Error::WrongFingerprintError(_) => "fingerprint_mismatch",
+            Error::FingerprintNotFound => "fingerprint_missing",
+            Error::ImportError(_) => "import_error",
+            Error::AvatarError(_) => "avatar_error",
+            Error::ImageError(_) => "invalid_image",
+            Error::ValidationError(_) => "validation_error",
+            Error::ContactNotFound => "contact_not_found",
+            Error::AvatarNotFound => "avatar_not_found",
+            Error::JobNotFound(_) => "job_not_found",
+            Error::ContactAlreadyUsed => "contact_already_used",
+            Error::AmbiguousLogin(_) => "ambiguous_login",
+            Error::TooManyRequests => "too_many_requests",
+            Error::FeatureDisabled(_) => "feature_disabled",
+            Error::MigrationError(_) => "migration_error",
+            Error::ConfigurationError(_) => "invalid_configuration",
+            Error::InvalidFields(_) => "invalid_fields",
+            Error::Internal(_) => "internal_error"
+        }
+    }
+    pub fn status(&self) -> StatusCode
+    {
+        match self
+        {
+            Error::DeserializeError(_) 
+            | Error::JsonError(_) 
+            | Error::FingerprintNotFound => StatusCode::BAD_REQUEST,
+            Error::AuthError(_) 
+            | Error::SessionExpired 
+            | Error::JwtError(_) 
+            | Error::WrongFingerprintError(_) => StatusCode::UNAUTHORIZED,
+            Error::FeatureDisabled(_) => StatusCode::FORBIDDEN,
+            Error::UserNotFound 
+            | Error::SessionNotFound 
+            | Error::VerificationNotFound 
+            | Error::ContactNotFound 
+            | Error::AvatarNotFound 
+            | Error::JobNotFound(_) => StatusCode::NOT_FOUND,
+            Error::ContactAlreadyUsed 
+            | Error::AmbiguousLogin(_) => StatusCode::CONFLICT,
+            Error::VerificationCodeExpired 
+            | Error::VerificationCodeWrong 
+            | Error::ImportError(_) 
+            | Error::AvatarError(_) 
+            | Error::ImageError(_) 
+            | Error::ValidationError(_) 
+            | Error::ConfigurationError(_) 
+            | Error::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            Error::IoError(_) 
+            | Error::SqlxError(_) 
+            | Error::MigrationError(_) 
+            | Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+    fn field_errors(&self) -> Option<Vec<FieldError>>
+    {
+        match self
+        {
+            Error::InvalidFields(errors) => Some(errors.clone()),
+            Error::ConfigurationError(issues) => Some(issues.iter().map(|i| FieldError::new(&i.field, &i.message)).collect()),
+            _ => None
+        }
+    }
+    ///Описание ошибки для клиента на языке текущего запроса, внутренние ошибки не раскрываются.
+    /// Текст в вариантах с описанием тоже переводится если он является идентификатором сообщения
+    fn detail(&self) -> String
+    {
+        match self
+        {
+            Error::DeserializeError(e) => tr_args(self.code(), &[&e.to_string()]),
+            Error::JsonError(m) => tr_args(self.code(), &[m]),
+            Error::AuthError(m)
+            | Error::ImportError(m)
+            | Error::AvatarError(m)
+            | Error::ValidationError(m)
+            | Error::FeatureDisabled(m) => tr_args(self.code(), &[&tr(m)]),
+            Error::AmbiguousLogin(login) => tr_args(self.code(), &[login]),
+            Error::JobNotFound(id) => tr_args(self.code(), &[id]),
+            _ => tr(self.code())
+        }
+    }
+    pub fn to_problem(&self) -> Problem
+    {
+        Problem
+        {
+            error_type: ["urn:planner:error:", self.code()].concat(),
+            title: self.status().canonical_reason().unwrap_or_default().to_owned(),
+            status: self.status().as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+            request_id: current_request_id(),
+            errors: self.field_errors()
+        }
+    }
+}
+
+///Тело ответа с ошибкой в формате RFC 7807 `application/problem+json`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Problem
+{
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    #[schema(value_type = String)]
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<FieldError>>
+}
+impl Problem
+{
+    ///Ошибка не связанная с вариантом [`Error`], например отсутствующий маршрут
+    pub fn new<D: ToString>(status: StatusCode, code: &'static str, detail: D) -> Self
+    {
+        Problem
+        {
+            error_type: ["urn:planner:error:", code].concat(),
+            title: status.canonical_reason().unwrap_or_default().to_owned(),
+            status: status.as_u16(),
+            detail: detail.to_string(),
+            code,
+            request_id: current_request_id(),
+            errors: None
+        }
+    }
+}
+impl IntoResponse for Problem
+{
+    fn into_response(self) -> Response
+    {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let body = serde_json::to_string(&self).unwrap_or_default();
+        (
+            status,
+            [(CONTENT_TYPE, HeaderValue::from_static("application/problem+json"))],
+            body
+        ).into_response()
+    }
 }
 
 impl serde::Serialize for Error 
@@ -48,48 +264,99 @@ impl IntoResponse for Error
 {
     fn into_response(self) -> axum::response::Response 
     {
-        let message = self.to_string();
-        match self
+        if self.status().is_server_error()
+        {
+            logger::error!("{}", self.to_string());
+        }
+        match &self
         {
             Error::WrongFingerprintError(cookie_name) =>
             {
-                cookie_remove_error_response(&message, &cookie_name)
-            }
-            Error::SqlxError(e) => 
-            {
-                let body = "Ошибка базы данных";
-                (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
-            },
-            Error::VerificationCodeExpired =>
-            {
-                let body = self.to_string();
-                (StatusCode::REQUEST_TIMEOUT, body).into_response()
-            }
-            _ => 
-            {
-                let body = self.to_string();
-                (StatusCode::BAD_REQUEST, body).into_response()
+                cookie_remove_error_response(&self, cookie_name)
             }
+            _ => self.to_problem().into_response()
         }
     }
 }
 
+///Тело json не разобрано: синтаксическая ошибка или неверный заголовок - [`Error::JsonError`],
+/// данные не соответствуют ожидаемой структуре - [`Error::InvalidFields`]
+impl From<JsonRejection> for Error
+{
+    fn from(rejection: JsonRejection) -> Self
+    {
+        match rejection
+        {
+            JsonRejection::JsonDataError(e) => Error::InvalidFields(vec![FieldError::new("body", e.body_text())]),
+            _ => Error::JsonError(rejection.body_text())
+        }
+    }
+}
+impl From<QueryRejection> for Error
+{
+    fn from(rejection: QueryRejection) -> Self
+    {
+        Error::InvalidFields(vec![FieldError::new("query", rejection.body_text())])
+    }
+}
+impl From<PathRejection> for Error
+{
+    fn from(rejection: PathRejection) -> Self
+    {
+        match rejection
+        {
+            PathRejection::FailedToDeserializePathParams(e) => Error::InvalidFields(vec![FieldError::new("path", e.body_text())]),
+            _ => Error::Internal(rejection.body_text())
+        }
+    }
+}
+impl From<MultipartRejection> for Error
+{
+    fn from(rejection: MultipartRejection) -> Self
+    {
+        Error::ValidationError(rejection.body_text())
+    }
+}
 
-pub fn cookie_remove_error_response<T: ToString>(body: T, cookie_name: &str) -> Response<axum::body::Body>
+///Ответ с ошибкой и удалением cookie сессии
+pub fn cookie_remove_error_response(error: &Error, cookie_name: &str) -> Response<axum::body::Body>
 {
-    
-    let error  = body.to_string();
-    let body = axum::body::Body::new(error);
+    let mut response = error.to_problem().into_response();
     let cookie: Cookie = Cookie::build((cookie_name, "")).path("/").max_age(CookieMaxLife::seconds(0)).into();
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        axum::http::header::SET_COOKIE,
-        cookie.to_string().parse().unwrap(),
-    );
-    let mut resp = Response::builder()
-    .status(StatusCode::UNAUTHORIZED)
-    .body(body)
-    .unwrap();
-    *resp.headers_mut() = headers;
-    resp
+    if let Ok(cookie) = cookie.to_string().parse()
+    {
+        response.headers_mut().insert(SET_COOKIE, cookie);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests
+{
+    use hyper::StatusCode;
+    use crate::i18n::{with_locale, Locale};
+    use super::{Error, FieldError};
+
+    #[test]
+    fn test_problem()
+    {
+        let problem = Error::UserNotFound.to_problem();
+        assert_eq!(problem.status, 404);
+        assert_eq!(problem.code, "user_not_found");
+        let problem = Error::InvalidFields(vec![FieldError::new("profile.timezone", "Неизвестный часовой пояс")]).to_problem();
+        assert_eq!(problem.status, StatusCode::UNPROCESSABLE_ENTITY.as_u16());
+        let json = serde_json::to_value(&problem).unwrap();
+        assert_eq!(json["type"], "urn:planner:error:invalid_fields");
+        assert_eq!(json["errors"][0]["field"], "profile.timezone");
+        assert!(json.get("request_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_problem_locale()
+    {
+        let problem = with_locale(Locale::En, async { Error::AuthError("missing_session_cookie".to_owned()).to_problem() }).await;
+        assert_eq!(problem.detail, "Authorization error: session cookie is missing");
+        let problem = Error::SessionNotFound.to_problem();
+        assert_eq!(problem.detail, "Сессия не найдена");
+    }
 }
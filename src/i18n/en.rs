@@ -0,0 +1,111 @@
+///Каталог сообщений на английском языке
+pub const MESSAGES: &[(&str, &str)] = &[
+    //ошибки, ключ совпадает с `Error::code()`
+    ("invalid_json", "Invalid request data: {0}"),
+    ("io_error", "Internal server error"),
+    ("database_error", "Database error"),
+    ("unauthorized", "Authorization error: {0}"),
+    ("session_expired", "Session has expired, please sign in again"),
+    ("user_not_found", "User with these credentials was not found"),
+    ("session_not_found", "Session not found"),
+    ("verification_code_expired", "Your verification code has expired, please try again"),
+    ("verification_code_wrong", "Wrong verification code, please try again"),
+    ("verification_not_found", "Contact verification was not found, please request verification again"),
+    ("invalid_access_key", "Access key is invalid"),
+    ("fingerprint_mismatch", "Session fingerprint does not match, the session will be removed, please sign in again"),
+    ("fingerprint_missing", "Client identifier is missing or has invalid format"),
+    ("import_error", "Import error: {0}"),
+    ("avatar_error", "Avatar upload error: {0}"),
+    ("invalid_image", "Unable to read the image"),
+    ("validation_error", "Validation error: {0}"),
+    ("contact_not_found", "Contact not found"),
+    ("contact_already_used", "This contact is already verified by another user"),
+    ("ambiguous_login", "Identifier `{0}` matches several users, please sign in with username"),
+    ("too_many_requests", "Too many requests, please try again later"),
+    ("feature_disabled", "Feature `{0}` is disabled in server settings"),
+    ("migration_error", "Internal server error"),
+    ("invalid_configuration", "Invalid configuration"),
+    ("invalid_fields", "Validation error"),
+    ("internal_error", "Internal server error"),
+    ("route_not_found", "Route not found"),
+    ("missing_host", "Host header is missing"),
+    //ошибки авторизации
+    ("missing_session_cookie", "session cookie is missing"),
+    ("bearer_invalid", "Bearer is not recognized"),
+    ("authorization_header_encoding", "Authorization header has invalid encoding"),
+    ("missing_authorization_header", "Authorization header is missing"),
+    ("user_inactive", "User `{0}` is not active"),
+    ("invalid_credentials", "invalid username or password"),
+    ("login_code_other_device", "Login code was requested from another device"),
+    //проверка данных
+    ("invalid_timezone", "Unknown timezone `{0}`"),
+    ("invalid_locale", "Invalid language format `{0}`"),
+    ("invalid_session_id", "session id `{0}` is not valid"),
+    ("invalid_user_id", "user id `{0}` is not valid"),
+    ("no_verified_contacts", "User has no verified contacts for passwordless login"),
+    ("contact_already_verified", "Contact `{0}` is already verified"),
+    ("avatar_field_missing", "Field `avatar` is missing in the request"),
+    ("avatar_unsupported_type", "Unsupported file type `{0}`, allowed: {1}"),
+    ("avatar_too_large", "File size exceeds {0} KB"),
+    ("avatar_not_image", "File is not an image"),
+    ("avatar_type_mismatch", "File content does not match type `{0}`"),
+    ("rate_limit_client_empty", "Client is not specified"),
+    ("rate_limit_exempt_not_found", "Client `{0}` is not in the exempt list"),
+    ("passwordless_login", "passwordless login"),
+    ("ws_unknown_topic", "Unknown topic `{0}`, expected `project:<id>` or `board:<id>`"),
+    ("ws_too_many_subscriptions", "Too many subscriptions, at most {0} per connection"),
+    ("ws_topic_forbidden", "No access to `{0}`"),
+    ("ws_access_key_expired", "Access key has expired, refresh it and send a `refresh` message"),
+    ("job_not_found", "Job `{0}` not found"),
+    ("invalid_contact_id", "Invalid contact id `{0}`"),
+    ("wrong_old_password", "Wrong old password, please try again"),
+    ("unknown_contact_type", "Unknown contact type `{0}`"),
+    ("invalid_email", "Invalid e-mail format `{0}`"),
+    ("invalid_phone", "Invalid phone number format `{0}`"),
+    ("invalid_messenger_name", "Invalid messenger name format `{0}`"),
+    ("contact_already_added", "Contact `{0}` is already added"),
+    ("import_unknown_format", "Unknown format `{0}`, allowed formats: csv, json"),
+    ("import_row_error", "Failed to parse row {0}: {1}"),
+    ("import_username_missing", "Username is not specified"),
+    ("import_username_repeated", "Username `{0}` is repeated in the import file"),
+    ("username_busy", "Username `{0}` is already taken"),
+    ("unknown_role", "Unknown role `{0}`"),
+    ("password_too_short", "Password must be at least {0} characters long"),
+    ("schedule_invalid_interval", "Invalid interval `{0}`, expected a number with unit s, m, h or d"),
+    ("schedule_fields_count", "Schedule `{0}` must have five fields: minute hour day month weekday"),
+    ("schedule_invalid_field", "Invalid schedule field `{0}`, allowed values are from {1} to {2}"),
+    //настройки
+    ("config_invalid_value", "invalid value `{0}`"),
+    ("config_must_be_positive", "value must be greater than 0"),
+    ("config_invalid_synchronous", "`{0}` is not a synchronous mode, allowed: off, normal, full, extra"),
+    ("config_no_origins", "no origin is specified"),
+    ("config_invalid_origin", "`{0}` is not an origin of the form `http(s)://host[:port]`"),
+    ("config_invalid_url", "`{0}` is not an http(s) address"),
+    ("config_invalid_header_name", "`{0}` is not a header name"),
+    ("config_invalid_cookie_name", "`{0}` is not a cookie name"),
+    ("config_invalid_ip", "`{0}` is not an ip address"),
+    ("config_path_missing", "path is not specified"),
+    ("config_invalid_log_level", "`{0}` is not a log level"),
+    ("config_unknown_rate_limit_group", "unknown route group, allowed: {0}"),
+    ("config_rate_limit_positive", "`capacity` and `refill_per_minute` must be greater than 0"),
+    ("config_tls_paths", "tls requires both `tls_cert_path` and `tls_key_path`"),
+    ("config_redirect_without_tls", "redirect to https requires tls"),
+    ("config_must_differ", "must differ from `{0}`"),
+    ("config_restart_required", "the change takes effect only after server restart, settings were not applied"),
+    //успешные операции
+    ("password_changed", "Password changed successfully"),
+    ("data_updated", "Data updated successfully"),
+    ("session_exited", "You have signed out of session {0}"),
+    ("sessions_deleted", "Sessions removed: `{0}`"),
+    ("avatar_deleted", "Avatar removed"),
+    ("avatar_not_found", "Avatar not found"),
+    ("contact_deleted", "Contact removed"),
+    ("primary_contact_changed", "Primary contact changed"),
+    ("verification_code_sent", "Verification code sent"),
+    ("contact_verified", "Contact verified"),
+    ("admin_section", "you have entered the admin route"),
+    //уведомления
+    ("login_code_notification", "Login code: {0}, valid for {1} min. Or follow the link: {2}"),
+    ("verification_code_notification", "Contact verification code: {0}"),
+    ("account_created_notification", "An account `{0}` has been created for you, password: {1}"),
+];
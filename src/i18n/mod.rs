@@ -0,0 +1,144 @@
+mod ru;
+mod en;
+use std::future::Future;
+
+///Язык сообщений для клиента
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale
+{
+    #[default]
+    Ru,
+    En
+}
+impl Locale
+{
+    ///Язык из строки формата `ru`, `en-US` или `en_GB`, `None` если язык не поддерживается
+    pub fn parse(value: &str) -> Option<Self>
+    {
+        let lang = value.trim().split(['-', '_']).next()?;
+        match lang.to_ascii_lowercase().as_str()
+        {
+            "ru" => Some(Locale::Ru),
+            "en" => Some(Locale::En),
+            _ => None
+        }
+    }
+    ///Поддерживаемый язык с наибольшим весом из заголовка `Accept-Language`
+    pub fn from_accept_language(header: &str) -> Option<Self>
+    {
+        let mut best: Option<(Locale, f32)> = None;
+        for item in header.split(',')
+        {
+            let mut parts = item.split(';');
+            let tag = parts.next().unwrap_or_default();
+            let weight = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            if weight <= 0.0
+            {
+                continue;
+            }
+            if let Some(locale) = Locale::parse(tag)
+            {
+                if best.is_none_or(|(_, w)| weight > w)
+                {
+                    best = Some((locale, weight));
+                }
+            }
+        }
+        best.map(|(l, _)| l)
+    }
+    fn catalog(&self) -> &'static [(&'static str, &'static str)]
+    {
+        match self
+        {
+            Locale::Ru => ru::MESSAGES,
+            Locale::En => en::MESSAGES
+        }
+    }
+}
+
+tokio::task_local!
+{
+    static LOCALE: Locale;
+}
+
+///Язык текущего запроса, вне обработки запроса используется язык по умолчанию
+pub fn current_locale() -> Locale
+{
+    LOCALE.try_with(|l| *l).unwrap_or_default()
+}
+
+///Выполнение `f` с указанным языком сообщений
+pub async fn with_locale<F: Future>(locale: Locale, f: F) -> F::Output
+{
+    LOCALE.scope(locale, f).await
+}
+
+fn lookup(locale: Locale, id: &str) -> Option<&'static str>
+{
+    locale.catalog().iter().find(|(k, _)| *k == id).map(|(_, v)| *v)
+        .or_else(|| Locale::default().catalog().iter().find(|(k, _)| *k == id).map(|(_, v)| *v))
+}
+
+///Сообщение по идентификатору на языке текущего запроса,
+/// если идентификатора нет в каталогах возвращается сам идентификатор (например уже готовый текст ошибки)
+pub fn tr(id: &str) -> String
+{
+    lookup(current_locale(), id).unwrap_or(id).to_owned()
+}
+
+///Сообщение с подстановкой аргументов на места `{0}`, `{1}`...
+pub fn tr_args(id: &str, args: &[&str]) -> String
+{
+    let mut message = tr(id);
+    for (i, arg) in args.iter().enumerate()
+    {
+        message = message.replace(&["{", &i.to_string(), "}"].concat(), arg);
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::{en, ru, tr, tr_args, with_locale, Locale};
+
+    #[test]
+    fn test_accept_language()
+    {
+        assert_eq!(Locale::from_accept_language("en-US,en;q=0.9,ru;q=0.8"), Some(Locale::En));
+        assert_eq!(Locale::from_accept_language("de-DE, ru;q=0.5, en;q=0.3"), Some(Locale::Ru));
+        assert_eq!(Locale::from_accept_language("en;q=0, ru;q=0.1"), Some(Locale::Ru));
+        assert_eq!(Locale::from_accept_language("de, fr"), None);
+        assert_eq!(Locale::parse("en_GB"), Some(Locale::En));
+    }
+
+    #[test]
+    fn test_catalogs_complete()
+    {
+        for (id, _) in ru::MESSAGES
+        {
+            assert!(en::MESSAGES.iter().any(|(k, _)| k == id), "нет перевода `{}`", id);
+        }
+        assert_eq!(ru::MESSAGES.len(), en::MESSAGES.len());
+        //при поиске используется первое совпадение, повторный ключ никогда не будет найден
+        for catalog in [ru::MESSAGES, en::MESSAGES]
+        {
+            for (i, (id, _)) in catalog.iter().enumerate()
+            {
+                assert!(!catalog[..i].iter().any(|(k, _)| k == id), "ключ `{}` повторяется", id);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_translate()
+    {
+        assert_eq!(tr("session_not_found"), "Сессия не найдена");
+        let message = with_locale(Locale::En, async { tr_args("sessions_deleted", &["3"]) }).await;
+        assert_eq!(message, "Sessions removed: `3`");
+        assert_eq!(tr("какой-то текст"), "какой-то текст");
+    }
+}
@@ -0,0 +1,111 @@
+///Каталог сообщений на русском языке, используется также как запасной для отсутствующих переводов
+pub const MESSAGES: &[(&str, &str)] = &[
+    //ошибки, ключ совпадает с `Error::code()`
+    ("invalid_json", "Неверный формат данных: {0}"),
+    ("io_error", "Внутренняя ошибка сервера"),
+    ("database_error", "Ошибка базы данных"),
+    ("unauthorized", "Ошибка авторизации: {0}"),
+    ("session_expired", "Время сессии закончилось, необходимо зайти в систему заново"),
+    ("user_not_found", "Пользователь с такими данными не найден"),
+    ("session_not_found", "Сессия не найдена"),
+    ("verification_code_expired", "Ваш код для верификации был просрочен, попробуйте еще раз"),
+    ("verification_code_wrong", "Неверный код верификации, попробуйте еще раз"),
+    ("verification_not_found", "Запись верификации контакта не обнаружена, попробуйте запросить верификацию повторно"),
+    ("invalid_access_key", "Ключ доступа недействителен"),
+    ("fingerprint_mismatch", "Отпечаток сессии не совпадает, сессия будет удалена, необходимо зайти заново"),
+    ("fingerprint_missing", "Уникальный идетификатор клиента не найден или имеет неверный формат"),
+    ("import_error", "Ошибка импорта: {0}"),
+    ("avatar_error", "Ошибка загрузки аватара: {0}"),
+    ("invalid_image", "Не удалось прочитать изображение"),
+    ("validation_error", "Ошибка проверки данных: {0}"),
+    ("contact_not_found", "Контакт не найден"),
+    ("contact_already_used", "Этот контакт уже подтвержден другим пользователем"),
+    ("ambiguous_login", "Идентификатор `{0}` соответствует нескольким пользователям, войдите по имени пользователя"),
+    ("too_many_requests", "Слишком много запросов, попробуйте позже"),
+    ("feature_disabled", "Функция `{0}` отключена в настройках сервера"),
+    ("migration_error", "Внутренняя ошибка сервера"),
+    ("invalid_configuration", "Ошибка в настройках"),
+    ("invalid_fields", "Ошибка проверки данных"),
+    ("internal_error", "Внутренняя ошибка сервера"),
+    ("route_not_found", "Такого пути нет"),
+    ("missing_host", "В запросе отсуствует заголовок Host"),
+    //ошибки авторизации
+    ("missing_session_cookie", "отсуствует cookie вашей сессии"),
+    ("bearer_invalid", "Bearer не распознан"),
+    ("authorization_header_encoding", "заголовок Authorization имеет ошибки в кодировке"),
+    ("missing_authorization_header", "отсуствует заголовок Authorization"),
+    ("user_inactive", "Пользователь `{0}` не активен"),
+    ("invalid_credentials", "неверное имя пользователя или пароль"),
+    ("login_code_other_device", "Код входа был запрошен с другого устройства"),
+    //проверка данных
+    ("invalid_timezone", "Неизвестный часовой пояс `{0}`"),
+    ("invalid_locale", "Неверный формат языка `{0}`"),
+    ("invalid_session_id", "id сессии `{0}` не валиден"),
+    ("invalid_user_id", "id пользователя `{0}` не валиден"),
+    ("no_verified_contacts", "У пользователя нет подтвержденных контактов для входа без пароля"),
+    ("contact_already_verified", "Контакт `{0}` уже подтвержден"),
+    ("avatar_field_missing", "В запросе отсуствует поле `avatar`"),
+    ("avatar_unsupported_type", "Неподдерживаемый тип файла `{0}`, допустимы: {1}"),
+    ("avatar_too_large", "Размер файла превышает {0} кб"),
+    ("avatar_not_image", "Файл не является изображением"),
+    ("avatar_type_mismatch", "Содержимое файла не соответствует типу `{0}`"),
+    ("rate_limit_client_empty", "Клиент не указан"),
+    ("rate_limit_exempt_not_found", "Клиент `{0}` не найден в списке без ограничений"),
+    ("passwordless_login", "вход без пароля"),
+    ("ws_unknown_topic", "Неизвестный канал `{0}`, ожидается `project:<id>` или `board:<id>`"),
+    ("ws_too_many_subscriptions", "Слишком много подписок, не более {0} на одно соединение"),
+    ("ws_topic_forbidden", "Нет доступа к `{0}`"),
+    ("ws_access_key_expired", "Срок действия ключа доступа истек, обновите ключ и отправьте сообщение `refresh`"),
+    ("job_not_found", "Задача `{0}` не найдена"),
+    ("invalid_contact_id", "Неверный идентификатор контакта `{0}`"),
+    ("wrong_old_password", "Неверный старый пароль, попробуйте еще раз"),
+    ("unknown_contact_type", "Неизвестный тип контакта `{0}`"),
+    ("invalid_email", "Неверный формат e-mail `{0}`"),
+    ("invalid_phone", "Неверный формат телефона `{0}`"),
+    ("invalid_messenger_name", "Неверный формат имени в мессенджере `{0}`"),
+    ("contact_already_added", "Контакт `{0}` уже добавлен"),
+    ("import_unknown_format", "Неизвестный формат `{0}`, допустимые форматы: csv, json"),
+    ("import_row_error", "Ошибка разбора строки {0}: {1}"),
+    ("import_username_missing", "Не указано имя пользователя"),
+    ("import_username_repeated", "Имя пользователя `{0}` повторяется в файле импорта"),
+    ("username_busy", "Имя пользователя `{0}` уже занято"),
+    ("unknown_role", "Неизвестная роль `{0}`"),
+    ("password_too_short", "Пароль должен быть не короче {0} символов"),
+    ("schedule_invalid_interval", "неверный интервал `{0}`, ожидается число с единицей s, m, h или d"),
+    ("schedule_fields_count", "расписание `{0}` должно состоять из пяти полей: минута час день месяц день_недели"),
+    ("schedule_invalid_field", "неверное поле расписания `{0}`, допустимы значения от {1} до {2}"),
+    //настройки
+    ("config_invalid_value", "неверное значение `{0}`"),
+    ("config_must_be_positive", "значение должно быть больше 0"),
+    ("config_invalid_synchronous", "`{0}` не является режимом синхронизации, допустимы: off, normal, full, extra"),
+    ("config_no_origins", "не указан ни один источник"),
+    ("config_invalid_origin", "`{0}` не является источником вида `http(s)://host[:port]`"),
+    ("config_invalid_url", "`{0}` не является адресом http(s)"),
+    ("config_invalid_header_name", "`{0}` не является именем заголовка"),
+    ("config_invalid_cookie_name", "`{0}` не является именем cookie"),
+    ("config_invalid_ip", "`{0}` не является ip адресом"),
+    ("config_path_missing", "путь не указан"),
+    ("config_invalid_log_level", "`{0}` не является уровнем логирования"),
+    ("config_unknown_rate_limit_group", "неизвестная группа маршрутов, допустимы: {0}"),
+    ("config_rate_limit_positive", "`capacity` и `refill_per_minute` должны быть больше 0"),
+    ("config_tls_paths", "для tls должны быть указаны и `tls_cert_path`, и `tls_key_path`"),
+    ("config_redirect_without_tls", "перенаправление на https возможно только при включенном tls"),
+    ("config_must_differ", "должен отличаться от `{0}`"),
+    ("config_restart_required", "изменение вступит в силу только после перезапуска сервера, настройки не применены"),
+    //успешные операции
+    ("password_changed", "Пароль успешно изменен"),
+    ("data_updated", "Данные успешно обновлены"),
+    ("session_exited", "Вы успешно вышли из сессии {0}"),
+    ("sessions_deleted", "Сессий успешно удалено: `{0}`"),
+    ("avatar_deleted", "Аватар удален"),
+    ("avatar_not_found", "Аватар не найден"),
+    ("contact_deleted", "Контакт удален"),
+    ("primary_contact_changed", "Основной контакт изменен"),
+    ("verification_code_sent", "Код подтверждения отправлен"),
+    ("contact_verified", "Контакт подтвержден"),
+    ("admin_section", "вы зашли в админский роут"),
+    //уведомления
+    ("login_code_notification", "Код для входа: {0}, действителен {1} мин. Или перейдите по ссылке: {2}"),
+    ("verification_code_notification", "Код подтверждения контакта: {0}"),
+    ("account_created_notification", "Для вас создана учетная запись `{0}`, пароль для входа: {1}"),
+];
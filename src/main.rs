@@ -2,6 +2,8 @@ mod auth_route;
 mod roles;
 mod configuration;
 pub use roles::Role;
+mod contact_type;
+pub use contact_type::ContactType;
 mod api;
 mod middleware;
 mod state;
@@ -9,6 +11,18 @@ mod error;
 mod services;
 pub use error::Error;
 mod db;
-fn main() {
-    println!("Hello, world!");
+mod cli;
+mod i18n;
+use clap::Parser;
+
+#[tokio::main]
+async fn main()
+{
+    logger::StructLogger::new_default();
+    if let Err(e) = cli::run(cli::Cli::parse()).await
+    {
+        logger::error!("{}", e.to_string());
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
@@ -4,15 +4,18 @@ use axum::body::Body;
 use axum::response::IntoResponse;
 use hyper::header::{AUTHORIZATION, COOKIE};
 use hyper::HeaderMap;
-use jwt_authentification::{Cookie, CookieJar, Duration as CookieMaxLife};
+use jwt_authentification::{Cookie, CookieJar};
 use tower::{Service, Layer};
-use axum::http::{Extensions, Request, Response, StatusCode};
+use axum::http::{Extensions, Request, Response};
 use futures::future::BoxFuture;
 use futures::FutureExt;
 use crate::configuration::Configuration;
-use crate::db::{ISessionRepository, Session};
+use crate::error::cookie_remove_error_response;
+use crate::{Error, Role};
+use crate::db::{ISessionRepository, IUserRepository, Session};
+use crate::i18n::{current_locale, with_locale, Locale};
 use crate::state::AppState;
-use super::SessionExtension;
+use super::{record_user, RequestUser, SessionExtension};
 #[derive(Copy, Clone)]
 pub enum AuthCheck
 {
@@ -93,15 +96,18 @@ where
                         {
                             session: Arc::new(session.unwrap()),
                             fingerprint: Arc::new(fingerprint),
-                            role: Arc::new(None)
+                            role: Arc::new(None),
+                            token_verified: false
                         };
+                        record_session(&session_extension);
+                        let locale = user_locale(&state, &session_extension.session.user_id).await;
                         let ext = req.extensions_mut();
                         ext.insert(session_extension);
-                        inner.call(req).await
+                        with_locale(locale, inner.call(req)).await
                     }
                     else
                     {
-                        let bearer = bearer_checker(headers, session.as_ref().unwrap(), state, roles, audience).await;
+                        let bearer = bearer_checker(headers, session.as_ref().unwrap(), state.clone(), roles, audience).await;
                         if let Err(e) = bearer
                         {
                             Ok(e)
@@ -114,11 +120,14 @@ where
                             {
                                 session: Arc::new(session.unwrap()),
                                 fingerprint: Arc::new(fingerprint),
-                                role: Arc::new(role)
+                                role: Arc::new(role),
+                                token_verified: true
                             };
+                            record_session(&session_extension);
+                            let locale = user_locale(&state, &session_extension.session.user_id).await;
                             let ext = req.extensions_mut();
                             ext.insert(session_extension);
-                            inner.call(req).await
+                            with_locale(locale, inner.call(req)).await
                         }
                     }
                 }
@@ -128,38 +137,42 @@ where
     }
 }
 
-fn error_response<T: ToString>(body: T) -> Response<axum::body::Body>
+///Пользователь, сессия и роль записываются в span запроса, чтобы события обработчика были связаны с пользователем
+fn record_session(extension: &SessionExtension)
 {
-    let err = body.to_string();
-    logger::error!("{}", &err);
-    let body = axum::body::Body::new(err);
-    Response::builder()
-    .status(StatusCode::UNAUTHORIZED)
-    .body(body)
-    .unwrap()
+    record_user(RequestUser
+    {
+        user_id: extension.session.user_id,
+        session_id: extension.session.session_id,
+        role: extension.role.as_deref().and_then(Role::try_parse)
+    });
+}
+
+///Язык из профиля пользователя имеет приоритет над `Accept-Language`
+async fn user_locale(state: &AppState, user_id: &uuid::Uuid) -> Locale
+{
+    state.services.database_service.user_repository.get_locale(user_id).await
+        .ok()
+        .flatten()
+        .and_then(|l| Locale::parse(&l))
+        .unwrap_or_else(current_locale)
+}
+
+fn error_response(error: Error) -> Response<axum::body::Body>
+{
+    logger::error!("{}", error.to_string());
+    error.into_response()
 }
 ///возврат ошибки клиенту с удалением cookie рефреш ключа (сессии)
-pub fn cookie_error_response<T: ToString>(body: T, cfg: &Configuration) -> Response<axum::body::Body>
+pub fn cookie_error_response(error: Error, cfg: &Configuration) -> Response<axum::body::Body>
 {
-    let err = body.to_string();
-    logger::error!("{}", &err);
-    let body = axum::body::Body::new(err);
-    let cookie: Cookie = Cookie::build((&cfg.session_cookie_name, "")).path("/").max_age(CookieMaxLife::seconds(0)).into();
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        axum::http::header::SET_COOKIE,
-        cookie.to_string().parse().unwrap(),
-    );
-    let mut resp = Response::builder()
-    .status(StatusCode::UNAUTHORIZED)
-    .body(body)
-    .unwrap();
-    *resp.headers_mut() = headers;
-    resp
+    logger::error!("{}", error.to_string());
+    cookie_remove_error_response(&error, &cfg.session_cookie_name)
 }
 
 async fn cookie_checker(headers: &HeaderMap, state: Arc<AppState>) -> Result<Session, Response<Body>>
 {
+    let cfg = state.configuration.get();
     if let Some(cookie_header) = headers.get(COOKIE)
     {
         let mut cookie_jar = CookieJar::new();
@@ -175,7 +188,7 @@ async fn cookie_checker(headers: &HeaderMap, state: Arc<AppState>) -> Result<Ses
             }
         }
         
-        if let Some(cookie) = cookie_jar.get(&state.configuration.session_cookie_name)
+        if let Some(cookie) = cookie_jar.get(&cfg.session_cookie_name)
         {
             let session = state.services.database_service.session_repository.get_session(&cookie.value().parse().unwrap()).await;
             if let Ok(session) = session
@@ -186,7 +199,7 @@ async fn cookie_checker(headers: &HeaderMap, state: Arc<AppState>) -> Result<Ses
                 }
                 else
                 {
-                    let response = cookie_error_response("Время вашей сессии истекло, необходимо зайти в систему заново", &state.configuration);
+                    let response = cookie_error_response(Error::SessionExpired, &cfg);
                     Err(response)
                 }
             }
@@ -201,13 +214,13 @@ async fn cookie_checker(headers: &HeaderMap, state: Arc<AppState>) -> Result<Ses
         }
         else
         {
-            let response = cookie_error_response("Ошибка авторизации, отсуствует cookie вашей сессии", &state.configuration);
+            let response = cookie_error_response(Error::AuthError("missing_session_cookie".to_owned()), &cfg);
             Err(response)
         }
     }
     else
     {
-        let response = cookie_error_response("Ошибка авторизации, отсуствует cookie вашей сессии", &state.configuration);
+        let response = cookie_error_response(Error::AuthError("missing_session_cookie".to_owned()), &cfg);
         Err(response)
     }
 }
@@ -221,7 +234,7 @@ async fn bearer_checker(headers: &HeaderMap, session: &Session, state: Arc<AppSt
         {
             if token_str.len() < 10
             {
-                let response = error_response("Bearer не распознан");
+                let response = error_response(Error::AuthError("bearer_invalid".to_owned()));
                 Err(response)
             }
             else
@@ -242,20 +255,20 @@ async fn bearer_checker(headers: &HeaderMap, session: &Session, state: Arc<AppSt
         }
         else
         {
-            let response = error_response("Ошибка авторизации, заголовок Authorization имеет ошибки в кодировке");
+            let response = error_response(Error::AuthError("authorization_header_encoding".to_owned()));
             Err(response)
         }
     }
     else
     {
-        let response = error_response("Ошибка авторизации, отсуствует заголовок Authorization");
+        let response = error_response(Error::AuthError("missing_authorization_header".to_owned()));
         Err(response)
     }
 }
 
 async fn fingerprint_checker<'a >(headers: &'a HeaderMap, state: Arc<AppState>) -> Result<&'a str, Response<Body>>
 {
-    if let Some(authorization) = headers.get(&state.configuration.fingerprint_header_name)
+    if let Some(authorization) = headers.get(&state.configuration.get().fingerprint_header_name)
     {
         //get key after Bearer 
         if let Ok(fingerprint) = authorization.to_str()
@@ -264,13 +277,13 @@ async fn fingerprint_checker<'a >(headers: &'a HeaderMap, state: Arc<AppState>)
         }
         else
         {
-            let response = error_response(["Ошибка, заголовок ", &state.configuration.fingerprint_header_name, " имеет ошибки в кодировке"].concat());
+            let response = error_response(Error::FingerprintNotFound);
             Err(response)
         }
     }
     else
     {
-        let response = error_response("Ошибка, отсуствует уникальный отпечаток сессии");
+        let response = error_response(Error::FingerprintNotFound);
         Err(response)
     }
 }
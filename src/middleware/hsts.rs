@@ -0,0 +1,18 @@
+use std::sync::Arc;
+use axum::{extract::{Request, State}, http::{header::STRICT_TRANSPORT_SECURITY, HeaderValue}, middleware::Next, response::Response};
+use crate::state::AppState;
+
+///Заголовок `Strict-Transport-Security` для ответов по https, `max-age` берется из `hsts_max_age`
+pub async fn hsts_middleware(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response
+{
+    let mut response = next.run(request).await;
+    let cfg = state.configuration.get();
+    if cfg.tls_enabled() && cfg.hsts_max_age > 0
+    {
+        if let Ok(value) = HeaderValue::from_str(&["max-age=", &cfg.hsts_max_age.to_string(), "; includeSubDomains"].concat())
+        {
+            response.headers_mut().insert(STRICT_TRANSPORT_SECURITY, value);
+        }
+    }
+    response
+}
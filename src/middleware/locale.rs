@@ -0,0 +1,13 @@
+use axum::{extract::Request, http::header::ACCEPT_LANGUAGE, middleware::Next, response::Response};
+use crate::i18n::{with_locale, Locale};
+
+///Язык сообщений выбирается по заголовку `Accept-Language`,
+/// для авторизованных запросов его переопределяет язык из профиля пользователя (см. `AuthMiddleware`)
+pub async fn locale_middleware(request: Request, next: Next) -> Response
+{
+    let locale = request.headers().get(ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Locale::from_accept_language)
+        .unwrap_or_default();
+    with_locale(locale, next.run(request)).await
+}
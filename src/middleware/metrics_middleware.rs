@@ -0,0 +1,50 @@
+use std::{sync::Arc, time::Instant};
+use axum::{extract::{MatchedPath, Request, State}, http::Method, middleware::Next, response::Response};
+use crate::state::AppState;
+
+///Учет количества и времени выполнения запросов, маршрут берется из шаблона пути
+pub async fn metrics_middleware(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response
+{
+    let start = Instant::now();
+    let method = method_label(request.method());
+    let route = request.extensions().get::<MatchedPath>()
+        .map(|p| p.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let response = next.run(request).await;
+    state.services.metrics_service.observe_request(method, &route, response.status().as_u16(), start.elapsed());
+    response
+}
+
+///Метод запроса задает клиент, нестандартные методы объединяются в `OTHER`, чтобы число меток было ограничено
+fn method_label(method: &Method) -> &'static str
+{
+    match *method
+    {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => "OTHER"
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use axum::http::Method;
+    use super::method_label;
+
+    #[test]
+    fn test_method_label()
+    {
+        assert_eq!(method_label(&Method::GET), "GET");
+        assert_eq!(method_label(&Method::PATCH), "PATCH");
+        assert_eq!(method_label(&Method::from_bytes(b"PROPFIND").unwrap()), "OTHER");
+        assert_eq!(method_label(&Method::from_bytes(b"RANDOM-123").unwrap()), "OTHER");
+    }
+}
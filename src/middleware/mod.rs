@@ -3,5 +3,17 @@ mod session_wrapper;
 pub use session_wrapper::{ResponseSessionWrapper, SessionExtension, FingerprintExtractor};
 pub use auth_middleware::{AuthLayer, AuthCheck};
 mod cookie_middleware;
+mod metrics_middleware;
+pub use metrics_middleware::metrics_middleware;
+mod request_id;
+pub use request_id::{request_id_middleware, current_request_id, current_user, record_user, RequestUser, REQUEST_ID_HEADER};
+mod trace;
+pub use trace::{RequestSpan, ResponseLog};
+mod locale;
+mod rate_limit;
+mod hsts;
+pub use hsts::hsts_middleware;
+pub use rate_limit::RateLimitLayer;
+pub use locale::locale_middleware;
 
 //pub use cookie_middleware::{CookieLayer, Cookies, CookiesExtractor};
\ No newline at end of file
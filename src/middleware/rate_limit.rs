@@ -0,0 +1,144 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Request, Response};
+use axum::response::IntoResponse;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hyper::header::{AUTHORIZATION, RETRY_AFTER};
+use tower::{Layer, Service};
+use crate::configuration::RateLimitKey;
+use crate::services::RateLimitDecision;
+use crate::state::AppState;
+use crate::Error;
+use super::SessionExtension;
+
+const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Слой ограничения частоты запросов для группы маршрутов, лимит группы берется из `Configuration::rate_limits`.
+/// Для ограничения по пользователю или сессии слой должен стоять после `AuthLayer`:
+/// `post(handler).route_layer(RateLimitLayer::new("api", state)).route_layer(AuthLayer::...)`
+#[derive(Clone)]
+pub struct RateLimitLayer
+{
+    state: Arc<AppState>,
+    group: &'static str
+}
+impl RateLimitLayer
+{
+    pub fn new(group: &'static str, state: Arc<AppState>) -> Self
+    {
+        Self
+        {
+            state,
+            group
+        }
+    }
+}
+impl<S> Layer<S> for RateLimitLayer
+where
+    S: Service<Request<axum::body::Body>, Response = Response<axum::body::Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Service = RateLimitMiddleware<S>;
+    fn layer(&self, inner: S) -> Self::Service
+    {
+        RateLimitMiddleware
+        {
+            inner,
+            state: self.state.clone(),
+            group: self.group
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitMiddleware<S>
+{
+    inner: S,
+    state: Arc<AppState>,
+    group: &'static str
+}
+
+impl<S> Service<Request<axum::body::Body>> for RateLimitMiddleware<S>
+where
+    S: Service<Request<axum::body::Body>, Response = Response<axum::body::Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<axum::body::Body>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>
+    {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<axum::body::Body>) -> Self::Future
+    {
+        let state = self.state.clone();
+        let group = self.group;
+        let mut inner = self.inner.clone();
+        async move
+        {
+            let cfg = state.configuration.get();
+            let Some(limit) = cfg.rate_limits.get(group) else
+            {
+                return inner.call(req).await;
+            };
+            let ip = req.extensions().get::<ConnectInfo<SocketAddr>>()
+                .map(|c| c.0.ip().to_string())
+                .unwrap_or_else(|| "unknown".to_owned());
+            let session = req.extensions().get::<SessionExtension>();
+            let user_id = session.map(|s| s.session.user_id.to_string());
+            let session_id = session.map(|s| s.session.session_id.to_string());
+            let exempt_ids: Vec<&str> = [Some(ip.as_str()), user_id.as_deref(), session_id.as_deref()].into_iter().flatten().collect();
+            if state.services.rate_limit_service.is_exempt(&cfg, &exempt_ids)
+            {
+                return inner.call(req).await;
+            }
+            let client = match limit.key
+            {
+                RateLimitKey::User => user_id.map(|id| ["user:", &id].concat()),
+                RateLimitKey::Session => session_id.map(|id| ["session:", &id].concat()),
+                RateLimitKey::Token => session.filter(|s| s.token_verified).and_then(|_| token_key(req.headers())),
+                RateLimitKey::Ip => None
+            }.unwrap_or_else(|| ["ip:", &ip].concat());
+            let decision = state.services.rate_limit_service.check(group, &client, limit);
+            if !decision.allowed
+            {
+                logger::warn!("Превышено ограничение частоты запросов группы `{}` для `{}`", group, &client);
+                let mut response = Error::TooManyRequests.into_response();
+                insert_headers(response.headers_mut(), &decision);
+                response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(decision.retry_after));
+                return Ok(response);
+            }
+            let mut response = inner.call(req).await?;
+            insert_headers(response.headers_mut(), &decision);
+            Ok(response)
+        }
+        .boxed()
+    }
+}
+
+///Ключ доступа не хранится в памяти в открытом виде.
+/// Вызывается только для ключа, проверенного `AuthLayer`: иначе случайный ключ в каждом запросе давал бы новый полный лимит
+fn token_key(headers: &HeaderMap) -> Option<String>
+{
+    let token = headers.get(AUTHORIZATION)?.to_str().ok()?.strip_prefix("Bearer ")?.trim();
+    if token.is_empty()
+    {
+        return None;
+    }
+    Some(["token:", &utilites::Hasher::hash_from_strings([token])].concat())
+}
+
+fn insert_headers(headers: &mut HeaderMap, decision: &RateLimitDecision)
+{
+    headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(RATE_LIMIT_RESET, HeaderValue::from(decision.reset));
+}
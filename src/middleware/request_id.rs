@@ -0,0 +1,81 @@
+use std::sync::OnceLock;
+use axum::{extract::Request, http::{HeaderName, HeaderValue}, middleware::Next, response::Response};
+use crate::Role;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+///Авторизованный пользователь текущего запроса
+#[derive(Debug, Clone)]
+pub struct RequestUser
+{
+    pub user_id: uuid::Uuid,
+    pub session_id: uuid::Uuid,
+    ///`None` если маршрут проверяет только сессию
+    pub role: Option<Role>
+}
+
+struct RequestContext
+{
+    id: String,
+    ///заполняется `AuthMiddleware` после проверки сессии
+    user: OnceLock<RequestUser>
+}
+
+tokio::task_local!
+{
+    static REQUEST: RequestContext;
+}
+
+///Идентификатор текущего запроса, `None` вне обработки запроса
+pub fn current_request_id() -> Option<String>
+{
+    REQUEST.try_with(|r| r.id.clone()).ok()
+}
+
+///Пользователь текущего запроса, `None` вне обработки запроса и для маршрутов без авторизации
+pub fn current_user() -> Option<RequestUser>
+{
+    REQUEST.try_with(|r| r.user.get().cloned()).ok().flatten()
+}
+
+///Пользователь сохраняется в контексте запроса и записывается в поля `user_id`, `session_id`, `role` span запроса,
+/// после этого все события внутри обработчика связаны с пользователем
+pub fn record_user(user: RequestUser)
+{
+    let span = tracing::Span::current();
+    span.record("user_id", tracing::field::display(&user.user_id));
+    span.record("session_id", tracing::field::display(&user.session_id));
+    if let Some(role) = user.role.as_ref()
+    {
+        span.record("role", tracing::field::display(role));
+    }
+    let _ = REQUEST.try_with(|r| r.user.set(user));
+}
+
+///Идентификатор берется из заголовка `x-request-id` или генерируется,
+/// доступен обработчикам через [`current_request_id`] и возвращается в заголовке ответа.
+/// Сгенерированный идентификатор добавляется в заголовки запроса, чтобы его видели следующие слои
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response
+{
+    let id = request.headers().get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(|v| v.to_owned())
+        .unwrap_or_else(|| uuid::Uuid::now_v7().to_string());
+    let value = HeaderValue::from_str(&id).ok();
+    if let Some(value) = value.as_ref()
+    {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value.clone());
+    }
+    let context = RequestContext
+    {
+        id,
+        user: OnceLock::new()
+    };
+    let mut response = REQUEST.scope(context, next.run(request)).await;
+    if let Some(value) = value
+    {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
@@ -28,6 +28,7 @@ impl ResponseSessionWrapper
         let cookie: Cookie = Cookie::build((&self.cfg.session_cookie_name, self.session.session_id.to_string()))
         .max_age(Duration::days(self.cfg.session_life_time as i64))
         .path("/")
+        .secure(self.cfg.tls_enabled())
         .into();
         cookie.to_string()
     }
@@ -45,7 +46,7 @@ where
     {
         // Извлекаем заголовки из запроса
         let app_state = Arc::<AppState>::from_ref(state);
-        if let Some(fingerprint) = parts.headers.get(&app_state.configuration.fingerprint_header_name)
+        if let Some(fingerprint) = parts.headers.get(&app_state.configuration.get().fingerprint_header_name)
         {
             if let Ok(f) = fingerprint.to_str()
             {
@@ -82,7 +83,9 @@ pub struct SessionExtension
 {
     pub session: Arc<Session>,
     pub fingerprint: Arc<String>,
-    pub role: Arc<Option<String>>
+    pub role: Arc<Option<String>>,
+    ///ключ доступа из заголовка `Authorization` проверен (`AuthCheck::All`)
+    pub token_verified: bool
 }
 impl<S> FromRequestParts<S> for SessionExtension
 where
@@ -97,7 +100,7 @@ where
         }
         else 
         {
-            Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Ошибка экстрактора").into_response())
+            Err(Error::Internal("Ошибка экстрактора".to_owned()).into_response())
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+use std::{sync::Arc, time::Duration};
+use axum::http::{header::{AUTHORIZATION, COOKIE, SET_COOKIE}, HeaderMap, HeaderName, Request, Response};
+use tower_http::trace::{MakeSpan, OnResponse};
+use tracing::{field::Empty, Span};
+use crate::configuration::Configuration;
+use super::{request_id::{current_request_id, current_user}, REQUEST_ID_HEADER};
+
+const REDACTED: &str = "<redacted>";
+
+///Span запроса для `TraceLayer`: метод, путь, идентификатор запроса и заголовки без секретов.
+/// Поля `user_id`, `session_id` и `role` заполняет `AuthMiddleware` после проверки сессии
+#[derive(Clone)]
+pub struct RequestSpan
+{
+    ///значения этих заголовков не попадают в журнал: ключи доступа, cookie сессии и отпечаток клиента
+    redacted: Arc<[HeaderName]>
+}
+impl RequestSpan
+{
+    pub fn new(cfg: &Configuration) -> Self
+    {
+        let mut redacted = vec![AUTHORIZATION, COOKIE, SET_COOKIE];
+        if let Ok(fingerprint) = cfg.fingerprint_header_name.parse::<HeaderName>()
+        {
+            redacted.push(fingerprint);
+        }
+        Self
+        {
+            redacted: redacted.into()
+        }
+    }
+}
+impl<B> MakeSpan<B> for RequestSpan
+{
+    fn make_span(&mut self, request: &Request<B>) -> Span
+    {
+        let request_id = request.headers().get(&REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned())
+            .or_else(current_request_id)
+            .unwrap_or_default();
+        tracing::info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            request_id = %request_id,
+            headers = ?redact_headers(request.headers(), &self.redacted),
+            user_id = Empty,
+            session_id = Empty,
+            role = Empty
+        )
+    }
+}
+
+fn redact_headers(headers: &HeaderMap, redacted: &[HeaderName]) -> Vec<(String, String)>
+{
+    headers.iter().map(|(name, value)|
+    {
+        let value = if redacted.contains(name)
+        {
+            REDACTED.to_owned()
+        }
+        else
+        {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        (name.as_str().to_owned(), value)
+    }).collect()
+}
+
+///Строка журнала о завершении запроса с идентификатором запроса и пользователем,
+/// по идентификатору из заголовка `x-request-id` ответа находятся все записи запроса
+#[derive(Clone, Copy)]
+pub struct ResponseLog;
+impl<B> OnResponse<B> for ResponseLog
+{
+    fn on_response(self, response: &Response<B>, latency: Duration, _span: &Span)
+    {
+        let request_id = current_request_id().unwrap_or_default();
+        match current_user()
+        {
+            Some(user) => logger::debug!("Запрос `{}` завершен со статусом {} за {} мс, пользователь `{}`, сессия `{}`",
+                request_id, response.status().as_u16(), latency.as_millis(), user.user_id, user.session_id),
+            None => logger::debug!("Запрос `{}` завершен со статусом {} за {} мс",
+                request_id, response.status().as_u16(), latency.as_millis())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use axum::http::{header::{AUTHORIZATION, COOKIE, USER_AGENT}, HeaderMap, HeaderName, HeaderValue};
+    use super::redact_headers;
+
+    #[test]
+    fn test_redact_headers()
+    {
+        let fingerprint = HeaderName::from_static("x-unique");
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        headers.insert(COOKIE, HeaderValue::from_static("session-key=secret"));
+        headers.insert(fingerprint.clone(), HeaderValue::from_static("secret"));
+        headers.insert(USER_AGENT, HeaderValue::from_static("test"));
+        let redacted = redact_headers(&headers, &[AUTHORIZATION, COOKIE, fingerprint]);
+        assert_eq!(redacted.len(), 4);
+        assert!(redacted.iter().all(|(_, v)| !v.contains("secret")));
+        assert!(redacted.contains(&("user-agent".to_owned(), "test".to_owned())));
+    }
+}
@@ -1,7 +1,8 @@
 use std::{convert::Infallible, fmt::Display, str::FromStr};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
 pub enum Role
 {
     Administrator,
@@ -20,6 +21,20 @@ impl Display for Role
         }
     }
 }
+impl Role
+{
+    ///Строгий разбор роли, в отличии от `FromStr` неизвестная роль вернет `None`
+    pub fn try_parse(s: &str) -> Option<Self>
+    {
+        match s
+        {
+            "Administrator" => Some(Role::Administrator),
+            "User" => Some(Role::User),
+            "NonPrivileged" => Some(Role::NonPrivileged),
+            _ => None
+        }
+    }
+}
 impl FromStr for Role
 {
     type Err = Infallible;
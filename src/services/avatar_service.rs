@@ -0,0 +1,165 @@
+use std::{io::Cursor, path::PathBuf, sync::Arc};
+use image::{imageops::FilterType, ImageFormat};
+use crate::{configuration::Configuration, db::DatabaseService, i18n::tr_args, Error};
+
+///Размеры (сторона квадрата в пикселях) в которых сохраняется каждый аватар
+pub const AVATAR_SIZES: [u32; 4] = [32, 64, 128, 256];
+pub const DEFAULT_AVATAR_SIZE: u32 = 128;
+const ALLOWED_CONTENT_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/webp", "image/gif"];
+
+///Адрес аватара для клиента, версия нужна чтобы браузер не показывал старый аватар из кэша
+pub fn avatar_url(user_id: &uuid::Uuid, version: &str) -> String
+{
+    ["/api/users/avatar/", &user_id.to_string(), "?v=", version].concat()
+}
+
+#[derive(Clone)]
+pub struct AvatarService
+{
+    database_service: Arc<DatabaseService>,
+    ///используются только параметры, требующие перезапуска, поэтому достаточно снимка настроек
+    configuration: Arc<Configuration>
+}
+impl AvatarService
+{
+    pub fn new(database_service: Arc<DatabaseService>, configuration: Arc<Configuration>) -> Self
+    {
+        Self
+        {
+            database_service,
+            configuration
+        }
+    }
+    fn user_directory(&self, user_id: &uuid::Uuid) -> PathBuf
+    {
+        PathBuf::from(&self.configuration.avatars_directory).join(user_id.to_string())
+    }
+    ///Путь к файлу аватара нужного размера, если размер не поддерживается берется ближайший больший
+    pub fn avatar_path(&self, user_id: &uuid::Uuid, size: Option<u32>) -> PathBuf
+    {
+        let size = size.unwrap_or(DEFAULT_AVATAR_SIZE);
+        let size = AVATAR_SIZES.iter().find(|s| **s >= size).unwrap_or(AVATAR_SIZES.last().unwrap());
+        self.user_directory(user_id).join([&size.to_string(), ".png"].concat())
+    }
+    ///Сохранение нового аватара, возвращает url аватара
+    pub async fn upload(&self, user_id: &uuid::Uuid, content_type: Option<&str>, data: Vec<u8>) -> Result<String, Error>
+    {
+        let content_type = content_type.unwrap_or_default();
+        if !ALLOWED_CONTENT_TYPES.contains(&content_type)
+        {
+            return Err(Error::AvatarError(tr_args("avatar_unsupported_type", &[content_type, &ALLOWED_CONTENT_TYPES.join(", ")])));
+        }
+        if data.len() > self.configuration.avatar_max_size_kb as usize * 1024
+        {
+            return Err(Error::AvatarError(tr_args("avatar_too_large", &[&self.configuration.avatar_max_size_kb.to_string()])));
+        }
+        //тип из заголовка может не совпадать с содержимым, проверяем по сигнатуре файла
+        let format = image::guess_format(&data).map_err(|_| Error::AvatarError("avatar_not_image".to_owned()))?;
+        if ImageFormat::from_mime_type(content_type) != Some(format)
+        {
+            return Err(Error::AvatarError(tr_args("avatar_type_mismatch", &[content_type])));
+        }
+        let resized = tokio::task::spawn_blocking(move || resize(&data, format))
+        .await
+        .map_err(|e| Error::AvatarError(e.to_string()))??;
+        let directory = self.user_directory(user_id);
+        tokio::fs::create_dir_all(&directory).await?;
+        let version = uuid::Uuid::now_v7().simple().to_string();
+        //файлы пишутся под временными именами и заменяют текущие только после обновления записи пользователя
+        let mut files = Vec::with_capacity(resized.len());
+        for (size, bytes) in resized
+        {
+            let temp = directory.join([&size.to_string(), ".", &version, ".tmp"].concat());
+            files.push((temp.clone(), self.avatar_path(user_id, Some(size))));
+            if let Err(e) = tokio::fs::write(&temp, bytes).await
+            {
+                remove_files(&files).await;
+                return Err(e.into());
+            }
+        }
+        if let Err(e) = self.database_service.user_repository.update_avatar(user_id, Some(&version)).await
+        {
+            remove_files(&files).await;
+            return Err(e);
+        }
+        for (temp, path) in &files
+        {
+            tokio::fs::rename(temp, path).await?;
+        }
+        logger::info!("Загружен новый аватар для `{}`", user_id.to_string());
+        Ok(avatar_url(user_id, &version))
+    }
+    pub async fn delete(&self, user_id: &uuid::Uuid) -> Result<(), Error>
+    {
+        self.database_service.user_repository.update_avatar(user_id, None).await?;
+        let directory = self.user_directory(user_id);
+        if tokio::fs::try_exists(&directory).await?
+        {
+            tokio::fs::remove_dir_all(&directory).await?;
+        }
+        logger::info!("Аватар пользователя `{}` удален", user_id.to_string());
+        Ok(())
+    }
+}
+
+///Удаление временных файлов незавершенной загрузки
+async fn remove_files(files: &[(PathBuf, PathBuf)])
+{
+    for (temp, _) in files
+    {
+        let _ = tokio::fs::remove_file(temp).await;
+    }
+}
+
+fn resize(data: &[u8], format: ImageFormat) -> Result<Vec<(u32, Vec<u8>)>, Error>
+{
+    let img = image::load_from_memory_with_format(data, format)?;
+    let mut result = Vec::with_capacity(AVATAR_SIZES.len());
+    for size in AVATAR_SIZES
+    {
+        let resized = img.resize_to_fill(size, size, FilterType::Lanczos3);
+        let mut bytes = Vec::new();
+        resized.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+        result.push((size, bytes));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{io::Cursor, sync::Arc};
+    use image::{ImageFormat, RgbImage};
+    use crate::{configuration::Configuration, db::DatabaseService, Error};
+    use super::AvatarService;
+
+    #[test]
+    fn test_resize()
+    {
+        let img = RgbImage::new(300, 200);
+        let mut bytes = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+        let resized = super::resize(&bytes, ImageFormat::Png).unwrap();
+        assert_eq!(resized.len(), super::AVATAR_SIZES.len());
+        let small = image::load_from_memory(&resized[0].1).unwrap();
+        assert_eq!((small.width(), small.height()), (32, 32));
+    }
+
+    #[tokio::test]
+    async fn test_upload_unknown_user()
+    {
+        let directory = std::env::temp_dir().join(["planner_avatars_", &uuid::Uuid::now_v7().to_string()].concat());
+        let mut cfg = Configuration::default();
+        cfg.avatars_directory = directory.to_string_lossy().into_owned();
+        let database_service = Arc::new(DatabaseService::in_memory(&cfg).await.unwrap());
+        let service = AvatarService::new(database_service, Arc::new(cfg));
+        let mut bytes = Vec::new();
+        RgbImage::new(64, 64).write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+        let user_id = uuid::Uuid::now_v7();
+        assert!(matches!(service.upload(&user_id, Some("image/png"), bytes).await, Err(Error::UserNotFound)));
+        //запись пользователя не обновлена - файлы не остаются ни под временными, ни под постоянными именами
+        let mut entries = tokio::fs::read_dir(directory.join(user_id.to_string())).await.unwrap();
+        assert!(entries.next_entry().await.unwrap().is_none());
+        let _ = tokio::fs::remove_dir_all(&directory).await;
+    }
+}
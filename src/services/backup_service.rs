@@ -0,0 +1,199 @@
+use std::{path::{Path, PathBuf}, sync::Arc};
+use serde::Serialize;
+use utilites::Date;
+use crate::{configuration::ConfigurationHandle, db::DatabaseService, Error};
+
+const BACKUP_EXTENSION: &str = ".sq3";
+
+///Созданная резервная копия
+#[derive(Debug, Serialize, Clone, utoipa::ToSchema)]
+pub struct BackupFile
+{
+    ///имя базы данных: `planner` или `sessions`
+    pub database: String,
+    pub path: String,
+    ///размер в байтах
+    pub size: u64
+}
+
+///Резервное копирование баз данных без остановки сервера через `VACUUM INTO`.
+/// Копии сохраняются в `backup_directory` с именем `<база>-<время>.sq3`, для каждой базы хранится `backup_retention` последних копий
+#[derive(Clone)]
+pub struct BackupService
+{
+    database_service: Arc<DatabaseService>,
+    configuration: ConfigurationHandle,
+    ///одновременно выполняется одно копирование
+    running: Arc<tokio::sync::Mutex<()>>
+}
+impl BackupService
+{
+    pub fn new(database_service: Arc<DatabaseService>, configuration: ConfigurationHandle) -> Self
+    {
+        Self
+        {
+            database_service,
+            configuration,
+            running: Arc::new(tokio::sync::Mutex::new(()))
+        }
+    }
+    ///Резервная копия всех баз данных, после копирования удаляются копии сверх `backup_retention`
+    pub async fn backup(&self) -> Result<Vec<BackupFile>, Error>
+    {
+        let _running = self.running.lock().await;
+        let cfg = self.configuration.get();
+        let directory = Path::new(&cfg.backup_directory);
+        std::fs::create_dir_all(directory)?;
+        let stamp: String = Date::now().format(utilites::DateFormat::Serialize).chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        let mut files = Vec::new();
+        for database in self.database_service.databases()
+        {
+            let target = backup_path(directory, database, &stamp);
+            self.database_service.vacuum_into(database, &target).await?;
+            let size = std::fs::metadata(&target)?.len();
+            logger::info!("Создана резервная копия базы данных `{}`: {}", database, target.display());
+            files.push(BackupFile
+            {
+                database: database.to_owned(),
+                path: target.to_string_lossy().into_owned(),
+                size
+            });
+            prune(directory, database, cfg.backup_retention as usize)?;
+        }
+        Ok(files)
+    }
+}
+
+///Имя базы данных по имени файла резервной копии
+pub fn backup_database(file: &Path) -> Option<&str>
+{
+    let name = file.file_name()?.to_str()?;
+    let (database, _) = name.split_once('-')?;
+    name.ends_with(BACKUP_EXTENSION).then_some(database)
+}
+
+///Если копия с таким временем уже есть (несколько запусков в одну секунду) к имени добавляется номер
+fn backup_path(directory: &Path, database: &str, stamp: &str) -> PathBuf
+{
+    let mut path = directory.join([database, "-", stamp, BACKUP_EXTENSION].concat());
+    let mut n = 1;
+    while path.exists()
+    {
+        path = directory.join([database, "-", stamp, "-", &n.to_string(), BACKUP_EXTENSION].concat());
+        n += 1;
+    }
+    path
+}
+
+///Удаление старых копий базы `database`, остаются `retention` последних
+fn prune(directory: &Path, database: &str, retention: usize) -> Result<(), Error>
+{
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(directory)?
+    {
+        let path = entry?.path();
+        if path.is_file() && backup_database(&path) == Some(database)
+        {
+            let modified = std::fs::metadata(&path)?.modified()?;
+            backups.push((modified, path));
+        }
+    }
+    backups.sort();
+    let excess = backups.len().saturating_sub(retention);
+    for (_, path) in backups.into_iter().take(excess)
+    {
+        std::fs::remove_file(&path)?;
+        logger::info!("Удалена устаревшая резервная копия {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{path::Path, sync::Arc};
+    use crate::{configuration::{Configuration, ConfigurationHandle, IN_MEMORY_DATABASE}, db::{DatabaseService, UserDbo}, Role};
+    use super::{backup_database, BackupService};
+
+    #[test]
+    fn test_backup_database()
+    {
+        assert_eq!(backup_database(Path::new("backups/planner-20250301T120000.sq3")), Some("planner"));
+        assert_eq!(backup_database(Path::new("sessions-20250301T120000-1.sq3")), Some("sessions"));
+        assert_eq!(backup_database(Path::new("planner.sq3")), None);
+        assert_eq!(backup_database(Path::new("planner-20250301T120000.sq3-wal")), None);
+    }
+
+    #[tokio::test]
+    async fn test_backup_retention()
+    {
+        let directory = std::env::temp_dir().join(["planner_backup_", &uuid::Uuid::now_v7().to_string()].concat());
+        let mut cfg = Configuration::default();
+        cfg.database_path = IN_MEMORY_DATABASE.to_owned();
+        cfg.unified_database = true;
+        cfg.backup_directory = directory.to_string_lossy().into_owned();
+        cfg.backup_retention = 2;
+        let database_service = Arc::new(DatabaseService::new(&cfg).await.unwrap());
+        let service = BackupService::new(database_service, ConfigurationHandle::new(cfg));
+        for _ in 0..3
+        {
+            let files = service.backup().await.unwrap();
+            //в общей базе сессии копируются вместе с основной базой
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].database, "planner");
+            assert!(files[0].size > 0);
+        }
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 2);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    fn test_user(username: &str) -> UserDbo
+    {
+        UserDbo
+        {
+            id: uuid::Uuid::now_v7(),
+            username: username.to_owned(),
+            password: "test_password".to_owned(),
+            is_active: true,
+            role: Role::User,
+            audiences: Vec::new(),
+            avatar: None,
+            profile: None,
+            contacts: Vec::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore()
+    {
+        let directory = std::env::temp_dir().join(["planner_restore_", &uuid::Uuid::now_v7().to_string()].concat());
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut cfg = Configuration::default();
+        cfg.database_path = directory.join("planner.sq3").to_string_lossy().into_owned();
+        cfg.unified_database = true;
+        cfg.backup_directory = directory.join("backups").to_string_lossy().into_owned();
+        let database_service = Arc::new(DatabaseService::new(&cfg).await.unwrap());
+        database_service.user_repository.create(test_user("before_backup")).await.unwrap();
+        let service = BackupService::new(Arc::clone(&database_service), ConfigurationHandle::new(cfg.clone()));
+        let backup = service.backup().await.unwrap().remove(0);
+        database_service.user_repository.create(test_user("after_backup")).await.unwrap();
+        //база открыта, восстановление запрещено
+        assert!(crate::db::restore(&cfg, "planner", Path::new(&backup.path)).await.is_err());
+        database_service.close().await;
+        drop(service);
+        drop(database_service);
+        crate::db::restore(&cfg, "planner", Path::new(&backup.path)).await.unwrap();
+        let restored = DatabaseService::new(&cfg).await.unwrap();
+        let users = restored.user_repository.get_users().await.unwrap();
+        assert_eq!(users.iter().map(|u| u.username.as_str()).collect::<Vec<_>>(), vec!["before_backup"]);
+        restored.close().await;
+        drop(restored);
+        //в сохраненной базе есть изменения, сделанные после резервного копирования
+        let mut previous = cfg.clone();
+        previous.database_path = [cfg.database_path.as_str(), ".before-restore"].concat();
+        let previous = DatabaseService::new(&previous).await.unwrap();
+        assert_eq!(previous.user_repository.get_users().await.unwrap().len(), 2);
+        previous.close().await;
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
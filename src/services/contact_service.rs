@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use crate::{db::{ContactDbo, DatabaseService}, i18n::tr_args, ContactType, Error};
+use super::{user_service::Contact, NotificationService};
+
+///Управление контактами пользователя и их подтверждением
+pub struct ContactService
+{
+    database_service: Arc<DatabaseService>,
+    notification_service: NotificationService
+}
+impl ContactService
+{
+    pub fn new(database_service: Arc<DatabaseService>, notification_service: NotificationService) -> Self
+    {
+        Self
+        {
+            database_service,
+            notification_service
+        }
+    }
+    ///Контакт пользователя, чужой контакт считается не найденным
+    async fn get_own_contact(&self, user_id: &uuid::Uuid, contact_id: &uuid::Uuid) -> Result<ContactDbo, Error>
+    {
+        let contact = self.database_service.user_repository.get_contact(contact_id).await?;
+        if &contact.user_id != user_id
+        {
+            Err(Error::ContactNotFound)
+        }
+        else
+        {
+            Ok(contact)
+        }
+    }
+    pub async fn add(&self, user_id: &uuid::Uuid, contact_type: ContactType, contact: &str) -> Result<Contact, Error>
+    {
+        let contact = contact_type.normalize(contact)?;
+        let user = self.database_service.user_repository.get_user(user_id).await?;
+        let contact = ContactDbo
+        {
+            id: uuid::Uuid::now_v7(),
+            user_id: *user_id,
+            contact_type,
+            verified: false,
+            is_primary: user.contacts.is_empty(),
+            contact
+        };
+        self.database_service.user_repository.add_contact(contact.clone()).await?;
+        logger::info!("Пользователю `{}` добавлен контакт `{}`", user_id.to_string(), &contact.contact);
+        Ok(contact.into())
+    }
+    pub async fn delete(&self, user_id: &uuid::Uuid, contact_id: &uuid::Uuid) -> Result<(), Error>
+    {
+        self.database_service.user_repository.delete_contact(user_id, contact_id).await?;
+        logger::info!("У пользователя `{}` удален контакт `{}`", user_id.to_string(), contact_id.to_string());
+        Ok(())
+    }
+    pub async fn set_primary(&self, user_id: &uuid::Uuid, contact_id: &uuid::Uuid) -> Result<(), Error>
+    {
+        self.database_service.user_repository.set_primary_contact(user_id, contact_id).await
+    }
+    ///Генерация кода подтверждения и отправка его на сам контакт
+    pub async fn request_verification(&self, user_id: &uuid::Uuid, contact_id: &uuid::Uuid) -> Result<(), Error>
+    {
+        let contact = self.get_own_contact(user_id, contact_id).await?;
+        if contact.verified
+        {
+            return Err(Error::ValidationError(tr_args("contact_already_verified", &[&contact.contact])));
+        }
+        let code = self.database_service.user_repository.contact_verification_request(contact_id).await?;
+        self.notification_service.send(&contact, &tr_args("verification_code_notification", &[&code.to_string()])).await
+    }
+    pub async fn confirm_verification(&self, user_id: &uuid::Uuid, contact_id: &uuid::Uuid, code: u32) -> Result<(), Error>
+    {
+        let _ = self.get_own_contact(user_id, contact_id).await?;
+        self.database_service.user_repository.contact_verification_accept(contact_id, code).await?;
+        logger::info!("Контакт `{}` пользователя `{}` подтвержден", contact_id.to_string(), user_id.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,439 @@
+use std::{collections::HashMap, pin::Pin, sync::{Arc, RwLock}, time::Instant};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::{sync::Notify, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+use utilites::Date;
+use crate::{configuration::Configuration, db::{format_time, DatabaseService, JobDbo, JobRunDbo}, Error};
+use super::{schedule::Schedule, BackupService, MetricsService};
+
+///интервал проверки задач, запуск вручную и новая отложенная задача будят планировщик сразу
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+///время блокировки задачи, пока задача выполняется блокировка продлевается каждую треть этого времени
+const LEASE_SECONDS: i64 = 60;
+///наибольшая задержка перед повторной попыткой
+const MAX_BACKOFF_SECONDS: i64 = 3600;
+///сколько дней хранятся завершенные отложенные задачи
+const FINISHED_RETENTION_DAYS: i64 = 7;
+
+const BACKUP_JOB: &str = "backup";
+const CLEANUP_JOB: &str = "cleanup";
+const CLEANUP_SCHEDULE: &str = "@hourly";
+
+///Обработчик задач одного типа, `payload` - данные, переданные при создании задачи
+pub trait IJobHandler
+{
+    fn run<'a>(&'a self, payload: &'a serde_json::Value) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+}
+
+///Повторные попытки после ошибки: задержка удваивается с каждой попыткой.
+/// Когда попытки закончились, периодическая задача ждет следующего запуска по расписанию, отложенная больше не запускается
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy
+{
+    ///попыток всего, включая первую
+    pub max_attempts: u32,
+    ///задержка перед первой повторной попыткой в секундах
+    pub backoff: i64
+}
+impl Default for RetryPolicy
+{
+    fn default() -> Self
+    {
+        Self
+        {
+            max_attempts: 3,
+            backoff: 60
+        }
+    }
+}
+impl RetryPolicy
+{
+    ///задержка после `attempt` неудачной попытки
+    fn delay(&self, attempt: u32) -> chrono::Duration
+    {
+        let factor = 1i64.checked_shl(attempt.saturating_sub(1)).unwrap_or(i64::MAX);
+        chrono::Duration::seconds(self.backoff.saturating_mul(factor).min(MAX_BACKOFF_SECONDS))
+    }
+}
+
+#[derive(Clone)]
+struct JobKind
+{
+    handler: Arc<dyn IJobHandler + Send + Sync>,
+    retry: RetryPolicy
+}
+
+///Состояние задачи
+#[derive(Debug, Serialize, Clone, utoipa::ToSchema)]
+pub struct JobInfo
+{
+    pub id: String,
+    pub kind: String,
+    ///`None` у отложенной задачи
+    pub schedule: Option<String>,
+    pub payload: serde_json::Value,
+    ///время следующего запуска в UTC, `None` - задача больше не запускается
+    pub next_run: Option<String>,
+    pub running: bool,
+    ///неудачные попытки подряд
+    pub attempts: u32,
+    pub last_run: Option<String>,
+    pub last_success: Option<bool>,
+    pub last_error: Option<String>,
+    ///длительность последнего запуска в миллисекундах
+    pub last_duration: Option<u64>
+}
+impl From<JobDbo> for JobInfo
+{
+    fn from(value: JobDbo) -> Self
+    {
+        let now = Utc::now();
+        Self
+        {
+            running: value.locked_until.is_some_and(|t| t > now),
+            next_run: value.next_run.as_ref().map(format_time),
+            last_run: value.last_run.as_ref().map(format_time),
+            id: value.id,
+            kind: value.kind,
+            schedule: value.schedule,
+            payload: value.payload,
+            attempts: value.attempts,
+            last_success: value.last_success,
+            last_error: value.last_error,
+            last_duration: value.last_duration
+        }
+    }
+}
+
+///Периодические и отложенные задачи, сохраняемые в базе данных.
+/// Задача одновременно выполняется только в одном месте: перед запуском она блокируется в базе,
+/// блокировка процесса, остановленного во время выполнения задачи, истекает сама
+#[derive(Clone)]
+pub struct JobService
+{
+    database_service: Arc<DatabaseService>,
+    metrics_service: MetricsService,
+    kinds: Arc<RwLock<HashMap<String, JobKind>>>,
+    wake: Arc<Notify>
+}
+impl JobService
+{
+    pub fn new(database_service: Arc<DatabaseService>, metrics_service: MetricsService) -> Self
+    {
+        Self
+        {
+            database_service,
+            metrics_service,
+            kinds: Arc::new(RwLock::new(HashMap::new())),
+            wake: Arc::new(Notify::new())
+        }
+    }
+    ///Обработчик задач типа `kind`, задачи без обработчика не запускаются
+    pub fn register(&self, kind: &str, handler: Arc<dyn IJobHandler + Send + Sync>, retry: RetryPolicy)
+    {
+        self.kinds.write().unwrap().insert(kind.to_owned(), JobKind { handler, retry });
+    }
+    ///Периодическая задача `id`, при повторной регистрации с тем же расписанием время следующего запуска не меняется
+    pub async fn schedule(&self, id: &str, kind: &str, schedule: &str) -> Result<(), Error>
+    {
+        let next_run = schedule.parse::<Schedule>()?.next_after(&Utc::now());
+        let job = JobDbo::new(id.to_owned(), kind, Some(schedule.to_owned()), serde_json::Value::Null, next_run);
+        self.database_service.job_repository.upsert_scheduled(&job).await?;
+        self.wake.notify_one();
+        Ok(())
+    }
+    ///Удаление задачи, `false` если задачи нет
+    pub async fn unschedule(&self, id: &str) -> Result<bool, Error>
+    {
+        self.database_service.job_repository.delete(id).await
+    }
+    ///Однократный запуск задачи типа `kind` через `delay`, возвращает id задачи
+    pub async fn delay<T: Serialize>(&self, kind: &str, payload: &T, delay: chrono::Duration) -> Result<String, Error>
+    {
+        let id = uuid::Uuid::now_v7().to_string();
+        let job = JobDbo::new(id.clone(), kind, None, serde_json::to_value(payload)?, Some(Utc::now() + delay));
+        self.database_service.job_repository.create(&job).await?;
+        self.wake.notify_one();
+        Ok(id)
+    }
+    pub async fn list(&self) -> Result<Vec<JobInfo>, Error>
+    {
+        let jobs = self.database_service.job_repository.list().await?;
+        Ok(jobs.into_iter().map(JobInfo::from).collect())
+    }
+    ///Запуск задачи вне расписания, задачу выполняет планировщик.
+    /// Выполняющаяся сейчас задача повторно не запускается, следующий запуск определится по ее результату
+    pub async fn trigger(&self, id: &str) -> Result<JobInfo, Error>
+    {
+        let repository = &self.database_service.job_repository;
+        if !repository.set_next_run(id, &Utc::now()).await?
+        {
+            return Err(Error::JobNotFound(id.to_owned()));
+        }
+        self.wake.notify_one();
+        let job = repository.get(id).await?.ok_or(Error::Internal(["задача `", id, "` удалена во время запуска"].concat()))?;
+        Ok(job.into())
+    }
+    ///Встроенные задачи: резервное копирование каждые `backup_interval` часов и очистка устаревших записей каждый час
+    pub async fn register_default_jobs(&self, cfg: &Configuration, backup_service: &BackupService) -> Result<(), Error>
+    {
+        self.register(BACKUP_JOB, Arc::new(backup_service.clone()), RetryPolicy::default());
+        self.register(CLEANUP_JOB, Arc::new(CleanupJob { database_service: Arc::clone(&self.database_service) }), RetryPolicy::default());
+        if cfg.backup_interval == 0
+        {
+            self.unschedule(BACKUP_JOB).await?;
+        }
+        else
+        {
+            self.schedule(BACKUP_JOB, BACKUP_JOB, &["@every ", &cfg.backup_interval.to_string(), "h"].concat()).await?;
+        }
+        self.schedule(CLEANUP_JOB, CLEANUP_JOB, CLEANUP_SCHEDULE).await
+    }
+    ///Планировщик запускает наступившие задачи, каждая задача выполняется в отдельной задаче tokio.
+    /// При отмене `shutdown` новые задачи не запускаются, выполняющиеся прерываются и снимают блокировку,
+    /// чтобы запуститься снова после перезапуска сервера
+    pub fn spawn_scheduler(&self, shutdown: CancellationToken)
+    {
+        let service = self.clone();
+        tokio::spawn(async move
+        {
+            let mut running = JoinSet::new();
+            loop
+            {
+                while running.try_join_next().is_some() {}
+                match service.database_service.job_repository.due(&Utc::now()).await
+                {
+                    Ok(jobs) => for job in jobs
+                    {
+                        let service = service.clone();
+                        let shutdown = shutdown.clone();
+                        running.spawn(async move { service.run(job, shutdown).await });
+                    },
+                    Err(e) => logger::error!("Ошибка получения задач: {}", e.to_string())
+                }
+                tokio::select!
+                {
+                    _ = shutdown.cancelled() => break,
+                    _ = service.wake.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+            while running.join_next().await.is_some() {}
+            logger::info!("Планировщик задач остановлен");
+        });
+    }
+    async fn run(&self, job: JobDbo, shutdown: CancellationToken)
+    {
+        let repository = &self.database_service.job_repository;
+        let now = Utc::now();
+        match repository.lock(&job.id, &now, &(now + chrono::Duration::seconds(LEASE_SECONDS))).await
+        {
+            Ok(true) => {},
+            //задачу уже запустил другой планировщик
+            Ok(false) => return,
+            Err(e) =>
+            {
+                logger::error!("Ошибка блокировки задачи `{}`: {}", &job.id, e.to_string());
+                return;
+            }
+        }
+        let kind = self.kinds.read().unwrap().get(&job.kind).cloned();
+        let started = Instant::now();
+        let result = match kind.as_ref()
+        {
+            Some(kind) =>
+            {
+                let Some(result) = self.execute(&job, kind, &shutdown).await else
+                {
+                    logger::warn!("Задача `{}` прервана остановкой сервера", &job.id);
+                    let _ = repository.release(&job.id).await;
+                    return;
+                };
+                result
+            },
+            None => Err(Error::Internal(["нет обработчика задач типа `", &job.kind, "`"].concat()))
+        };
+        let retry = kind.map(|k| k.retry).unwrap_or(RetryPolicy { max_attempts: 1, backoff: 0 });
+        let run = self.job_run(&job, &result, retry, now, started);
+        match &result
+        {
+            Ok(_) => logger::info!("Задача `{}` выполнена за {} мс", &job.id, run.duration),
+            Err(e) => logger::error!("Ошибка задачи `{}` (попытка {}): {}", &job.id, job.attempts + 1, e.to_string())
+        }
+        self.metrics_service.job_result(&job.kind, result.is_ok());
+        if let Err(e) = repository.finish(&job.id, &run).await
+        {
+            logger::error!("Ошибка сохранения результата задачи `{}`: {}", &job.id, e.to_string());
+        }
+    }
+    ///Выполнение с продлением блокировки, `None` если выполнение прервано остановкой сервера
+    async fn execute(&self, job: &JobDbo, kind: &JobKind, shutdown: &CancellationToken) -> Option<Result<(), Error>>
+    {
+        let renew_period = std::time::Duration::from_secs(LEASE_SECONDS as u64 / 3);
+        let mut renew = tokio::time::interval_at(tokio::time::Instant::now() + renew_period, renew_period);
+        let run = kind.handler.run(&job.payload);
+        tokio::pin!(run);
+        loop
+        {
+            tokio::select!
+            {
+                result = &mut run => return Some(result),
+                _ = shutdown.cancelled() => return None,
+                _ = renew.tick() =>
+                {
+                    let until = Utc::now() + chrono::Duration::seconds(LEASE_SECONDS);
+                    if let Err(e) = self.database_service.job_repository.extend_lock(&job.id, &until).await
+                    {
+                        logger::error!("Ошибка продления блокировки задачи `{}`: {}", &job.id, e.to_string());
+                    }
+                }
+            }
+        }
+    }
+    fn job_run(&self, job: &JobDbo, result: &Result<(), Error>, retry: RetryPolicy, started: DateTime<Utc>, timer: Instant) -> JobRunDbo
+    {
+        let scheduled = || job.schedule.as_deref()
+            .and_then(|s| s.parse::<Schedule>().ok())
+            .and_then(|s| s.next_after(&Utc::now()));
+        let (next_run, attempts) = match result
+        {
+            Ok(_) => (scheduled(), 0),
+            Err(_) if job.attempts + 1 < retry.max_attempts => (Some(Utc::now() + retry.delay(job.attempts + 1)), job.attempts + 1),
+            Err(_) => (scheduled(), if job.schedule.is_some() { 0 } else { job.attempts + 1 })
+        };
+        JobRunDbo
+        {
+            next_run,
+            attempts,
+            started,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            duration: timer.elapsed().as_millis() as u64
+        }
+    }
+}
+
+impl IJobHandler for BackupService
+{
+    fn run<'a>(&'a self, _payload: &'a serde_json::Value) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        Box::pin(async move
+        {
+            self.backup().await?;
+            Ok(())
+        })
+    }
+}
+
+///Удаление сессий с истекшим ключом, просроченных кодов входа и старых завершенных отложенных задач
+struct CleanupJob
+{
+    database_service: Arc<DatabaseService>
+}
+impl IJobHandler for CleanupJob
+{
+    fn run<'a>(&'a self, _payload: &'a serde_json::Value) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        Box::pin(async move
+        {
+            let sessions = self.database_service.session_repository.delete_expired().await?;
+            let codes = self.database_service.login_code_repository.delete_expired(&Date::now()).await?;
+            let before = Utc::now() - chrono::Duration::days(FINISHED_RETENTION_DAYS);
+            let jobs = self.database_service.job_repository.delete_finished(&before).await?;
+            logger::info!("Удалено сессий: {}, кодов входа: {}, завершенных задач: {}", sessions, codes, jobs);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{pin::Pin, sync::{atomic::{AtomicU32, Ordering}, Arc}};
+    use tokio_util::sync::CancellationToken;
+    use crate::{configuration::Configuration, db::DatabaseService, services::MetricsService, Error};
+    use super::{IJobHandler, JobService, RetryPolicy};
+
+    ///падает `failures` раз, затем выполняется успешно
+    struct FlakyJob
+    {
+        runs: AtomicU32,
+        failures: u32
+    }
+    impl IJobHandler for FlakyJob
+    {
+        fn run<'a>(&'a self, _payload: &'a serde_json::Value) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+        {
+            Box::pin(async move
+            {
+                let run = self.runs.fetch_add(1, Ordering::SeqCst);
+                if run < self.failures
+                {
+                    Err(Error::Internal("flaky".to_owned()))
+                }
+                else
+                {
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    async fn test_service() -> JobService
+    {
+        let database_service = Arc::new(DatabaseService::in_memory(&Configuration::default()).await.unwrap());
+        JobService::new(database_service, MetricsService::new())
+    }
+
+    #[test]
+    fn test_backoff()
+    {
+        let retry = RetryPolicy { max_attempts: 10, backoff: 60 };
+        assert_eq!(retry.delay(1).num_seconds(), 60);
+        assert_eq!(retry.delay(3).num_seconds(), 240);
+        assert_eq!(retry.delay(9).num_seconds(), 3600);
+        assert_eq!(retry.delay(100).num_seconds(), 3600);
+    }
+
+    #[tokio::test]
+    async fn test_retry()
+    {
+        let service = test_service().await;
+        let handler = Arc::new(FlakyJob { runs: AtomicU32::new(0), failures: 1 });
+        service.register("flaky", handler.clone(), RetryPolicy { max_attempts: 2, backoff: 0 });
+        let id = service.delay("flaky", &serde_json::json!({ "n": 1 }), chrono::Duration::zero()).await.unwrap();
+        let shutdown = CancellationToken::new();
+        let job = service.database_service.job_repository.get(&id).await.unwrap().unwrap();
+        service.run(job, shutdown.clone()).await;
+        let job = service.database_service.job_repository.get(&id).await.unwrap().unwrap();
+        assert_eq!((job.attempts, job.last_success), (1, Some(false)));
+        assert!(job.next_run.is_some());
+        service.run(job, shutdown).await;
+        let info = service.list().await.unwrap().into_iter().find(|j| j.id == id).unwrap();
+        assert_eq!((info.attempts, info.last_success, info.running), (0, Some(true), false));
+        assert!(info.next_run.is_none());
+        assert_eq!(handler.runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled()
+    {
+        let service = test_service().await;
+        let cfg = Configuration::default();
+        let backup = crate::services::BackupService::new(Arc::clone(&service.database_service), crate::configuration::ConfigurationHandle::new(cfg.clone()));
+        service.register_default_jobs(&cfg, &backup).await.unwrap();
+        let jobs = service.list().await.unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs.iter().all(|j| j.next_run.is_some() && j.last_run.is_none()));
+        let info = service.trigger(super::CLEANUP_JOB).await.unwrap();
+        assert_eq!(info.kind, super::CLEANUP_JOB);
+        assert!(matches!(service.trigger("missing").await, Err(Error::JobNotFound(id)) if id == "missing"));
+        let job = service.database_service.job_repository.get(super::CLEANUP_JOB).await.unwrap().unwrap();
+        service.run(job, CancellationToken::new()).await;
+        let job = service.database_service.job_repository.get(super::CLEANUP_JOB).await.unwrap().unwrap();
+        assert_eq!(job.last_success, Some(true));
+        //следующий запуск снова по расписанию
+        assert!(job.next_run.unwrap() > chrono::Utc::now());
+    }
+}
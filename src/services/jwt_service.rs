@@ -6,6 +6,7 @@ use tokio::sync::Mutex;
 use crate::Error;
 
 
+
 #[derive(Clone)]
 pub struct JwtService
 {
@@ -14,14 +15,26 @@ pub struct JwtService
 }
 impl JwtService
 {
-    pub fn new() -> Self
+    ///`key_path` - файл ключа подписи access ключей и cookie, если файла нет ключ будет сгенерирован
+    pub fn new(key_path: &str) -> Self
     {
         Self
         {
-            jwt: Arc::new(Mutex::new(JWT::new_in_file("key.pkcs8"))),
-            cookie: Arc::new(CookieService::new_with_key("key.pkcs8"))
+            jwt: Arc::new(Mutex::new(JWT::new_in_file(key_path))),
+            cookie: Arc::new(CookieService::new_with_key(key_path))
         }
     }
+    ///Временный ключ подписи, действует до остановки процесса и не сохраняется на диск.
+    /// Выданные с ним ключи доступа и cookie после перезапуска недействительны, используется в тестах
+    pub fn ephemeral() -> Self
+    {
+        //библиотека загружает ключ только из файла, файл удаляется сразу после загрузки
+        let path = std::env::temp_dir().join(["planner_ephemeral_", &uuid::Uuid::now_v7().simple().to_string(), ".pkcs8"].concat());
+        let path = path.to_string_lossy();
+        let service = Self::new(&path);
+        let _ = std::fs::remove_file(path.as_ref());
+        service
+    }
     ///Генерирование нового access ключа
     /// `lifetime` - время жизни ключа в минутах
     pub async fn gen_key<T: ToString>(&self, id: &uuid::Uuid, role: T, audience: &Vec<String>, lifetime: u8) -> String 
@@ -53,6 +66,14 @@ impl JwtService
             .validate(token)?;
         Ok(data.claims)
     }
+    ///Проверка что ключ подписи загружен: ключ доступа подписывается и сразу проверяется
+    pub async fn self_check(&self) -> Result<(), Error>
+    {
+        let id = uuid::Uuid::nil();
+        let token = self.gen_key(&id, "self-check", &Vec::new(), 1).await;
+        let _ = self.validate::<&str, _, String>(&id, &token, [], &[]).await?;
+        Ok(())
+    }
     pub fn cookie_service(&self) -> &CookieService
     {
         &self.cookie
@@ -0,0 +1,186 @@
+use std::{sync::Arc, time::Duration};
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use crate::{db::{DatabaseService, ISessionRepository}, Error};
+
+///Способ входа для метрик входа
+#[derive(Debug, Clone, Copy)]
+pub enum LoginMethod
+{
+    Password,
+    Passwordless
+}
+impl LoginMethod
+{
+    fn as_str(&self) -> &'static str
+    {
+        match self
+        {
+            LoginMethod::Password => "password",
+            LoginMethod::Passwordless => "passwordless"
+        }
+    }
+}
+
+struct Metrics
+{
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    logins: IntCounterVec,
+    token_refreshes: IntCounterVec,
+    fingerprint_revocations: IntCounter,
+    active_sessions: IntGauge,
+    pool_connections: IntGaugeVec,
+    pool_idle_connections: IntGaugeVec,
+    jobs: IntCounterVec
+}
+
+///Метрики сервера в формате Prometheus
+#[derive(Clone)]
+pub struct MetricsService
+{
+    metrics: Arc<Metrics>
+}
+impl MetricsService
+{
+    pub fn new() -> Self
+    {
+        let registry = Registry::new_custom(Some("planner".to_owned()), None).expect("valid metrics prefix");
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"]).unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route and status"),
+            &["method", "route", "status"]).unwrap();
+        let logins = IntCounterVec::new(
+            Opts::new("logins_total", "Login attempts by method, result and failure reason"),
+            &["method", "result", "reason"]).unwrap();
+        let token_refreshes = IntCounterVec::new(
+            Opts::new("token_refreshes_total", "Access key refreshes by result"),
+            &["result"]).unwrap();
+        let fingerprint_revocations = IntCounter::new(
+            "fingerprint_revocations_total", "Sessions revoked because fingerprint did not match on access key refresh").unwrap();
+        let active_sessions = IntGauge::new("active_sessions", "Sessions with unexpired key").unwrap();
+        let pool_connections = IntGaugeVec::new(
+            Opts::new("sqlite_pool_connections", "Open SQLite connections"),
+            &["database"]).unwrap();
+        let pool_idle_connections = IntGaugeVec::new(
+            Opts::new("sqlite_pool_idle_connections", "Idle SQLite connections"),
+            &["database"]).unwrap();
+        let jobs = IntCounterVec::new(
+            Opts::new("background_jobs_total", "Background job runs by job and result"),
+            &["job", "result"]).unwrap();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(logins.clone())).unwrap();
+        registry.register(Box::new(token_refreshes.clone())).unwrap();
+        registry.register(Box::new(fingerprint_revocations.clone())).unwrap();
+        registry.register(Box::new(active_sessions.clone())).unwrap();
+        registry.register(Box::new(pool_connections.clone())).unwrap();
+        registry.register(Box::new(pool_idle_connections.clone())).unwrap();
+        registry.register(Box::new(jobs.clone())).unwrap();
+        Self
+        {
+            metrics: Arc::new(Metrics
+            {
+                registry,
+                http_requests,
+                http_request_duration,
+                logins,
+                token_refreshes,
+                fingerprint_revocations,
+                active_sessions,
+                pool_connections,
+                pool_idle_connections,
+                jobs
+            })
+        }
+    }
+    ///`route` - шаблон маршрута (например `/api/users/avatar/{user_id}`), а не фактический путь, чтобы не раздувать количество серий
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, duration: Duration)
+    {
+        let status = status.to_string();
+        let labels = [method, route, &status];
+        self.metrics.http_requests.with_label_values(&labels).inc();
+        self.metrics.http_request_duration.with_label_values(&labels).observe(duration.as_secs_f64());
+    }
+    pub fn login<T>(&self, method: LoginMethod, result: &Result<T, Error>)
+    {
+        match result
+        {
+            Ok(_) => self.metrics.logins.with_label_values(&[method.as_str(), "success", ""]).inc(),
+            Err(e) => self.metrics.logins.with_label_values(&[method.as_str(), "failure", failure_reason(e)]).inc()
+        }
+    }
+    pub fn token_refresh<T>(&self, result: &Result<T, Error>)
+    {
+        let result = if result.is_ok() { "success" } else { "failure" };
+        self.metrics.token_refreshes.with_label_values(&[result]).inc();
+    }
+    pub fn fingerprint_revocation(&self)
+    {
+        self.metrics.fingerprint_revocations.inc();
+    }
+    pub fn job_result(&self, job: &str, success: bool)
+    {
+        let result = if success { "success" } else { "failure" };
+        self.metrics.jobs.with_label_values(&[job, result]).inc();
+    }
+    ///Текстовый формат Prometheus, значения состояния (сессии, пулы) считываются в момент запроса
+    pub async fn render(&self, database_service: &DatabaseService) -> Result<String, Error>
+    {
+        match database_service.session_repository.active_sessions_count().await
+        {
+            Ok(count) => self.metrics.active_sessions.set(count as i64),
+            Err(e) => logger::error!("Ошибка получения количества сессий для метрик: {}", e.to_string())
+        }
+        for (database, size, idle) in database_service.pool_stats()
+        {
+            self.metrics.pool_connections.with_label_values(&[database]).set(size as i64);
+            self.metrics.pool_idle_connections.with_label_values(&[database]).set(idle as i64);
+        }
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.metrics.registry.gather(), &mut buffer)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+fn failure_reason(error: &Error) -> &'static str
+{
+    match error
+    {
+        Error::UserNotFound => "user_not_found",
+        Error::AuthError(_) => "auth_error",
+        Error::AmbiguousLogin(_) => "ambiguous_login",
+        Error::TooManyRequests => "too_many_requests",
+        Error::FeatureDisabled(_) => "feature_disabled",
+        Error::VerificationCodeWrong => "wrong_code",
+        Error::VerificationCodeExpired => "code_expired",
+        Error::VerificationNotFound => "code_not_found",
+        Error::WrongFingerprintError(_) | Error::FingerprintNotFound => "fingerprint",
+        Error::SqlxError(_) => "database",
+        _ => "other"
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::Duration;
+    use crate::Error;
+    use super::{LoginMethod, MetricsService};
+
+    #[test]
+    fn test_metrics_text()
+    {
+        let metrics = MetricsService::new();
+        metrics.observe_request("GET", "/health/live", 200, Duration::from_millis(3));
+        metrics.login::<()>(LoginMethod::Password, &Err(Error::UserNotFound));
+        let mut buffer = Vec::new();
+        prometheus::Encoder::encode(&prometheus::TextEncoder::new(), &metrics.metrics.registry.gather(), &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.contains("planner_http_requests_total{method=\"GET\",route=\"/health/live\",status=\"200\"} 1"));
+        assert!(text.contains("planner_logins_total{method=\"password\",reason=\"user_not_found\",result=\"failure\"} 1"));
+    }
+}
@@ -1,4 +1,27 @@
 mod user_service;
 mod jwt_service;
+mod notification_service;
+mod user_transfer;
+mod avatar_service;
+mod contact_service;
+mod passwordless_service;
+mod metrics_service;
+mod rate_limit_service;
+mod backup_service;
+mod sse_service;
+mod ws_service;
+mod schedule;
+mod job_service;
 pub use jwt_service::JwtService;
-pub use user_service::{UserService, Contact, UserInformation, AuthorizationInformation};
\ No newline at end of file
+pub use user_service::{UserService, Contact, UserInformation, AuthorizationInformation, Profile};
+pub use notification_service::{NotificationService, INotificationSender, LogNotificationSender};
+pub use user_transfer::{UserTransferService, TransferFormat, ImportReport};
+pub use avatar_service::AvatarService;
+pub use contact_service::ContactService;
+pub use passwordless_service::PasswordlessService;
+pub use metrics_service::{MetricsService, LoginMethod};
+pub use rate_limit_service::{RateLimitService, RateLimitDecision};
+pub use backup_service::{BackupService, BackupFile, backup_database};
+pub use sse_service::{SSEService, EventSubscription, EventTarget, ServerEvent, RESYNC_EVENT};
+pub use ws_service::{WsService, WsConnection, Topic, Viewer, ClientMessage, ServerMessage};
+pub use job_service::{JobService, JobInfo, IJobHandler, RetryPolicy};
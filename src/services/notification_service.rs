@@ -0,0 +1,55 @@
+use std::{pin::Pin, sync::Arc};
+use crate::{db::ContactDbo, Error};
+
+///Отправка сообщения пользователю на один из его контактов
+pub trait INotificationSender
+{
+    fn send<'a>(&'a self, contact: &'a ContactDbo, message: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+}
+
+///Отправитель по умолчанию, пока нет шлюзов почты и смс сообщение не доставляется.
+/// Текст сообщения в лог не пишется: в нем бывают пароли и коды входа
+pub struct LogNotificationSender;
+impl INotificationSender for LogNotificationSender
+{
+    fn send<'a>(&'a self, contact: &'a ContactDbo, _message: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+    {
+        Box::pin(async move
+        {
+            logger::warn!("Шлюз отправки сообщений не настроен, сообщение для `{}` ({}) не доставлено", &contact.contact, &contact.contact_type);
+            Err(Error::FeatureDisabled("notifications".to_owned()))
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct NotificationService
+{
+    sender: Arc<dyn INotificationSender + Send + Sync>
+}
+impl NotificationService
+{
+    pub fn new() -> Self
+    {
+        Self
+        {
+            sender: Arc::new(LogNotificationSender)
+        }
+    }
+    pub fn with_sender(sender: Arc<dyn INotificationSender + Send + Sync>) -> Self
+    {
+        Self
+        {
+            sender
+        }
+    }
+    pub async fn send(&self, contact: &ContactDbo, message: &str) -> Result<(), Error>
+    {
+        let result = self.sender.send(contact, message).await;
+        if result.is_err()
+        {
+            logger::error!("Ошибка отправки сообщения на `{}`: {}", &contact.contact, result.as_ref().err().unwrap());
+        }
+        result
+    }
+}
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+use rand::{distr::Alphanumeric, Rng};
+use utilites::Date;
+use crate::{configuration::ConfigurationHandle, i18n::tr_args, db::{ContactDbo, DatabaseService, LoginCodeDbo, UserDbo}, ContactType, Error};
+use super::{metrics_service::LoginMethod, MetricsService, NotificationService};
+
+const TOKEN_LEN: usize = 32;
+
+///Вход без пароля: одноразовый код или ссылка отправляется на подтвержденный контакт пользователя
+pub struct PasswordlessService
+{
+    database_service: Arc<DatabaseService>,
+    notification_service: NotificationService,
+    configuration: ConfigurationHandle,
+    metrics_service: MetricsService
+}
+impl PasswordlessService
+{
+    pub fn new(database_service: Arc<DatabaseService>, notification_service: NotificationService, configuration: ConfigurationHandle, metrics_service: MetricsService) -> Self
+    {
+        Self
+        {
+            database_service,
+            notification_service,
+            configuration,
+            metrics_service
+        }
+    }
+    fn check_enabled(&self) -> Result<(), Error>
+    {
+        if self.configuration.get().passwordless_login
+        {
+            Ok(())
+        }
+        else
+        {
+            Err(Error::FeatureDisabled("passwordless_login".to_owned()))
+        }
+    }
+    ///Пользователь и контакт на который будет отправлен код.
+    /// Если введен контакт - код уходит на него, если имя пользователя - на основной подтвержденный контакт
+    async fn find_recipient(&self, login: &str) -> Result<(UserDbo, ContactDbo), Error>
+    {
+        let repository = &self.database_service.user_repository;
+        for contact_type in [ContactType::Email, ContactType::Phone]
+        {
+            if let Ok(contact) = contact_type.normalize(login)
+            {
+                if let Some(username) = repository.find_username_by_verified_contact(contact_type, &contact).await?
+                {
+                    let user = repository.get_user_by_username(&username).await?;
+                    let contact = user.contacts.iter().find(|c| c.verified && c.contact_type == contact_type && c.contact == contact).cloned();
+                    if let Some(contact) = contact
+                    {
+                        return Ok((user, contact));
+                    }
+                }
+            }
+        }
+        let user = repository.get_user_by_username(login).await?;
+        let contact = user.contacts.iter()
+        .filter(|c| c.verified && c.contact_type != ContactType::Messenger)
+        .max_by_key(|c| c.is_primary)
+        .cloned();
+        if let Some(contact) = contact
+        {
+            Ok((user, contact))
+        }
+        else
+        {
+            Err(Error::ValidationError("no_verified_contacts".to_owned()))
+        }
+    }
+    ///Запрос кода, возвращает id запроса, который клиент передает вместе с кодом
+    pub async fn request(&self, login: &str, fingerprint: &str) -> Result<uuid::Uuid, Error>
+    {
+        self.check_enabled()?;
+        let cfg = self.configuration.get();
+        let (user, contact) = self.find_recipient(login).await?;
+        if !user.is_active
+        {
+            return Err(Error::AuthError(tr_args("user_inactive", &[&user.username])));
+        }
+        let since = Date::now().add_minutes(-(cfg.login_code_requests_window as i64));
+        let count = self.database_service.login_code_repository.requests_count(&user.id, &since).await?;
+        if count >= cfg.login_code_max_requests as u32
+        {
+            logger::warn!("Превышено количество запросов кода входа для `{}`", &user.username);
+            return Err(Error::TooManyRequests);
+        }
+        let id = uuid::Uuid::now_v7();
+        let code: u32 = rand::rng().random_range(100000..=999999);
+        let code = code.to_string();
+        let token: String = rand::rng().sample_iter(&Alphanumeric).take(TOKEN_LEN).map(char::from).collect();
+        let login_code = LoginCodeDbo
+        {
+            id,
+            user_id: user.id,
+            contact_id: contact.id,
+            code_hash: hash(&code, &id),
+            token_hash: hash(&token, &id),
+            fingerprint: fingerprint.to_owned(),
+            created: Date::now(),
+            expiration_time: Date::now().add_minutes(cfg.login_code_lifetime as i64),
+            attempts: 0,
+            used: false
+        };
+        self.database_service.login_code_repository.create(&login_code).await?;
+        let link = [cfg.public_url.trim_end_matches('/'), "/passwordless?request=", &id.to_string(), "&token=", &token].concat();
+        let message = tr_args("login_code_notification", &[&code, &cfg.login_code_lifetime.to_string(), &link]);
+        self.notification_service.send(&contact, &message).await?;
+        logger::info!("Пользователю `{}` отправлен код входа на `{}`", &user.username, &contact.contact);
+        Ok(id)
+    }
+    ///Проверка кода или токена из ссылки, возвращает пользователя для которого нужно создать сессию
+    pub async fn redeem(&self, request_id: &uuid::Uuid, secret: &str, fingerprint: &str) -> Result<UserDbo, Error>
+    {
+        let result = self.redeem_code(request_id, secret, fingerprint).await;
+        self.metrics_service.login(LoginMethod::Passwordless, &result);
+        result
+    }
+    async fn redeem_code(&self, request_id: &uuid::Uuid, secret: &str, fingerprint: &str) -> Result<UserDbo, Error>
+    {
+        self.check_enabled()?;
+        let cfg = self.configuration.get();
+        let repository = &self.database_service.login_code_repository;
+        let login_code = repository.get(request_id).await?;
+        if login_code.used
+        {
+            return Err(Error::VerificationNotFound);
+        }
+        if login_code.expiration_time < Date::now()
+        {
+            return Err(Error::VerificationCodeExpired);
+        }
+        if login_code.attempts >= cfg.login_code_max_attempts as u32
+        {
+            return Err(Error::TooManyRequests);
+        }
+        if login_code.fingerprint != fingerprint
+        {
+            logger::warn!("Попытка использовать код входа `{}` с другого устройства", request_id.to_string());
+            repository.increment_attempts(request_id).await?;
+            return Err(Error::AuthError("login_code_other_device".to_owned()));
+        }
+        let secret_hash = hash(secret.trim(), request_id);
+        if secret_hash != login_code.code_hash && secret_hash != login_code.token_hash
+        {
+            repository.increment_attempts(request_id).await?;
+            return Err(Error::VerificationCodeWrong);
+        }
+        if !repository.mark_used(request_id).await?
+        {
+            return Err(Error::VerificationNotFound);
+        }
+        self.database_service.user_repository.get_user(&login_code.user_id).await
+    }
+}
+
+fn hash(secret: &str, request_id: &uuid::Uuid) -> String
+{
+    utilites::Hasher::hash_from_strings([secret, &request_id.to_string()])
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::{pin::Pin, sync::{Arc, Mutex}};
+    use utilites::Date;
+    use crate::{configuration::{Configuration, ConfigurationHandle}, db::{ContactDbo, DatabaseService, UserDbo}, services::{INotificationSender, MetricsService, NotificationService}, ContactType, Error, Role};
+    use super::{PasswordlessService, TOKEN_LEN};
+
+    ///сохраняет отправленные сообщения, из них тест берет токен входа
+    #[derive(Default)]
+    struct CapturingSender
+    {
+        messages: Mutex<Vec<String>>
+    }
+    impl INotificationSender for CapturingSender
+    {
+        fn send<'a>(&'a self, _contact: &'a ContactDbo, message: &'a str) -> Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>
+        {
+            Box::pin(async move
+            {
+                self.messages.lock().unwrap().push(message.to_owned());
+                Ok(())
+            })
+        }
+    }
+    impl CapturingSender
+    {
+        fn last_token(&self) -> String
+        {
+            let messages = self.messages.lock().unwrap();
+            let (_, token) = messages.last().unwrap().split_once("&token=").unwrap();
+            token.chars().take(TOKEN_LEN).collect()
+        }
+    }
+
+    ///сервис с включенным входом без пароля и пользователь `TestUser` с подтвержденным email
+    async fn test_service() -> (PasswordlessService, Arc<CapturingSender>, Arc<DatabaseService>)
+    {
+        let mut cfg = Configuration::default();
+        cfg.passwordless_login = true;
+        let database_service = Arc::new(DatabaseService::in_memory(&cfg).await.unwrap());
+        let id = uuid::Uuid::now_v7();
+        let contact_id = uuid::Uuid::now_v7();
+        let user = UserDbo
+        {
+            id,
+            username: "TestUser".to_owned(),
+            password: "test_password".to_owned(),
+            is_active: true,
+            role: Role::User,
+            audiences: Vec::new(),
+            avatar: None,
+            profile: None,
+            contacts: vec![ContactDbo
+            {
+                id: contact_id,
+                user_id: id,
+                contact_type: ContactType::Email,
+                verified: false,
+                is_primary: true,
+                contact: "test@test.ru".to_owned()
+            }]
+        };
+        let repository = &database_service.user_repository;
+        repository.create(user).await.unwrap();
+        let code = repository.contact_verification_request(&contact_id).await.unwrap();
+        repository.contact_verification_accept(&contact_id, code).await.unwrap();
+        let sender = Arc::new(CapturingSender::default());
+        let service = PasswordlessService::new(Arc::clone(&database_service), NotificationService::with_sender(sender.clone()), ConfigurationHandle::new(cfg), MetricsService::new());
+        (service, sender, database_service)
+    }
+
+    #[tokio::test]
+    async fn test_request()
+    {
+        let (service, sender, database_service) = test_service().await;
+        let request_id = service.request("test@test.ru", "fingerprint").await.unwrap();
+        let code = database_service.login_code_repository.get(&request_id).await.unwrap();
+        assert_eq!(code.fingerprint, "fingerprint");
+        assert!(code.expiration_time > Date::now());
+        assert_eq!(sender.messages.lock().unwrap().len(), 1);
+        //код отправлен на основной подтвержденный контакт и при входе по имени пользователя
+        service.request("TestUser", "fingerprint").await.unwrap();
+        service.request("TestUser", "fingerprint").await.unwrap();
+        assert!(matches!(service.request("TestUser", "fingerprint").await, Err(Error::TooManyRequests)));
+    }
+
+    #[tokio::test]
+    async fn test_single_use()
+    {
+        let (service, sender, _) = test_service().await;
+        let request_id = service.request("TestUser", "fingerprint").await.unwrap();
+        let token = sender.last_token();
+        assert!(matches!(service.redeem(&request_id, "wrong", "fingerprint").await, Err(Error::VerificationCodeWrong)));
+        let user = service.redeem(&request_id, &token, "fingerprint").await.unwrap();
+        assert_eq!(user.username, "TestUser");
+        assert!(matches!(service.redeem(&request_id, &token, "fingerprint").await, Err(Error::VerificationNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_other_device()
+    {
+        let (service, sender, database_service) = test_service().await;
+        let request_id = service.request("TestUser", "fingerprint").await.unwrap();
+        let token = sender.last_token();
+        assert!(matches!(service.redeem(&request_id, &token, "other_fingerprint").await, Err(Error::AuthError(_))));
+        assert_eq!(database_service.login_code_repository.get(&request_id).await.unwrap().attempts, 1);
+        assert!(service.redeem(&request_id, &token, "fingerprint").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_expired()
+    {
+        let (service, sender, database_service) = test_service().await;
+        let request_id = service.request("TestUser", "fingerprint").await.unwrap();
+        let token = sender.last_token();
+        let mut code = database_service.login_code_repository.get(&request_id).await.unwrap();
+        code.id = uuid::Uuid::now_v7();
+        code.expiration_time = Date::now().add_minutes(-1);
+        database_service.login_code_repository.create(&code).await.unwrap();
+        //срок действия проверяется раньше кода, поэтому токен другого запроса подходит для проверки
+        assert!(matches!(service.redeem(&code.id, &token, "fingerprint").await, Err(Error::VerificationCodeExpired)));
+    }
+}
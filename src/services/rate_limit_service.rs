@@ -0,0 +1,165 @@
+use std::{collections::{BTreeSet, HashMap}, sync::{Arc, Mutex, RwLock}, time::Instant};
+use crate::configuration::{Configuration, RateLimit};
+
+///при превышении этого количества корзин удаляются заполненные (давно не использованные)
+const MAX_BUCKETS: usize = 100_000;
+
+struct Bucket
+{
+    tokens: f64,
+    updated: Instant,
+    capacity: u32,
+    refill_per_minute: u32
+}
+impl Bucket
+{
+    fn refill(&mut self, now: Instant)
+    {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate()).min(self.capacity as f64);
+        self.updated = now;
+    }
+    fn rate(&self) -> f64
+    {
+        self.refill_per_minute as f64 / 60.0
+    }
+    fn is_full(&self, now: Instant) -> bool
+    {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * self.rate() >= self.capacity as f64
+    }
+}
+
+///Результат проверки ограничения, значения для заголовков `RateLimit-*`
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision
+{
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    ///секунд до полного восстановления запросов
+    pub reset: u64,
+    ///секунд до следующего разрешенного запроса, 0 если запрос разрешен
+    pub retry_after: u64
+}
+
+///Ограничение частоты запросов по алгоритму token bucket.
+/// Корзины хранятся в памяти процесса отдельно для каждой группы маршрутов и клиента
+#[derive(Clone)]
+pub struct RateLimitService
+{
+    buckets: Arc<Mutex<HashMap<(String, String), Bucket>>>,
+    ///клиенты, добавленные администратором во время работы сервера, дополняют `rate_limit_exempt` из настроек
+    exempt: Arc<RwLock<BTreeSet<String>>>
+}
+impl RateLimitService
+{
+    pub fn new() -> Self
+    {
+        Self
+        {
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            exempt: Arc::new(RwLock::new(BTreeSet::new()))
+        }
+    }
+    pub fn check(&self, group: &str, client: &str, limit: &RateLimit) -> RateLimitDecision
+    {
+        self.check_at(group, client, limit, Instant::now())
+    }
+    fn check_at(&self, group: &str, client: &str, limit: &RateLimit, now: Instant) -> RateLimitDecision
+    {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_BUCKETS
+        {
+            buckets.retain(|_, b| !b.is_full(now));
+        }
+        let bucket = buckets.entry((group.to_owned(), client.to_owned())).or_insert_with(|| Bucket
+        {
+            tokens: limit.capacity as f64,
+            updated: now,
+            capacity: limit.capacity,
+            refill_per_minute: limit.refill_per_minute
+        });
+        //лимит мог измениться при перезагрузке настроек
+        bucket.capacity = limit.capacity;
+        bucket.refill_per_minute = limit.refill_per_minute;
+        bucket.refill(now);
+        let allowed = bucket.tokens >= 1.0;
+        if allowed
+        {
+            bucket.tokens -= 1.0;
+        }
+        let rate = bucket.rate();
+        RateLimitDecision
+        {
+            allowed,
+            limit: limit.capacity,
+            remaining: bucket.tokens.floor() as u32,
+            reset: ((limit.capacity as f64 - bucket.tokens) / rate).ceil() as u64,
+            retry_after: if allowed { 0 } else { ((1.0 - bucket.tokens) / rate).ceil() as u64 }
+        }
+    }
+    ///Клиент не ограничивается, если любой из его идентификаторов (ip, id пользователя, id сессии)
+    /// указан в настройках или добавлен администратором
+    pub fn is_exempt(&self, cfg: &Configuration, ids: &[&str]) -> bool
+    {
+        let exempt = self.exempt.read().unwrap_or_else(|e| e.into_inner());
+        ids.iter().any(|id| exempt.contains(*id) || cfg.rate_limit_exempt.iter().any(|e| e == id))
+    }
+    pub fn exempt(&self) -> Vec<String>
+    {
+        self.exempt.read().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+    ///Снятие ограничений с клиента, накопленные корзины клиента удаляются
+    pub fn add_exempt(&self, client: &str)
+    {
+        self.exempt.write().unwrap_or_else(|e| e.into_inner()).insert(client.to_owned());
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner()).retain(|(_, c), _| c != client);
+    }
+    ///`false` если клиента не было в списке
+    pub fn remove_exempt(&self, client: &str) -> bool
+    {
+        self.exempt.write().unwrap_or_else(|e| e.into_inner()).remove(client)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::time::{Duration, Instant};
+    use crate::configuration::{Configuration, RateLimit, RateLimitKey};
+    use super::RateLimitService;
+
+    #[test]
+    fn test_token_bucket()
+    {
+        let service = RateLimitService::new();
+        let limit = RateLimit { key: RateLimitKey::Ip, capacity: 2, refill_per_minute: 60 };
+        let now = Instant::now();
+        assert!(service.check_at("api", "127.0.0.1", &limit, now).allowed);
+        let second = service.check_at("api", "127.0.0.1", &limit, now);
+        assert!(second.allowed);
+        assert_eq!(second.remaining, 0);
+        let denied = service.check_at("api", "127.0.0.1", &limit, now);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, 1);
+        //другой клиент и другая группа считаются отдельно
+        assert!(service.check_at("api", "127.0.0.2", &limit, now).allowed);
+        assert!(service.check_at("auth", "127.0.0.1", &limit, now).allowed);
+        assert!(service.check_at("api", "127.0.0.1", &limit, now + Duration::from_secs(1)).allowed);
+    }
+
+    #[test]
+    fn test_exempt()
+    {
+        let service = RateLimitService::new();
+        let mut cfg = Configuration::default();
+        cfg.rate_limit_exempt = vec!["10.0.0.1".to_owned()];
+        assert!(service.is_exempt(&cfg, &["10.0.0.1"]));
+        assert!(!service.is_exempt(&cfg, &["10.0.0.2", "user"]));
+        service.add_exempt("user");
+        assert!(service.is_exempt(&cfg, &["10.0.0.2", "user"]));
+        assert!(service.remove_exempt("user"));
+        assert!(!service.remove_exempt("user"));
+    }
+}
@@ -0,0 +1,254 @@
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+use crate::{i18n::tr_args, Error};
+
+///предел поиска следующего запуска, выражение вроде `0 0 31 2 *` никогда не срабатывает
+const SEARCH_LIMIT_DAYS: i64 = 366 * 5;
+
+///Расписание периодической задачи, время в UTC.
+/// Поддерживаются выражения cron из пяти полей (`минута час день месяц день_недели`, со списками, диапазонами и шагом),
+/// сокращения `@hourly`, `@daily`, `@weekly`, `@monthly` и интервал `@every <n>s|m|h|d`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule
+{
+    Cron(CronExpression),
+    Every(Duration)
+}
+impl Schedule
+{
+    ///Первый запуск строго после `after`, `None` если выражение никогда не срабатывает
+    pub fn next_after(&self, after: &DateTime<Utc>) -> Option<DateTime<Utc>>
+    {
+        match self
+        {
+            Schedule::Every(interval) => Some(*after + *interval),
+            Schedule::Cron(cron) => cron.next_after(after)
+        }
+    }
+}
+impl FromStr for Schedule
+{
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let s = s.trim();
+        let expression = match s
+        {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            _ => s
+        };
+        if let Some(interval) = expression.strip_prefix("@every ")
+        {
+            return parse_interval(interval.trim()).map(Schedule::Every);
+        }
+        expression.parse().map(Schedule::Cron)
+    }
+}
+
+fn parse_interval(s: &str) -> Result<Duration, Error>
+{
+    let invalid = || Error::ValidationError(tr_args("schedule_invalid_interval", &[s]));
+    let (value, unit) = s.split_at(s.len().saturating_sub(1));
+    let value: i64 = value.parse().map_err(|_| invalid())?;
+    if value <= 0
+    {
+        return Err(invalid());
+    }
+    let interval = match unit
+    {
+        "s" => Duration::try_seconds(value),
+        "m" => Duration::try_minutes(value),
+        "h" => Duration::try_hours(value),
+        "d" => Duration::try_days(value),
+        _ => None
+    };
+    interval.ok_or_else(invalid)
+}
+
+///Выражение cron, каждое поле - набор допустимых значений битами
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpression
+{
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    ///поле дня месяца задано `*`
+    any_day: bool,
+    ///поле дня недели задано `*`
+    any_weekday: bool
+}
+impl CronExpression
+{
+    fn next_after(&self, after: &DateTime<Utc>) -> Option<DateTime<Utc>>
+    {
+        //запуск не раньше начала следующей минуты
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = *after + Duration::days(SEARCH_LIMIT_DAYS);
+        while time <= limit
+        {
+            if !contains(self.months, time.month())
+            {
+                let (year, month) = if time.month() == 12 { (time.year() + 1, 1) } else { (time.year(), time.month() + 1) };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?.and_utc();
+                continue;
+            }
+            if !self.day_matches(&time)
+            {
+                time = (time.date_naive() + Duration::days(1)).and_hms_opt(0, 0, 0)?.and_utc();
+                continue;
+            }
+            if !contains(self.hours, time.hour())
+            {
+                time = time.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if !contains(self.minutes, time.minute())
+            {
+                time += Duration::minutes(1);
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+    ///как в cron: если заданы и день месяца и день недели, достаточно совпадения одного из них
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool
+    {
+        let day = contains(self.days, time.day());
+        let weekday = contains(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday)
+        {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday
+        }
+    }
+}
+impl FromStr for CronExpression
+{
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 5
+        {
+            return Err(Error::ValidationError(tr_args("schedule_fields_count", &[s])));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        //7 - тоже воскресенье
+        if contains(weekdays, 7)
+        {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self
+        {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*"
+        })
+    }
+}
+
+fn contains(set: u64, value: u32) -> bool
+{
+    set & (1 << value) != 0
+}
+
+///Поле cron: `*`, `5`, `1-5`, `*/15`, `10-50/10`, списки через запятую
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, Error>
+{
+    let invalid = || Error::ValidationError(tr_args("schedule_invalid_field", &[field, &min.to_string(), &max.to_string()]));
+    let mut set = 0u64;
+    for part in field.split(',')
+    {
+        let (range, step) = match part.split_once('/')
+        {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1)
+        };
+        if step == 0
+        {
+            return Err(invalid());
+        }
+        let (start, end) = if range == "*"
+        {
+            (min, max)
+        }
+        else if let Some((start, end)) = range.split_once('-')
+        {
+            (start.parse::<u32>().map_err(|_| invalid())?, end.parse::<u32>().map_err(|_| invalid())?)
+        }
+        else
+        {
+            let start = range.parse::<u32>().map_err(|_| invalid())?;
+            //`5/10` - с 5 до конца диапазона с шагом 10
+            (start, if part.contains('/') { max } else { start })
+        };
+        if start < min || end > max || start > end
+        {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize)
+        {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use chrono::{DateTime, Duration, Utc};
+    use super::Schedule;
+
+    fn time(s: &str) -> DateTime<Utc>
+    {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+    fn next(schedule: &str, after: &str) -> Option<DateTime<Utc>>
+    {
+        schedule.parse::<Schedule>().unwrap().next_after(&time(after))
+    }
+
+    #[test]
+    fn test_cron()
+    {
+        assert_eq!(next("@hourly", "2025-03-01T12:00:00Z"), Some(time("2025-03-01T13:00:00Z")));
+        assert_eq!(next("*/15 * * * *", "2025-03-01T12:07:30Z"), Some(time("2025-03-01T12:15:00Z")));
+        assert_eq!(next("30 9 * * 1-5", "2025-03-01T12:00:00Z"), Some(time("2025-03-03T09:30:00Z")));
+        assert_eq!(next("0 0 1 * *", "2025-12-15T00:00:00Z"), Some(time("2026-01-01T00:00:00Z")));
+        assert_eq!(next("0 0 29 2 *", "2025-03-01T00:00:00Z"), Some(time("2028-02-29T00:00:00Z")));
+        //день месяца или день недели
+        assert_eq!(next("0 0 15 * 0", "2025-03-01T00:00:00Z"), Some(time("2025-03-02T00:00:00Z")));
+        assert_eq!(next("0 12 * * 7", "2025-03-01T00:00:00Z"), Some(time("2025-03-02T12:00:00Z")));
+        assert_eq!(next("0 0 31 2 *", "2025-03-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_every()
+    {
+        let schedule: Schedule = "@every 24h".parse().unwrap();
+        assert_eq!(schedule, Schedule::Every(Duration::hours(24)));
+        assert_eq!(next("@every 90s", "2025-03-01T12:00:00Z"), Some(time("2025-03-01T12:01:30Z")));
+    }
+
+    #[test]
+    fn test_invalid()
+    {
+        for schedule in ["", "* * * *", "60 * * * *", "* 24 * * *", "*/0 * * * *", "5-1 * * * *", "@every 0h", "@every 5w", "@yearly"]
+        {
+            assert!(schedule.parse::<Schedule>().is_err(), "{}", schedule);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::{collections::VecDeque, sync::{Arc, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use crate::{Error, Role};
+
+///емкость канала подписчиков, отставший подписчик дочитывает пропущенное из буфера повтора
+const CHANNEL_CAPACITY: usize = 256;
+///событие, после которого клиент должен заново загрузить данные: часть событий уже вытеснена из буфера повтора
+pub const RESYNC_EVENT: &str = "resync";
+
+///Получатели события
+#[derive(Debug, Clone)]
+pub enum EventTarget
+{
+    All,
+    User(uuid::Uuid),
+    Role(Role)
+}
+
+///Событие для клиентов `/api/events`
+#[derive(Debug, Clone)]
+pub struct ServerEvent
+{
+    ///передается клиенту в поле `id`, клиент возвращает его в `Last-Event-ID` при переподключении
+    pub id: u64,
+    ///имя события в поле `event`
+    pub event: String,
+    ///данные события в json
+    pub data: String,
+    target: EventTarget
+}
+impl ServerEvent
+{
+    pub fn is_visible(&self, user_id: &uuid::Uuid, role: Role) -> bool
+    {
+        match &self.target
+        {
+            EventTarget::All => true,
+            EventTarget::User(id) => id == user_id,
+            EventTarget::Role(r) => *r == role
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SseMessage
+{
+    Event(Arc<ServerEvent>),
+    CloseSession(uuid::Uuid),
+    CloseUser(uuid::Uuid)
+}
+
+struct ReplayBuffer
+{
+    next_id: u64,
+    capacity: usize,
+    events: VecDeque<Arc<ServerEvent>>
+}
+impl ReplayBuffer
+{
+    ///События после `last_id`, `false` если часть событий после `last_id` уже вытеснена или `last_id` неизвестен
+    fn after(&self, last_id: u64) -> (Vec<Arc<ServerEvent>>, bool)
+    {
+        let first_id = self.events.front().map(|e| e.id).unwrap_or(self.next_id);
+        let complete = last_id < self.next_id && last_id + 1 >= first_id;
+        let events = self.events.iter().filter(|e| e.id > last_id).cloned().collect();
+        (events, complete)
+    }
+}
+
+///Рассылка событий Server-Sent Events подключенным клиентам: конкретному пользователю, роли или всем.
+/// Последние `sse_replay_buffer` событий хранятся для продолжения потока по `Last-Event-ID`
+#[derive(Clone)]
+pub struct SSEService
+{
+    sender: broadcast::Sender<SseMessage>,
+    replay: Arc<Mutex<ReplayBuffer>>
+}
+impl SSEService
+{
+    pub fn new(replay_capacity: usize) -> Self
+    {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        //идентификаторы начинаются со времени запуска, чтобы `Last-Event-ID` из прошлого запуска не совпал с новыми событиями
+        let next_id = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(1);
+        Self
+        {
+            sender,
+            replay: Arc::new(Mutex::new(ReplayBuffer
+            {
+                next_id,
+                capacity: replay_capacity.max(1),
+                events: VecDeque::new()
+            }))
+        }
+    }
+    ///Событие `event` с данными `data` в json, возвращает идентификатор события
+    pub fn publish<T: Serialize>(&self, target: EventTarget, event: &str, data: &T) -> Result<u64, Error>
+    {
+        let data = serde_json::to_string(data)?;
+        //событие попадает в буфер и канал под одной блокировкой, поэтому подписчик не получит его дважды
+        let mut replay = self.replay.lock().unwrap();
+        let event = Arc::new(ServerEvent
+        {
+            id: replay.next_id,
+            event: event.to_owned(),
+            data,
+            target
+        });
+        replay.next_id += 1;
+        if replay.events.len() >= replay.capacity
+        {
+            replay.events.pop_front();
+        }
+        replay.events.push_back(Arc::clone(&event));
+        let _ = self.sender.send(SseMessage::Event(Arc::clone(&event)));
+        Ok(event.id)
+    }
+    pub fn publish_to_user<T: Serialize>(&self, user_id: &uuid::Uuid, event: &str, data: &T) -> Result<u64, Error>
+    {
+        self.publish(EventTarget::User(*user_id), event, data)
+    }
+    pub fn publish_to_role<T: Serialize>(&self, role: Role, event: &str, data: &T) -> Result<u64, Error>
+    {
+        self.publish(EventTarget::Role(role), event, data)
+    }
+    pub fn publish_to_all<T: Serialize>(&self, event: &str, data: &T) -> Result<u64, Error>
+    {
+        self.publish(EventTarget::All, event, data)
+    }
+    ///Закрытие потоков сессии, вызывается при удалении сессии
+    pub fn close_session(&self, session_id: &uuid::Uuid)
+    {
+        let _ = self.sender.send(SseMessage::CloseSession(*session_id));
+    }
+    ///Закрытие всех потоков пользователя: выход из всех сессий, изменение роли или блокировка
+    pub fn close_user(&self, user_id: &uuid::Uuid)
+    {
+        let _ = self.sender.send(SseMessage::CloseUser(*user_id));
+    }
+    ///Количество открытых потоков
+    pub fn connections(&self) -> usize
+    {
+        self.sender.receiver_count()
+    }
+    ///Подписка сессии на события, видимые пользователю с ролью `role`.
+    /// Если передан `last_event_id` - сначала отдаются события из буфера после него
+    pub fn subscribe(&self, session_id: uuid::Uuid, user_id: uuid::Uuid, role: Role, last_event_id: Option<u64>) -> EventSubscription
+    {
+        let replay = self.replay.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let last_id = replay.next_id - 1;
+        let mut subscription = EventSubscription
+        {
+            session_id,
+            user_id,
+            role,
+            last_id,
+            pending: VecDeque::new(),
+            receiver,
+            replay: Arc::clone(&self.replay)
+        };
+        if let Some(last_event_id) = last_event_id
+        {
+            subscription.refill(&replay, last_event_id);
+        }
+        subscription
+    }
+}
+
+///Поток событий одной сессии
+pub struct EventSubscription
+{
+    session_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    role: Role,
+    ///последнее событие, полученное подпиской, в том числе не видимое пользователю
+    last_id: u64,
+    pending: VecDeque<Arc<ServerEvent>>,
+    receiver: broadcast::Receiver<SseMessage>,
+    replay: Arc<Mutex<ReplayBuffer>>
+}
+impl EventSubscription
+{
+    ///Следующее событие для клиента, `None` - поток нужно закрыть: сессия или пользователь отключены
+    pub async fn next(&mut self) -> Option<Arc<ServerEvent>>
+    {
+        loop
+        {
+            if let Some(event) = self.pending.pop_front()
+            {
+                return Some(event);
+            }
+            match self.receiver.recv().await
+            {
+                Ok(SseMessage::Event(event)) =>
+                {
+                    if event.id <= self.last_id
+                    {
+                        continue;
+                    }
+                    self.last_id = event.id;
+                    if event.is_visible(&self.user_id, self.role)
+                    {
+                        return Some(event);
+                    }
+                }
+                Ok(SseMessage::CloseSession(session_id)) if session_id == self.session_id => return None,
+                Ok(SseMessage::CloseUser(user_id)) if user_id == self.user_id => return None,
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) =>
+                {
+                    logger::warn!("Поток событий сессии {} отстал на {} сообщений, пропущенное берется из буфера повтора", self.session_id, skipped);
+                    let replay = Arc::clone(&self.replay);
+                    let replay = replay.lock().unwrap();
+                    self.refill(&replay, self.last_id);
+                }
+                Err(RecvError::Closed) => return None
+            }
+        }
+    }
+    fn refill(&mut self, replay: &ReplayBuffer, last_id: u64)
+    {
+        let (events, complete) = replay.after(last_id);
+        if !complete
+        {
+            //идентификатор перед первым событием в буфере - при следующем переподключении повтор будет полным
+            let first_id = events.first().map(|e| e.id).unwrap_or(replay.next_id);
+            self.pending.push_back(Arc::new(ServerEvent
+            {
+                id: first_id - 1,
+                event: RESYNC_EVENT.to_owned(),
+                data: "null".to_owned(),
+                target: EventTarget::User(self.user_id)
+            }));
+        }
+        for event in events
+        {
+            self.last_id = event.id;
+            if event.is_visible(&self.user_id, self.role)
+            {
+                self.pending.push_back(event);
+            }
+        }
+        self.last_id = self.last_id.max(replay.next_id - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::Role;
+    use super::{SSEService, RESYNC_EVENT};
+
+    #[tokio::test]
+    async fn test_targets()
+    {
+        let service = SSEService::new(10);
+        let user = uuid::Uuid::now_v7();
+        let session = uuid::Uuid::now_v7();
+        let mut subscription = service.subscribe(session, user, Role::User, None);
+        service.publish_to_user(&uuid::Uuid::now_v7(), "other", &1).unwrap();
+        service.publish_to_role(Role::Administrator, "admins", &2).unwrap();
+        let id = service.publish_to_role(Role::User, "users", &3).unwrap();
+        service.publish_to_user(&user, "mine", &"data").unwrap();
+        let event = subscription.next().await.unwrap();
+        assert_eq!((event.id, event.event.as_str(), event.data.as_str()), (id, "users", "3"));
+        assert_eq!(subscription.next().await.unwrap().data, "\"data\"");
+        service.close_session(&uuid::Uuid::now_v7());
+        service.publish_to_all("all", &()).unwrap();
+        assert_eq!(subscription.next().await.unwrap().event, "all");
+        service.close_session(&session);
+        assert!(subscription.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume()
+    {
+        let service = SSEService::new(3);
+        let user = uuid::Uuid::now_v7();
+        let first = service.publish_to_all("event", &1).unwrap();
+        let second = service.publish_to_all("event", &2).unwrap();
+        service.publish_to_all("event", &3).unwrap();
+        let mut subscription = service.subscribe(uuid::Uuid::now_v7(), user, Role::User, Some(first));
+        assert_eq!(subscription.next().await.unwrap().id, second);
+        assert_eq!(subscription.next().await.unwrap().data, "3");
+        //четвертое событие вытесняет первое, клиент его не получал и должен загрузить данные заново
+        service.publish_to_all("event", &4).unwrap();
+        let mut subscription = service.subscribe(uuid::Uuid::now_v7(), user, Role::User, Some(first - 1));
+        let resync = subscription.next().await.unwrap();
+        assert_eq!((resync.event.as_str(), resync.id), (RESYNC_EVENT, first));
+        assert_eq!(subscription.next().await.unwrap().id, second);
+        service.close_user(&user);
+        assert_eq!(subscription.next().await.unwrap().data, "3");
+        assert_eq!(subscription.next().await.unwrap().data, "4");
+        assert!(subscription.next().await.is_none());
+    }
+}
@@ -4,9 +4,10 @@ use hyper::StatusCode;
 use jwt_authentification::JWT;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
-use crate::{configuration::Configuration, db::{ContactDbo, DatabaseService, ISessionRepository, Session, SessionRepository, UserDbo}, Error, Role};
+use utoipa::ToSchema;
+use crate::{configuration::ConfigurationHandle, error::FieldError, i18n::{tr, tr_args}, ContactType, db::{ContactDbo, DatabaseService, ISessionRepository, ProfileDbo, Session, SessionRepository, UserDbo}, Error, Role};
 
-use super::JwtService;
+use super::{avatar_service::avatar_url, metrics_service::LoginMethod, JwtService, MetricsService, SSEService, WsService};
 
 pub trait IUserService
 {
@@ -17,52 +18,122 @@ pub struct UserService
 {
     database_service: Arc<DatabaseService>,
     jwt_service: JwtService,
-    configuration: Arc<Configuration>
+    configuration: ConfigurationHandle,
+    metrics_service: MetricsService,
+    ///потоки событий и соединения WebSocket удаленных сессий закрываются
+    sse_service: SSEService,
+    ws_service: WsService
 }
 impl UserService
 {
-    pub fn new(database_service: Arc<DatabaseService>, jwt_service: JwtService, config: Arc<Configuration>) -> Self
+    pub fn new(database_service: Arc<DatabaseService>, jwt_service: JwtService, config: ConfigurationHandle, metrics_service: MetricsService, sse_service: SSEService, ws_service: WsService) -> Self
     {
         Self
         {
             database_service,
             jwt_service,
             configuration: config,
+            metrics_service,
+            sse_service,
+            ws_service
         }
     }
     ///Result -> (user_information, refresh_key)
     /// запускаем все это из хэндлера маршрута
-    pub async fn login(&self, username: &str, password: &str, ip_addr: &str, fingerprint: &str, device: &str) -> Result<(UserInformation, Session), Error>
+    pub async fn login(&self, login: &str, password: &str, ip_addr: &str, fingerprint: &str, device: &str) -> Result<(UserInformation, Session), Error>
     {
-        let user_dbo = self.database_service.user_repository.login(username, password).await;
+        let result = self.login_with_password(login, password, ip_addr, fingerprint, device).await;
+        self.metrics_service.login(LoginMethod::Password, &result);
+        result
+    }
+    async fn login_with_password(&self, login: &str, password: &str, ip_addr: &str, fingerprint: &str, device: &str) -> Result<(UserInformation, Session), Error>
+    {
+        let username = self.resolve_username(login).await?;
+        let user_dbo = self.database_service.user_repository.login(&username, password).await;
         if let Ok(user) = user_dbo
         {
-            let session = self.database_service.session_repository.create_session(&user.id,  self.configuration.session_life_time, ip_addr, fingerprint, device).await;
-            if let Ok(s) = session
+            if !user.is_active
             {
-                let access_key = self.jwt_service.gen_key(&user.id, user.role, &user.audiences, self.configuration.access_key_lifetime).await;
-                let mut user: UserInformation = user.into();
-                if let Some(auth) = user.authorization_information.as_mut()
-                {
-                    auth.access_key = Some(access_key);
-                }
-                Ok((user, s))
+                logger::warn!("Попытка входа неактивного пользователя `{}`", &user.username);
+                return Err(Error::AuthError(tr_args("user_inactive", &[&user.username])));
             }
-            else 
+            self.start_session(user, ip_addr, fingerprint, device).await
+        }
+        else 
+        {
+            let error = user_dbo.err().unwrap();
+            logger::error!("{}", error.to_string());
+            Err(error)
+        }
+    }
+    ///Создание сессии и ключа доступа для уже прошедшего проверку пользователя
+    pub async fn start_session(&self, user: UserDbo, ip_addr: &str, fingerprint: &str, device: &str) -> Result<(UserInformation, Session), Error>
+    {
+        let cfg = self.configuration.get();
+        let session = self.database_service.session_repository.create_session(&user.id,  cfg.session_life_time, cfg.max_sessions_count, ip_addr, fingerprint, device).await;
+        if let Ok(s) = session
+        {
+            let access_key = self.jwt_service.gen_key(&user.id, user.role, &user.audiences, cfg.access_key_lifetime).await;
+            let mut user: UserInformation = user.into();
+            if let Some(auth) = user.authorization_information.as_mut()
             {
-                let error = session.err().unwrap();
-                logger::error!("{}", error.to_string());
-                Err(error)
+                auth.access_key = Some(access_key);
             }
+            Ok((user, s))
         }
         else 
         {
-            let error = user_dbo.err().unwrap();
+            let error = session.err().unwrap();
             logger::error!("{}", error.to_string());
             Err(error)
         }
     }
 
+    ///Имя пользователя по идентификатору входа, если включен `login_by_contact`
+    /// идентификатором может быть подтвержденный email или телефон.
+    /// Совпадение с именем пользователя имеет приоритет, если при этом идентификатор
+    /// является подтвержденным контактом другого пользователя вход запрещается как неоднозначный
+    pub async fn resolve_username(&self, login: &str) -> Result<String, Error>
+    {
+        if !self.configuration.get().login_by_contact
+        {
+            return Ok(login.to_owned());
+        }
+        let is_username = self.database_service.user_repository.username_is_busy(login).await?;
+        let mut owners: Vec<String> = Vec::new();
+        for contact_type in [ContactType::Email, ContactType::Phone]
+        {
+            if let Ok(contact) = contact_type.normalize(login)
+            {
+                if let Some(username) = self.database_service.user_repository.find_username_by_verified_contact(contact_type, &contact).await?
+                {
+                    if !owners.contains(&username)
+                    {
+                        owners.push(username);
+                    }
+                }
+            }
+        }
+        if is_username
+        {
+            if owners.iter().any(|o| o != login)
+            {
+                logger::warn!("Идентификатор `{}` совпадает с именем пользователя и контактом пользователя `{}`", login, owners.join(", "));
+                return Err(Error::AmbiguousLogin(login.to_owned()));
+            }
+            Ok(login.to_owned())
+        }
+        else if owners.len() > 1
+        {
+            logger::warn!("Идентификатор `{}` соответствует пользователям `{}`", login, owners.join(", "));
+            Err(Error::AmbiguousLogin(login.to_owned()))
+        }
+        else
+        {
+            Ok(owners.pop().unwrap_or(login.to_owned()))
+        }
+    }
+
     pub async fn change_password<'a,'s >(&'s self, user_id: &'a uuid::Uuid, old_password: &'a str, new_password: &'a str) -> Result<impl IntoResponse + use<'a>, Error>
     {
         let result = self.database_service.user_repository.update_password(user_id, old_password, new_password).await;
@@ -70,7 +141,7 @@ impl UserService
         {
             Ok((
                 StatusCode::OK,
-                "Пароль успешно изменен"
+                tr("password_changed")
             ))
         }
         else
@@ -81,15 +152,20 @@ impl UserService
         }
     }
 
-    pub async fn update_user_info(&self, user: UserInformation) -> Result<impl IntoResponse, Error>
+    pub async fn update_user_info(&self, mut user: UserInformation) -> Result<impl IntoResponse, Error>
     {
-        let user = user.into();
+        if let Some(profile) = user.profile.as_ref()
+        {
+            profile.validate()?;
+        }
+        normalize_contacts(&mut user.contacts)?;
+        let user = UserDbo::try_from(user)?;
         let result = self.database_service.user_repository.update_info(user).await;
         if let Ok(_) = result
         {
             Ok((
                 StatusCode::OK,
-                "Данные успешно обновлены"
+                tr("data_updated")
             ))
         }
         else
@@ -99,15 +175,24 @@ impl UserService
             Err(error)
         }
     }
-    pub async fn update_user_by_admin(&self, user: UserInformation) -> Result<impl IntoResponse, Error>
+    pub async fn update_user_by_admin(&self, mut user: UserInformation) -> Result<impl IntoResponse, Error>
     {
-        let user = user.into();
+        if let Some(profile) = user.profile.as_ref()
+        {
+            profile.validate()?;
+        }
+        normalize_contacts(&mut user.contacts)?;
+        let user = UserDbo::try_from(user)?;
+        let user_id = user.id;
         let result = self.database_service.user_repository.update(user).await;
         if let Ok(_) = result
         {
+            //роль или активность могли измениться, клиент переподключится к потоку событий с новыми правами
+            self.sse_service.close_user(&user_id);
+            self.ws_service.close_user(&user_id);
             Ok((
                 StatusCode::OK,
-                "Данные успешно обновлены"
+                tr("data_updated")
             ))
         }
         else
@@ -123,9 +208,11 @@ impl UserService
         let result = self.database_service.session_repository.delete_session(&session_id).await;
         if result.is_ok()
         {
+            self.sse_service.close_session(session_id);
+            self.ws_service.close_session(session_id);
             Ok((
                 StatusCode::OK,
-                format!("Вы успешно вышли из сессии {}", session_id),
+                tr_args("session_exited", &[&session_id.to_string()]),
             ))
         }
         else
@@ -142,9 +229,11 @@ impl UserService
         let result = self.database_service.session_repository.delete_all_sessions(&user_id).await;
         if result.is_ok()
         {
+            self.sse_service.close_user(user_id);
+            self.ws_service.close_user(user_id);
             Ok((
                 StatusCode::OK,
-                format!("Сессий успешно удалено: `{}`", result.unwrap()),
+                tr_args("sessions_deleted", &[&result.unwrap().to_string()]),
             ))
         }
         else
@@ -156,11 +245,21 @@ impl UserService
     }
     pub async fn update_access_key(&self, session: &Session, fingerprint: &str) -> Result<String, Error>
     {
+        let result = self.refresh_access_key(session, fingerprint).await;
+        self.metrics_service.token_refresh(&result);
+        result
+    }
+    async fn refresh_access_key(&self, session: &Session, fingerprint: &str) -> Result<String, Error>
+    {
+        let cfg = self.configuration.get();
         if &session.fingerprint != fingerprint
         {
             logger::error!("Ошибка, новый fingerprint {} не совпадает с отпечатком сеcсии {}", fingerprint, &session.fingerprint);
             let _ = self.database_service.session_repository.delete_session(&session.session_id).await;
-            return Err(Error::WrongFingerprintError(self.configuration.session_cookie_name.clone()));
+            self.sse_service.close_session(&session.session_id);
+            self.ws_service.close_session(&session.session_id);
+            self.metrics_service.fingerprint_revocation();
+            return Err(Error::WrongFingerprintError(cfg.session_cookie_name.clone()));
         }
 
         let user = self.database_service.user_repository.get_user(&session.user_id).await;
@@ -173,8 +272,8 @@ impl UserService
         else
         {
             let user = user.unwrap();
-            let new_access = self.jwt_service.gen_key(&user.id, user.role, &user.audiences, self.configuration.access_key_lifetime).await;
-            let result = self.database_service.session_repository.update_session_key(&session.session_id, self.configuration.access_key_lifetime).await;
+            let new_access = self.jwt_service.gen_key(&user.id, user.role, &user.audiences, cfg.access_key_lifetime).await;
+            let result = self.database_service.session_repository.update_session_key(&session.session_id, cfg.access_key_lifetime).await;
             if result.is_err()
             {
                 let error = result.err().unwrap();
@@ -190,22 +289,99 @@ impl UserService
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UserInformation
 {
     pub id: String,
     pub username: String,
+    ///url аватара, `None` если аватар не загружен
+    pub avatar: Option<String>,
+    pub profile: Option<Profile>,
     pub contacts: Vec<Contact>,
     pub authorization_information: Option<AuthorizationInformation>
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Profile
+{
+    pub last_name: String,
+    pub first_name: String,
+    pub middle_name: String,
+    pub position: String,
+    pub department: String,
+    pub timezone: String,
+    pub locale: String
+}
+impl Profile
+{
+    pub fn validate(&self) -> Result<(), Error>
+    {
+        let mut errors = Vec::new();
+        if self.timezone.parse::<chrono_tz::Tz>().is_err()
+        {
+            errors.push(FieldError::new("profile.timezone", tr_args("invalid_timezone", &[&self.timezone])));
+        }
+        //язык в формате `ru` или `ru-RU`
+        let mut parts = self.locale.split(['-', '_']);
+        let lang_valid = parts.next().is_some_and(|l| l.len() == 2 && l.chars().all(|c| c.is_ascii_lowercase()));
+        let region_valid = parts.next().is_none_or(|r| r.len() == 2 && r.chars().all(|c| c.is_ascii_alphabetic()));
+        if !lang_valid || !region_valid || parts.next().is_some()
+        {
+            errors.push(FieldError::new("profile.locale", tr_args("invalid_locale", &[&self.locale])));
+        }
+        if errors.is_empty()
+        {
+            Ok(())
+        }
+        else
+        {
+            Err(Error::InvalidFields(errors))
+        }
+    }
+}
+impl Into<Profile> for ProfileDbo
+{
+    fn into(self) -> Profile 
+    {
+        Profile
+        {
+            last_name: self.last_name,
+            first_name: self.first_name,
+            middle_name: self.middle_name,
+            position: self.position,
+            department: self.department,
+            timezone: self.timezone,
+            locale: self.locale
+        }
+    }
+}
+impl Into<ProfileDbo> for Profile
+{
+    fn into(self) -> ProfileDbo 
+    {
+        ProfileDbo
+        {
+            last_name: self.last_name.trim().to_owned(),
+            first_name: self.first_name.trim().to_owned(),
+            middle_name: self.middle_name.trim().to_owned(),
+            position: self.position.trim().to_owned(),
+            department: self.department.trim().to_owned(),
+            timezone: self.timezone,
+            locale: self.locale
+        }
+    }
+}
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Contact
 {
     pub id: String,
-    pub contact_type: String,
-    pub contact: String
+    pub contact_type: ContactType,
+    pub contact: String,
+    #[serde(default)]
+    pub verified: bool,
+    #[serde(default)]
+    pub is_primary: bool
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct AuthorizationInformation
 {
     pub is_active: bool,
@@ -221,20 +397,32 @@ impl Into<Contact> for ContactDbo
         { 
             id: self.id.to_string(),
             contact_type: self.contact_type,
-            contact: self.contact
+            contact: self.contact,
+            verified: self.verified,
+            is_primary: self.is_primary
         }
     }
 }
-fn to_contact_dbo(contact: Contact, user_id: &uuid::Uuid) -> ContactDbo
+fn normalize_contacts(contacts: &mut [Contact]) -> Result<(), Error>
 {
-    ContactDbo 
+    for c in contacts
+    {
+        c.contact = c.contact_type.normalize(&c.contact)?;
+    }
+    Ok(())
+}
+fn to_contact_dbo(contact: Contact, user_id: &uuid::Uuid) -> Result<ContactDbo, Error>
+{
+    let id = contact.id.parse().map_err(|_| Error::ValidationError(tr_args("invalid_contact_id", &[&contact.id])))?;
+    Ok(ContactDbo 
     { 
-        id: contact.id.parse().unwrap(),
+        id,
         user_id: *user_id,
         contact_type: contact.contact_type,
         verified: false,
+        is_primary: contact.is_primary,
         contact: contact.contact
-    }
+    })
 }
 
 
@@ -246,6 +434,8 @@ impl Into<UserInformation> for UserDbo
         UserInformation
         {
             id: self.id.to_string(),
+            avatar: self.avatar.as_ref().map(|v| avatar_url(&self.id, v)),
+            profile: self.profile.map(|p| p.into()),
             username: self.username,
             contacts: self.contacts.into_iter().map(|m| m.into()).collect(),
             authorization_information: Some(AuthorizationInformation 
@@ -259,39 +449,30 @@ impl Into<UserInformation> for UserDbo
     }
 }
 
-impl Into<UserDbo> for UserInformation
+impl TryFrom<UserInformation> for UserDbo
 {
-    fn into(self) -> UserDbo 
+    type Error = Error;
+    fn try_from(value: UserInformation) -> Result<Self, Self::Error> 
     {
-        let user_id: uuid::Uuid =  self.id.parse().unwrap();
-        if let Some(auth) = self.authorization_information
+        let user_id: uuid::Uuid = value.id.parse().map_err(|_| Error::ValidationError(tr_args("invalid_user_id", &[&value.id])))?;
+        let contacts = value.contacts.into_iter().map(|m| to_contact_dbo(m, &user_id)).collect::<Result<Vec<_>, _>>()?;
+        let (is_active, role, audiences) = match value.authorization_information
         {
-           
-            UserDbo
-            {
-                id: user_id,
-                username: self.username,
-                contacts: self.contacts.into_iter().map(|m| to_contact_dbo(m, &user_id)).collect(),
-                is_active: auth.is_active,
-                role: auth.role,
-                password: "".to_owned(),
-                audiences: auth.audiences
-            }
-        }
-        else 
+            Some(auth) => (auth.is_active, auth.role, auth.audiences),
+            None => (false, Role::NonPrivileged, Vec::new())
+        };
+        Ok(UserDbo
         {
-            UserDbo
-            {
-                id: user_id,
-                username: self.username,
-                contacts: self.contacts.into_iter().map(|m| to_contact_dbo(m, &user_id)).collect(),
-                is_active: false,
-                role: Role::NonPrivileged,
-                password: "".to_owned(),
-                audiences: Vec::new()
-            }
-        }
-       
+            id: user_id,
+            username: value.username,
+            contacts,
+            is_active,
+            role,
+            password: "".to_owned(),
+            audiences,
+            avatar: None,
+            profile: value.profile.map(|p| p.into())
+        })
     }
 }
 
@@ -306,4 +487,126 @@ pub fn log_result<T, E: IntoResponse + Display>(result: Result<T, E>) -> Result<
     {
         result
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests
+{
+    use crate::{configuration::Configuration, db::{ContactDbo, UserDbo}, state::{AppState, AppStateBuilder}, ContactType, Error, Role};
+    use super::{Contact, UserInformation};
+
+    async fn test_state(login_by_contact: bool) -> AppState
+    {
+        let mut cfg = Configuration::default();
+        cfg.login_by_contact = login_by_contact;
+        AppStateBuilder::new(cfg).in_memory().ephemeral_key().build().await.unwrap()
+    }
+    ///пользователь с email, при `verified` контакт подтверждается кодом
+    async fn create_user(state: &AppState, username: &str, email: &str, verified: bool)
+    {
+        let repository = &state.services.database_service.user_repository;
+        let id = uuid::Uuid::now_v7();
+        let contact_id = uuid::Uuid::now_v7();
+        let user = UserDbo
+        {
+            id,
+            username: username.to_owned(),
+            password: "password".to_owned(),
+            is_active: true,
+            role: Role::User,
+            audiences: Vec::new(),
+            avatar: None,
+            profile: None,
+            contacts: vec![ContactDbo
+            {
+                id: contact_id,
+                user_id: id,
+                contact_type: ContactType::Email,
+                verified: false,
+                is_primary: true,
+                contact: email.to_owned()
+            }]
+        };
+        repository.create(user).await.unwrap();
+        if verified
+        {
+            let code = repository.contact_verification_request(&contact_id).await.unwrap();
+            repository.contact_verification_accept(&contact_id, code).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_verified_contact()
+    {
+        let state = test_state(true).await;
+        create_user(&state, "verified", "verified@test.ru", true).await;
+        let service = &state.services.user_service;
+        assert_eq!(service.resolve_username("verified@test.ru").await.unwrap(), "verified");
+        assert_eq!(service.resolve_username("verified").await.unwrap(), "verified");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unverified_contact()
+    {
+        let state = test_state(true).await;
+        create_user(&state, "unverified", "unverified@test.ru", false).await;
+        let service = &state.services.user_service;
+        assert_eq!(service.resolve_username("unverified@test.ru").await.unwrap(), "unverified@test.ru");
+        assert!(service.login("unverified@test.ru", "password", "127.0.0.1", "fingerprint", "test").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_ambiguous_login()
+    {
+        let state = test_state(true).await;
+        create_user(&state, "owner", "shared@test.ru", true).await;
+        create_user(&state, "shared@test.ru", "other@test.ru", false).await;
+        let service = &state.services.user_service;
+        assert!(matches!(service.resolve_username("shared@test.ru").await, Err(Error::AmbiguousLogin(_))));
+        assert!(matches!(service.login("shared@test.ru", "password", "127.0.0.1", "fingerprint", "test").await, Err(Error::AmbiguousLogin(_))));
+    }
+
+    #[tokio::test]
+    async fn test_inactive_login()
+    {
+        let state = test_state(false).await;
+        create_user(&state, "inactive", "inactive@test.ru", false).await;
+        let repository = &state.services.database_service.user_repository;
+        let mut user = repository.get_user_by_username("inactive").await.unwrap();
+        user.is_active = false;
+        repository.update(user).await.unwrap();
+        let service = &state.services.user_service;
+        assert!(matches!(service.login("inactive", "password", "127.0.0.1", "fingerprint", "test").await, Err(Error::AuthError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_disabled()
+    {
+        let state = test_state(false).await;
+        create_user(&state, "verified", "verified@test.ru", true).await;
+        let service = &state.services.user_service;
+        assert_eq!(service.resolve_username("verified@test.ru").await.unwrap(), "verified@test.ru");
+        assert!(service.login("verified@test.ru", "password", "127.0.0.1", "fingerprint", "test").await.is_err());
+    }
+
+    #[test]
+    fn test_invalid_contact_id()
+    {
+        let user = UserInformation
+        {
+            id: uuid::Uuid::now_v7().to_string(),
+            username: "TestUser".to_owned(),
+            avatar: None,
+            profile: None,
+            contacts: vec![Contact
+            {
+                id: "not-a-uuid".to_owned(),
+                contact_type: ContactType::Email,
+                contact: "test@test.ru".to_owned(),
+                verified: false,
+                is_primary: true
+            }],
+            authorization_information: None
+        };
+        assert!(matches!(UserDbo::try_from(user), Err(Error::ValidationError(_))));
+    }
+}
@@ -0,0 +1,449 @@
+use std::{collections::HashSet, str::FromStr, sync::Arc};
+use rand::{distr::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::{db::{DatabaseService, UserDbo}, i18n::{tr, tr_args}, ContactType, Error, Role};
+use super::NotificationService;
+
+const GENERATED_PASSWORD_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFormat
+{
+    Csv,
+    Json
+}
+impl TransferFormat
+{
+    pub fn content_type(&self) -> &'static str
+    {
+        match self
+        {
+            TransferFormat::Csv => "text/csv; charset=utf-8",
+            TransferFormat::Json => "application/json"
+        }
+    }
+}
+impl FromStr for TransferFormat
+{
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s.to_lowercase().as_str()
+        {
+            "csv" => Ok(TransferFormat::Csv),
+            "json" => Ok(TransferFormat::Json),
+            _ => Err(Error::ImportError(tr_args("import_unknown_format", &[s])))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferContact
+{
+    pub contact_type: String,
+    pub contact: String
+}
+
+///Строка импорта/экспорта пользователей в формате json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserTransferRow
+{
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    pub role: String,
+    #[serde(default)]
+    pub is_active: bool,
+    #[serde(default)]
+    pub audiences: Vec<String>,
+    #[serde(default)]
+    pub contacts: Vec<TransferContact>
+}
+
+///Плоская строка csv, аудитории разделяются `;`, контакты записываются как `тип:контакт` через `;`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CsvUserRow
+{
+    username: String,
+    #[serde(default)]
+    password: String,
+    role: String,
+    #[serde(default)]
+    is_active: bool,
+    #[serde(default)]
+    audiences: String,
+    #[serde(default)]
+    contacts: String
+}
+impl Into<UserTransferRow> for CsvUserRow
+{
+    fn into(self) -> UserTransferRow
+    {
+        UserTransferRow
+        {
+            username: self.username,
+            password: if self.password.is_empty() { None } else { Some(self.password) },
+            role: self.role,
+            is_active: self.is_active,
+            audiences: split_list(&self.audiences).map(|a| a.to_owned()).collect(),
+            contacts: split_list(&self.contacts).map(|c|
+            {
+                if let Some((contact_type, contact)) = c.split_once(':')
+                {
+                    TransferContact { contact_type: contact_type.trim().to_owned(), contact: contact.trim().to_owned() }
+                }
+                else
+                {
+                    TransferContact { contact_type: String::new(), contact: c.to_owned() }
+                }
+            }).collect()
+        }
+    }
+}
+impl Into<CsvUserRow> for UserTransferRow
+{
+    fn into(self) -> CsvUserRow
+    {
+        CsvUserRow
+        {
+            username: self.username,
+            password: self.password.unwrap_or_default(),
+            role: self.role,
+            is_active: self.is_active,
+            audiences: self.audiences.join(";"),
+            contacts: self.contacts.into_iter().map(|c| [c.contact_type, ":".to_owned(), c.contact].concat()).collect::<Vec<String>>().join(";")
+        }
+    }
+}
+impl Into<UserTransferRow> for UserDbo
+{
+    fn into(self) -> UserTransferRow
+    {
+        UserTransferRow
+        {
+            username: self.username,
+            password: None,
+            role: self.role.to_string(),
+            is_active: self.is_active,
+            audiences: self.audiences,
+            contacts: self.contacts.into_iter().map(|c| TransferContact { contact_type: c.contact_type.to_string(), contact: c.contact }).collect()
+        }
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str>
+{
+    value.split(';').map(|v| v.trim()).filter(|v| !v.is_empty())
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportRowReport
+{
+    ///номер строки начиная с 1 (без учета заголовка csv)
+    pub row: usize,
+    pub username: String,
+    pub errors: Vec<String>,
+    ///сгенерированный пароль возвращается только если его не удалось доставить пользователю
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_password: Option<String>,
+    pub password_delivered: bool
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportReport
+{
+    pub dry_run: bool,
+    pub total: usize,
+    pub valid: usize,
+    pub created: usize,
+    pub rows: Vec<ImportRowReport>
+}
+
+pub struct UserTransferService
+{
+    database_service: Arc<DatabaseService>,
+    notification_service: NotificationService
+}
+impl UserTransferService
+{
+    pub fn new(database_service: Arc<DatabaseService>, notification_service: NotificationService) -> Self
+    {
+        Self
+        {
+            database_service,
+            notification_service
+        }
+    }
+
+    pub fn parse(data: &str, format: TransferFormat) -> Result<Vec<UserTransferRow>, Error>
+    {
+        match format
+        {
+            TransferFormat::Json =>
+            {
+                let rows: Vec<UserTransferRow> = serde_json::from_str(data)?;
+                Ok(rows)
+            },
+            TransferFormat::Csv =>
+            {
+                let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(data.as_bytes());
+                let mut rows = Vec::new();
+                for (i, r) in reader.deserialize::<CsvUserRow>().enumerate()
+                {
+                    let row = r.map_err(|e| Error::ImportError(tr_args("import_row_error", &[&(i + 1).to_string(), &e.to_string()])))?;
+                    rows.push(row.into());
+                }
+                Ok(rows)
+            }
+        }
+    }
+
+    ///Импорт пользователей, при `dry_run` выполняется только проверка и возвращается отчет.
+    /// Строки с ошибками пропускаются, остальные пользователи создаются в одной транзакции:
+    /// при ошибке базы данных не создается ни один пользователь. Пароли рассылаются после сохранения
+    pub async fn import(&self, data: &str, format: TransferFormat, dry_run: bool) -> Result<ImportReport, Error>
+    {
+        let rows = Self::parse(data, format)?;
+        let mut report = ImportReport
+        {
+            dry_run,
+            total: rows.len(),
+            valid: 0,
+            created: 0,
+            rows: Vec::with_capacity(rows.len())
+        };
+        let mut usernames: HashSet<String> = HashSet::new();
+        let mut users = Vec::new();
+        for (i, row) in rows.into_iter().enumerate()
+        {
+            let row_report = ImportRowReport
+            {
+                row: i + 1,
+                username: row.username.clone(),
+                errors: self.validate(&row, &usernames).await?,
+                generated_password: None,
+                password_delivered: false
+            };
+            usernames.insert(row.username.clone());
+            if row_report.errors.is_empty()
+            {
+                report.valid += 1;
+                if !dry_run
+                {
+                    users.push((report.rows.len(), new_user(row)?));
+                }
+            }
+            report.rows.push(row_report);
+        }
+        if !users.is_empty()
+        {
+            let dbos: Vec<UserDbo> = users.iter().map(|(_, u)| u.user.clone()).collect();
+            self.database_service.user_repository.create_many(&dbos).await?;
+            report.created = users.len();
+            for (i, user) in users
+            {
+                self.deliver_password(user, &mut report.rows[i]).await;
+            }
+        }
+        logger::info!("Импорт пользователей: всего `{}`, без ошибок `{}`, создано `{}`", report.total, report.valid, report.created);
+        Ok(report)
+    }
+
+    async fn validate(&self, row: &UserTransferRow, usernames: &HashSet<String>) -> Result<Vec<String>, Error>
+    {
+        let mut errors = Vec::new();
+        if row.username.trim().is_empty()
+        {
+            errors.push(tr("import_username_missing"));
+        }
+        else if usernames.contains(&row.username)
+        {
+            errors.push(tr_args("import_username_repeated", &[&row.username]));
+        }
+        else if self.database_service.user_repository.username_is_busy(&row.username).await?
+        {
+            errors.push(tr_args("username_busy", &[&row.username]));
+        }
+        if Role::try_parse(&row.role).is_none()
+        {
+            errors.push(tr_args("unknown_role", &[&row.role]));
+        }
+        for c in &row.contacts
+        {
+            match c.contact_type.parse::<ContactType>().and_then(|t| t.normalize(&c.contact))
+            {
+                Err(Error::ValidationError(message)) => errors.push(message),
+                Err(e) => errors.push(e.to_string()),
+                Ok(_) => ()
+            }
+        }
+        if let Some(password) = row.password.as_ref()
+        {
+            if password.len() < 6
+            {
+                errors.push(tr_args("password_too_short", &["6"]));
+            }
+        }
+        Ok(errors)
+    }
+
+    ///Сгенерированный пароль отправляется на первый контакт пользователя, если отправить не удалось - возвращается в отчете
+    async fn deliver_password(&self, user: ImportedUser, report: &mut ImportRowReport)
+    {
+        if !user.generated
+        {
+            return;
+        }
+        report.password_delivered = if let Some(contact) = user.user.contacts.first()
+        {
+            let message = tr_args("account_created_notification", &[&report.username, &user.user.password]);
+            self.notification_service.send(contact, &message).await.is_ok()
+        }
+        else
+        {
+            false
+        };
+        if !report.password_delivered
+        {
+            report.generated_password = Some(user.user.password);
+        }
+    }
+
+    pub async fn export(&self, format: TransferFormat) -> Result<String, Error>
+    {
+        let users = self.database_service.user_repository.get_users().await?;
+        let rows: Vec<UserTransferRow> = users.into_iter().map(|u| u.into()).collect();
+        match format
+        {
+            TransferFormat::Json => Ok(serde_json::to_string_pretty(&rows)?),
+            TransferFormat::Csv =>
+            {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for r in rows
+                {
+                    let row: CsvUserRow = r.into();
+                    writer.serialize(row).map_err(|e| Error::ImportError(e.to_string()))?;
+                }
+                let bytes = writer.into_inner().map_err(|e| Error::ImportError(e.to_string()))?;
+                Ok(String::from_utf8_lossy(&bytes).into_owned())
+            }
+        }
+    }
+}
+
+///Пользователь из строки импорта, прошедшей проверку
+struct ImportedUser
+{
+    user: UserDbo,
+    ///пароль не указан в файле и сгенерирован
+    generated: bool
+}
+
+fn new_user(row: UserTransferRow) -> Result<ImportedUser, Error>
+{
+    let (password, generated) = if let Some(p) = row.password
+    {
+        (p, false)
+    }
+    else
+    {
+        (generate_password(), true)
+    };
+    let mut user = UserDbo
+    {
+        id: uuid::Uuid::now_v7(),
+        username: row.username,
+        password,
+        is_active: row.is_active,
+        role: Role::try_parse(&row.role).unwrap(),
+        audiences: row.audiences,
+        avatar: None,
+        profile: None,
+        contacts: Vec::new()
+    };
+    for c in row.contacts
+    {
+        //строка уже прошла проверку в validate
+        let contact_type: ContactType = c.contact_type.parse()?;
+        let contact = contact_type.normalize(&c.contact)?;
+        user = user.add_contact(contact_type, &contact);
+    }
+    Ok(ImportedUser { user, generated })
+}
+
+fn generate_password() -> String
+{
+    rand::rng()
+    .sample_iter(&Alphanumeric)
+    .take(GENERATED_PASSWORD_LEN)
+    .map(char::from)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::sync::Arc;
+    use crate::{configuration::Configuration, db::DatabaseService, services::NotificationService};
+    use super::{TransferFormat, UserTransferService};
+
+    async fn test_service() -> UserTransferService
+    {
+        let database_service = Arc::new(DatabaseService::in_memory(&Configuration::default()).await.unwrap());
+        UserTransferService::new(database_service, NotificationService::new())
+    }
+
+    #[test]
+    fn test_parse_csv()
+    {
+        let data = "username,password,role,is_active,audiences,contacts\nIvanov,,User,true,www.111.ru;www.222.ru,e-mail:ivanov@test.ru;мобильный телефон:+7 (900) 111-22-33";
+        let rows = UserTransferService::parse(data, TransferFormat::Csv).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].password, None);
+        assert_eq!(rows[0].audiences.len(), 2);
+        assert_eq!(rows[0].contacts[1].contact_type, "мобильный телефон");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run()
+    {
+        let service = test_service().await;
+        let data = "username,password,role,is_active,audiences,contacts\nIvanov,password1,User,true,,e-mail:ivanov@test.ru\nPetrov,password2,Unknown,true,,\nIvanov,password3,User,false,,";
+        let report = service.import(data, TransferFormat::Csv, true).await.unwrap();
+        assert_eq!((report.total, report.valid, report.created), (3, 1, 0));
+        assert!(report.rows[0].errors.is_empty());
+        assert_eq!(report.rows[1].errors.len(), 1);
+        assert_eq!(report.rows[2].errors.len(), 1);
+        assert!(!service.database_service.user_repository.username_is_busy("Ivanov").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_import_export()
+    {
+        let service = test_service().await;
+        let data = r#"[
+            {"username": "Ivanov", "password": "password1", "role": "User", "is_active": true, "audiences": ["www.111.ru"], "contacts": [{"contact_type": "e-mail", "contact": "Ivanov@Test.ru"}]},
+            {"username": "Petrov", "role": "Administrator", "is_active": false, "contacts": [{"contact_type": "phone", "contact": "8 (900) 111-22-33"}]}
+        ]"#;
+        let report = service.import(data, TransferFormat::Json, false).await.unwrap();
+        assert_eq!((report.total, report.valid, report.created), (2, 2, 2));
+        //пароль Петрова сгенерирован, отправитель по умолчанию его не доставляет - пароль возвращается в отчете
+        assert!(!report.rows[1].password_delivered);
+        assert!(report.rows[1].generated_password.is_some());
+        assert!(report.rows[0].generated_password.is_none());
+        let exported = service.export(TransferFormat::Csv).await.unwrap();
+        let rows = UserTransferService::parse(&exported, TransferFormat::Csv).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].username.as_str(), rows[0].is_active, rows[0].audiences.len()), ("Ivanov", true, 1));
+        assert_eq!((rows[0].contacts[0].contact_type.as_str(), rows[0].contacts[0].contact.as_str()), ("email", "ivanov@test.ru"));
+        assert_eq!((rows[1].username.as_str(), rows[1].role.as_str(), rows[1].is_active), ("Petrov", "Administrator", false));
+        assert_eq!(rows[1].contacts[0].contact, "+79001112233");
+        //повторный импорт экспорта не создает дубликатов
+        let report = service.import(&exported, TransferFormat::Csv, false).await.unwrap();
+        assert_eq!((report.valid, report.created), (0, 0));
+    }
+}
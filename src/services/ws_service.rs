@@ -0,0 +1,405 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, fmt::Display, str::FromStr, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use crate::{error::Problem, i18n::tr_args, Error, Role};
+
+///емкость канала рассылки, отставшее соединение получает `resync`
+const CHANNEL_CAPACITY: usize = 256;
+///подписок на одно соединение
+pub const MAX_SUBSCRIPTIONS: usize = 64;
+
+///Объект планировщика, на изменения которого подписывается клиент: `project:<id>` или `board:<id>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Topic
+{
+    Project(uuid::Uuid),
+    Board(uuid::Uuid)
+}
+impl Display for Topic
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self
+        {
+            Topic::Project(id) => write!(f, "project:{}", id),
+            Topic::Board(id) => write!(f, "board:{}", id)
+        }
+    }
+}
+impl FromStr for Topic
+{
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let unknown = || Error::ValidationError(tr_args("ws_unknown_topic", &[s]));
+        let (kind, id) = s.split_once(':').ok_or_else(unknown)?;
+        let id = id.parse::<uuid::Uuid>().map_err(|_| unknown())?;
+        match kind
+        {
+            "project" => Ok(Topic::Project(id)),
+            "board" => Ok(Topic::Board(id)),
+            _ => Err(unknown())
+        }
+    }
+}
+impl TryFrom<String> for Topic
+{
+    type Error = Error;
+    fn try_from(value: String) -> Result<Self, Self::Error>
+    {
+        value.parse()
+    }
+}
+impl From<Topic> for String
+{
+    fn from(value: Topic) -> Self
+    {
+        value.to_string()
+    }
+}
+
+///Пользователь, просматривающий проект или доску. Пока нет проверки доступа к отдельным проектам,
+/// подписаться может любой пользователь, поэтому имена зрителей не передаются
+#[derive(Debug, Clone, Serialize)]
+pub struct Viewer
+{
+    pub user_id: uuid::Uuid
+}
+
+///Сообщения клиента
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage
+{
+    Subscribe { topic: String },
+    Unsubscribe { topic: String },
+    ///новый ключ доступа после `/api/auth/update_key`, права соединения проверяются заново
+    Refresh { access_key: String }
+}
+
+///Сообщения сервера
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage
+{
+    Subscribed { topic: Topic, viewers: Vec<Viewer> },
+    Unsubscribed { topic: Topic },
+    ///изменение объекта, отправленное через [`WsService::publish`]
+    Event { topic: Topic, event: String, data: serde_json::Value },
+    ///текущие зрители после подключения или отключения одного из них
+    Presence { topic: Topic, viewers: Vec<Viewer> },
+    Refreshed,
+    ///часть сообщений потеряна, клиент должен заново загрузить данные подписок
+    Resync,
+    Error { problem: Problem }
+}
+impl ServerMessage
+{
+    pub fn to_json(&self) -> String
+    {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+impl From<Error> for ServerMessage
+{
+    fn from(value: Error) -> Self
+    {
+        ServerMessage::Error { problem: value.to_problem() }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum WsBroadcast
+{
+    Topic(Topic, Arc<str>),
+    CloseSession(uuid::Uuid),
+    CloseUser(uuid::Uuid)
+}
+
+#[derive(Default)]
+struct Presence
+{
+    viewers: HashMap<u64, Viewer>,
+    topics: HashMap<Topic, HashSet<u64>>
+}
+impl Presence
+{
+    ///один пользователь с нескольких вкладок отображается один раз
+    fn viewers(&self, topic: &Topic) -> Vec<Viewer>
+    {
+        let mut viewers = BTreeMap::new();
+        for id in self.topics.get(topic).into_iter().flatten()
+        {
+            if let Some(viewer) = self.viewers.get(id)
+            {
+                viewers.insert(viewer.user_id, viewer.clone());
+            }
+        }
+        viewers.into_values().collect()
+    }
+}
+
+///Двусторонний канал `/ws`: подписки на проекты и доски, рассылка изменений и присутствие пользователей
+#[derive(Clone)]
+pub struct WsService
+{
+    sender: broadcast::Sender<WsBroadcast>,
+    presence: Arc<Mutex<Presence>>,
+    next_id: Arc<AtomicU64>
+}
+impl WsService
+{
+    pub fn new() -> Self
+    {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self
+        {
+            sender,
+            presence: Arc::new(Mutex::new(Presence::default())),
+            next_id: Arc::new(AtomicU64::new(1))
+        }
+    }
+    ///Изменение объекта `topic` для всех подписчиков. Обработчиков REST для проектов и досок пока нет,
+    /// поэтому вызовов вне тестов нет: их нужно добавить вместе с обработчиками, после сохранения изменений
+    pub fn publish<T: Serialize>(&self, topic: &Topic, event: &str, data: &T) -> Result<(), Error>
+    {
+        let message = ServerMessage::Event
+        {
+            topic: *topic,
+            event: event.to_owned(),
+            data: serde_json::to_value(data)?
+        };
+        self.broadcast(topic, &message);
+        Ok(())
+    }
+    ///Пользователи, которые сейчас просматривают `topic`
+    pub fn viewers(&self, topic: &Topic) -> Vec<Viewer>
+    {
+        self.presence.lock().unwrap().viewers(topic)
+    }
+    ///Закрытие соединений сессии, вызывается при удалении сессии
+    pub fn close_session(&self, session_id: &uuid::Uuid)
+    {
+        let _ = self.sender.send(WsBroadcast::CloseSession(*session_id));
+    }
+    ///Закрытие всех соединений пользователя
+    pub fn close_user(&self, user_id: &uuid::Uuid)
+    {
+        let _ = self.sender.send(WsBroadcast::CloseUser(*user_id));
+    }
+    ///Количество открытых соединений
+    pub fn connections(&self) -> usize
+    {
+        self.sender.receiver_count()
+    }
+    pub fn connect(&self, session_id: uuid::Uuid, viewer: Viewer, role: Role) -> WsConnection
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.presence.lock().unwrap().viewers.insert(id, viewer.clone());
+        WsConnection
+        {
+            id,
+            session_id,
+            user_id: viewer.user_id,
+            role,
+            topics: HashSet::new(),
+            receiver: self.sender.subscribe(),
+            service: self.clone()
+        }
+    }
+    fn broadcast(&self, topic: &Topic, message: &ServerMessage)
+    {
+        let _ = self.sender.send(WsBroadcast::Topic(*topic, message.to_json().into()));
+    }
+    fn broadcast_presence(&self, topic: &Topic, viewers: Vec<Viewer>)
+    {
+        self.broadcast(topic, &ServerMessage::Presence { topic: *topic, viewers });
+    }
+}
+
+///Соединение одного клиента, при удалении клиент пропадает из списков зрителей
+pub struct WsConnection
+{
+    id: u64,
+    session_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    role: Role,
+    topics: HashSet<Topic>,
+    receiver: broadcast::Receiver<WsBroadcast>,
+    service: WsService
+}
+impl WsConnection
+{
+    pub fn subscribe(&mut self, topic: &str) -> Result<ServerMessage, Error>
+    {
+        let topic: Topic = topic.parse()?;
+        if !can_subscribe(self.role, &topic)
+        {
+            return Err(Error::AuthError(tr_args("ws_topic_forbidden", &[&topic.to_string()])));
+        }
+        if !self.topics.contains(&topic) && self.topics.len() >= MAX_SUBSCRIPTIONS
+        {
+            return Err(Error::ValidationError(tr_args("ws_too_many_subscriptions", &[&MAX_SUBSCRIPTIONS.to_string()])));
+        }
+        self.topics.insert(topic);
+        let viewers =
+        {
+            let mut presence = self.service.presence.lock().unwrap();
+            presence.topics.entry(topic).or_default().insert(self.id);
+            presence.viewers(&topic)
+        };
+        self.service.broadcast_presence(&topic, viewers.clone());
+        Ok(ServerMessage::Subscribed { topic, viewers })
+    }
+    pub fn unsubscribe(&mut self, topic: &str) -> Result<ServerMessage, Error>
+    {
+        let topic: Topic = topic.parse()?;
+        if self.topics.remove(&topic)
+        {
+            self.leave(&topic);
+        }
+        Ok(ServerMessage::Unsubscribed { topic })
+    }
+    ///Роль из обновленного ключа доступа, подписки, недоступные с новой ролью, снимаются
+    pub fn set_role(&mut self, role: Role) -> Vec<Topic>
+    {
+        self.role = role;
+        let revoked: Vec<Topic> = self.topics.iter().filter(|t| !can_subscribe(role, t)).copied().collect();
+        for topic in &revoked
+        {
+            self.topics.remove(topic);
+            self.leave(topic);
+        }
+        revoked
+    }
+    ///Следующее сообщение для клиента, `None` - соединение нужно закрыть: сессия или пользователь отключены
+    pub async fn recv(&mut self) -> Option<Arc<str>>
+    {
+        loop
+        {
+            match self.receiver.recv().await
+            {
+                Ok(WsBroadcast::Topic(topic, message)) if self.topics.contains(&topic) => return Some(message),
+                Ok(WsBroadcast::CloseSession(session_id)) if session_id == self.session_id => return None,
+                Ok(WsBroadcast::CloseUser(user_id)) if user_id == self.user_id => return None,
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) =>
+                {
+                    logger::warn!("Соединение {} сессии {} отстало на {} сообщений", self.id, self.session_id, skipped);
+                    return Some(ServerMessage::Resync.to_json().into());
+                }
+                Err(RecvError::Closed) => return None
+            }
+        }
+    }
+    fn leave(&self, topic: &Topic)
+    {
+        let viewers =
+        {
+            let mut presence = self.service.presence.lock().unwrap();
+            if let Some(connections) = presence.topics.get_mut(topic)
+            {
+                connections.remove(&self.id);
+                if connections.is_empty()
+                {
+                    presence.topics.remove(topic);
+                }
+            }
+            presence.viewers(topic)
+        };
+        self.service.broadcast_presence(topic, viewers);
+    }
+}
+impl Drop for WsConnection
+{
+    fn drop(&mut self)
+    {
+        for topic in &self.topics
+        {
+            self.leave(topic);
+        }
+        self.service.presence.lock().unwrap().viewers.remove(&self.id);
+    }
+}
+
+///Модели доступа к проектам пока нет, подписываться могут пользователи и администраторы
+fn can_subscribe(role: Role, _topic: &Topic) -> bool
+{
+    matches!(role, Role::User | Role::Administrator)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::Role;
+    use super::{Topic, Viewer, WsService};
+
+    fn viewer() -> Viewer
+    {
+        Viewer
+        {
+            user_id: uuid::Uuid::now_v7()
+        }
+    }
+
+    #[test]
+    fn test_topic()
+    {
+        let id = uuid::Uuid::now_v7();
+        let topic: Topic = ["board:", &id.to_string()].concat().parse().unwrap();
+        assert_eq!(topic, Topic::Board(id));
+        assert_eq!(serde_json::to_value(topic).unwrap(), ["board:", &id.to_string()].concat());
+        assert!("task:1".parse::<Topic>().is_err());
+        assert!("project:1".parse::<Topic>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_presence()
+    {
+        let service = WsService::new();
+        let board = Topic::Board(uuid::Uuid::now_v7());
+        let topic = board.to_string();
+        let first = viewer();
+        let mut a = service.connect(uuid::Uuid::now_v7(), first.clone(), Role::User);
+        let mut b = service.connect(uuid::Uuid::now_v7(), viewer(), Role::Administrator);
+        a.subscribe(&topic).unwrap();
+        //вторая вкладка того же пользователя не дублирует зрителя
+        let mut c = service.connect(uuid::Uuid::now_v7(), first.clone(), Role::User);
+        c.subscribe(&topic).unwrap();
+        b.subscribe(&topic).unwrap();
+        assert_eq!(service.viewers(&board).len(), 2);
+        let presence = a.recv().await.unwrap();
+        assert!(presence.contains("\"presence\""));
+        //в списке зрителей только идентификаторы
+        assert!(presence.contains(&first.user_id.to_string()));
+        assert!(!presence.contains("username"));
+        drop(c);
+        drop(b);
+        assert_eq!(service.viewers(&board).len(), 1);
+        assert!(a.set_role(Role::NonPrivileged).contains(&board));
+        assert!(service.viewers(&board).is_empty());
+        assert!(a.subscribe(&topic).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_publish()
+    {
+        let service = WsService::new();
+        let board = Topic::Board(uuid::Uuid::now_v7());
+        let session_id = uuid::Uuid::now_v7();
+        let mut connection = service.connect(session_id, viewer(), Role::User);
+        service.publish(&board, "task_moved", &1).unwrap();
+        connection.subscribe(&board.to_string()).unwrap();
+        service.publish(&Topic::Project(uuid::Uuid::now_v7()), "other", &2).unwrap();
+        service.publish(&board, "task_moved", &3).unwrap();
+        let presence = connection.recv().await.unwrap();
+        assert!(presence.contains("\"presence\""));
+        let event: serde_json::Value = serde_json::from_str(&connection.recv().await.unwrap()).unwrap();
+        assert_eq!(event["type"], "event");
+        assert_eq!(event["event"], "task_moved");
+        assert_eq!(event["data"], 3);
+        service.close_session(&session_id);
+        assert!(connection.recv().await.is_none());
+    }
+}
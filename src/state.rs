@@ -2,7 +2,9 @@ use std::sync::Arc;
 use jwt_authentification::JWT;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
-use crate::{configuration::Configuration, db::{self, DatabaseService, IUserRepository, UserRepository}, services::{self, JwtService, UserService}};
+use tokio_util::sync::CancellationToken;
+use sqlx::SqlitePool;
+use crate::{configuration::{Configuration, ConfigurationHandle}, db::{self, DatabaseService, ISessionRepository, IUserRepository, UserRepository}, services::{self, AvatarService, BackupService, JobService, MetricsService, RateLimitService, SSEService, WsService, ContactService, JwtService, PasswordlessService, NotificationService, UserService, UserTransferService}};
 
 pub struct Services
 {
@@ -10,42 +12,171 @@ pub struct Services
     pub database_service: Arc<crate::db::DatabaseService>,
     ///JWT сервис предоставляет методы для валидации ключа доступа и создания нового ключа доступа
     pub jwt_service: JwtService,
-    pub user_service: UserService
-    // Сервис предоставляет доступ к отправке сообщений Server Send Events всем подключенным клиентам
-    //pub sse_service: SSEService,
+    pub user_service: UserService,
+    ///Доставка сообщений пользователям (пароли, коды верификации)
+    pub notification_service: NotificationService,
+    ///Импорт и экспорт пользователей
+    pub user_transfer_service: UserTransferService,
+    ///Загрузка и хранение аватаров пользователей
+    pub avatar_service: AvatarService,
+    ///Добавление, удаление и подтверждение контактов пользователя
+    pub contact_service: ContactService,
+    ///Вход по одноразовому коду
+    pub passwordless_service: PasswordlessService,
+    ///Метрики Prometheus
+    pub metrics_service: MetricsService,
+    ///Ограничение частоты запросов
+    pub rate_limit_service: RateLimitService,
+    ///Резервное копирование баз данных
+    pub backup_service: BackupService,
+    ///Периодические и отложенные задачи: очистка, резервное копирование
+    pub job_service: JobService,
+    /// Сервис предоставляет доступ к отправке сообщений Server Send Events всем подключенным клиентам
+    pub sse_service: SSEService,
+    ///Канал WebSocket: подписки на проекты и доски, присутствие пользователей
+    pub ws_service: WsService
 }
 
 pub struct AppState
 {
-    pub configuration: Arc<Configuration>,
+    ///снимок настроек получается через `configuration.get()`, настройки могут быть перезагружены во время работы
+    pub configuration: ConfigurationHandle,
     pub services: Services,
-    pub errors: Mutex<Vec<String>>
+    pub errors: Mutex<Vec<String>>,
+    ///отменяется при остановке сервера, фоновые задачи должны завершаться по этому сигналу
+    pub shutdown: CancellationToken
 }
 
 impl AppState
 {
     pub async fn initialize() -> Result<AppState, crate::Error>
     {
-        let cfg = Arc::new(Configuration::load());
-        let database_service = Arc::new(super::db::DatabaseService::new(cfg.max_sessions_count).await?);
-        let jwt_service = JwtService::new();
-        let user_service = UserService::new(database_service.clone(), jwt_service.clone(), cfg.clone());
+        Self::initialize_with(ConfigurationHandle::new(Configuration::load()?)).await
+    }
+    pub async fn initialize_with(configuration: ConfigurationHandle) -> Result<AppState, crate::Error>
+    {
+        AppStateBuilder::with_handle(configuration).build().await
+    }
+    pub fn get_services(&self) -> &Services
+    {
+        &self.services
+    }
+}
+
+///Сборка состояния приложения с заменой отдельных частей: баз данных, репозиториев и ключа подписи.
+/// Без замен собирается то же состояние что и `AppState::initialize_with`
+pub struct AppStateBuilder
+{
+    configuration: ConfigurationHandle,
+    in_memory: bool,
+    pools: Option<(Arc<SqlitePool>, Arc<SqlitePool>)>,
+    user_repository: Option<Box<dyn IUserRepository + Sync + Send>>,
+    session_repository: Option<Box<dyn ISessionRepository + Sync + Send>>,
+    jwt_service: Option<JwtService>
+}
+impl AppStateBuilder
+{
+    pub fn new(configuration: Configuration) -> Self
+    {
+        Self::with_handle(ConfigurationHandle::new(configuration))
+    }
+    pub fn with_handle(configuration: ConfigurationHandle) -> Self
+    {
+        Self
+        {
+            configuration,
+            in_memory: false,
+            pools: None,
+            user_repository: None,
+            session_repository: None,
+            jwt_service: None
+        }
+    }
+    ///Основная база и сессии в одной базе данных в памяти, пути к файлам баз из настроек не используются
+    pub fn in_memory(mut self) -> Self
+    {
+        self.in_memory = true;
+        self
+    }
+    ///Уже открытые пулы, для общей базы передается один и тот же пул
+    pub fn pools(mut self, pool: Arc<SqlitePool>, sessions_pool: Arc<SqlitePool>) -> Self
+    {
+        self.pools = Some((pool, sessions_pool));
+        self
+    }
+    pub fn user_repository(mut self, repository: Box<dyn IUserRepository + Sync + Send>) -> Self
+    {
+        self.user_repository = Some(repository);
+        self
+    }
+    pub fn session_repository(mut self, repository: Box<dyn ISessionRepository + Sync + Send>) -> Self
+    {
+        self.session_repository = Some(repository);
+        self
+    }
+    ///Временный ключ подписи вместо файла `key_path`
+    pub fn ephemeral_key(mut self) -> Self
+    {
+        self.jwt_service = Some(JwtService::ephemeral());
+        self
+    }
+    pub async fn build(self) -> Result<AppState, crate::Error>
+    {
+        let configuration = self.configuration;
+        let cfg = configuration.get();
+        cfg.validate()?;
+        let mut database_service = match self.pools
+        {
+            Some((pool, sessions_pool)) => DatabaseService::with_pools(&cfg, pool, sessions_pool).await?,
+            None if self.in_memory => DatabaseService::in_memory(&cfg).await?,
+            None => DatabaseService::new(&cfg).await?
+        };
+        if let Some(repository) = self.user_repository
+        {
+            database_service.user_repository = repository;
+        }
+        if let Some(repository) = self.session_repository
+        {
+            database_service.session_repository = repository;
+        }
+        let database_service = Arc::new(database_service);
+        let jwt_service = self.jwt_service.unwrap_or_else(|| JwtService::new(&cfg.key_path));
+        let metrics_service = MetricsService::new();
+        let sse_service = SSEService::new(cfg.sse_replay_buffer as usize);
+        let ws_service = WsService::new();
+        let user_service = UserService::new(database_service.clone(), jwt_service.clone(), configuration.clone(), metrics_service.clone(), sse_service.clone(), ws_service.clone());
+        let notification_service = NotificationService::new();
+        let user_transfer_service = UserTransferService::new(database_service.clone(), notification_service.clone());
+        let avatar_service = AvatarService::new(database_service.clone(), cfg.clone());
+        let contact_service = ContactService::new(database_service.clone(), notification_service.clone());
+        let passwordless_service = PasswordlessService::new(database_service.clone(), notification_service.clone(), configuration.clone(), metrics_service.clone());
+        let backup_service = BackupService::new(database_service.clone(), configuration.clone());
+        let job_service = JobService::new(database_service.clone(), metrics_service.clone());
+        job_service.register_default_jobs(&cfg, &backup_service).await?;
       
         let services = Services
         {
             database_service,
             jwt_service,
-            user_service
+            user_service,
+            notification_service,
+            user_transfer_service,
+            avatar_service,
+            contact_service,
+            passwordless_service,
+            metrics_service,
+            rate_limit_service: RateLimitService::new(),
+            backup_service,
+            job_service,
+            sse_service,
+            ws_service
         };
-        Ok(Self
+        Ok(AppState
         {
             services,
-            configuration: cfg,
-            errors: Mutex::new(Vec::new())
+            configuration,
+            errors: Mutex::new(Vec::new()),
+            shutdown: CancellationToken::new()
         })
     }
-    pub fn get_services(&self) -> &Services
-    {
-        &self.services
-    }
-}
\ No newline at end of file
+}